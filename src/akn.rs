@@ -0,0 +1,514 @@
+//! 判決文をAkoma Ntoso（法令・判決のXML形式）の`<judgment>`文書に変換する
+//!
+//! 出力する要素は必要な子要素や属性を持っている必要があるため、書き出す前に[`validate`]で
+//! Akoma Ntoso 3.0のスキーマのうちこのモジュールが使う部分と照合する
+
+use crate::{
+  document::{self, Block},
+  metadata,
+  sections::SectionKind,
+};
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+
+const NAMESPACE: &str = "http://docs.oasis-open.org/legaldocml/ns/akn/3.0";
+
+/// 文書を作成した主体として`source`属性で参照するID
+const SOURCE_ID: &str = "pdf2txt_precedent";
+
+/// 判決をした裁判所として`FRBRauthor`で参照するID
+const COURT_ID: &str = "court";
+
+#[derive(Debug, Clone)]
+enum Node {
+  Element(Element),
+  Text(String),
+}
+
+#[derive(Debug, Clone)]
+struct Element {
+  name: &'static str,
+  attrs: Vec<(&'static str, String)>,
+  children: Vec<Node>,
+}
+
+impl Element {
+  fn new(name: &'static str) -> Self {
+    Element {
+      name,
+      attrs: Vec::new(),
+      children: Vec::new(),
+    }
+  }
+
+  fn attr(mut self, key: &'static str, value: &str) -> Self {
+    self.attrs.push((key, value.to_string()));
+    self
+  }
+
+  fn child(mut self, child: Element) -> Self {
+    self.children.push(Node::Element(child));
+    self
+  }
+
+  fn text(mut self, text: &str) -> Self {
+    self.children.push(Node::Text(text.to_string()));
+    self
+  }
+
+  fn push(&mut self, child: Element) {
+    self.children.push(Node::Element(child));
+  }
+
+  fn elements(&self) -> impl Iterator<Item = &Element> {
+    self.children.iter().filter_map(|node| match node {
+      Node::Element(e) => Some(e),
+      Node::Text(_) => None,
+    })
+  }
+
+  fn has_text(&self) -> bool {
+    self
+      .children
+      .iter()
+      .any(|node| matches!(node, Node::Text(_)))
+  }
+}
+
+/// 要素ごとの内容の決まり
+struct Rule {
+  name: &'static str,
+  /// 子要素として使える要素 `ordered`ならこの順に並べる必要がある
+  children: &'static [&'static str],
+  ordered: bool,
+  /// 必ず含む子要素
+  required_children: &'static [&'static str],
+  /// 子要素を一つ以上含む必要があるかどうか
+  non_empty: bool,
+  required_attrs: &'static [&'static str],
+  allows_text: bool,
+}
+
+const fn rule(name: &'static str, children: &'static [&'static str]) -> Rule {
+  Rule {
+    name,
+    children,
+    ordered: false,
+    required_children: &[],
+    non_empty: false,
+    required_attrs: &[],
+    allows_text: false,
+  }
+}
+
+const FRBR_WORK: [&str; 5] = [
+  "FRBRthis",
+  "FRBRuri",
+  "FRBRdate",
+  "FRBRauthor",
+  "FRBRcountry",
+];
+const FRBR_EXPRESSION: [&str; 5] = [
+  "FRBRthis",
+  "FRBRuri",
+  "FRBRdate",
+  "FRBRauthor",
+  "FRBRlanguage",
+];
+const FRBR_MANIFESTATION: [&str; 4] = ["FRBRthis", "FRBRuri", "FRBRdate", "FRBRauthor"];
+const IDENTIFICATION: [&str; 3] = ["FRBRWork", "FRBRExpression", "FRBRManifestation"];
+const JUDGMENT_BODY: [&str; 4] = ["introduction", "background", "motivation", "decision"];
+
+/// Akoma Ntoso 3.0のスキーマのうち、このモジュールが出力する要素の決まり
+const RULES: [Rule; 28] = [
+  Rule {
+    required_children: &["judgment"],
+    ..rule("akomaNtoso", &["judgment"])
+  },
+  Rule {
+    ordered: true,
+    required_children: &["meta", "judgmentBody"],
+    required_attrs: &["name"],
+    ..rule(
+      "judgment",
+      &["meta", "header", "judgmentBody", "attachments"],
+    )
+  },
+  Rule {
+    ordered: true,
+    required_children: &["identification"],
+    ..rule("meta", &["identification", "references"])
+  },
+  Rule {
+    ordered: true,
+    required_children: &IDENTIFICATION,
+    required_attrs: &["source"],
+    ..rule("identification", &IDENTIFICATION)
+  },
+  Rule {
+    ordered: true,
+    required_children: &FRBR_WORK,
+    ..rule("FRBRWork", &FRBR_WORK)
+  },
+  Rule {
+    ordered: true,
+    required_children: &FRBR_EXPRESSION,
+    ..rule("FRBRExpression", &FRBR_EXPRESSION)
+  },
+  Rule {
+    ordered: true,
+    required_children: &FRBR_MANIFESTATION,
+    ..rule("FRBRManifestation", &FRBR_MANIFESTATION)
+  },
+  Rule {
+    required_attrs: &["value"],
+    ..rule("FRBRthis", &[])
+  },
+  Rule {
+    required_attrs: &["value"],
+    ..rule("FRBRuri", &[])
+  },
+  Rule {
+    required_attrs: &["date", "name"],
+    ..rule("FRBRdate", &[])
+  },
+  Rule {
+    required_attrs: &["href"],
+    ..rule("FRBRauthor", &[])
+  },
+  Rule {
+    required_attrs: &["value"],
+    ..rule("FRBRcountry", &[])
+  },
+  Rule {
+    required_attrs: &["language"],
+    ..rule("FRBRlanguage", &[])
+  },
+  Rule {
+    non_empty: true,
+    required_attrs: &["source"],
+    ..rule("references", &["TLCOrganization"])
+  },
+  Rule {
+    required_attrs: &["eId", "href", "showAs"],
+    ..rule("TLCOrganization", &[])
+  },
+  Rule {
+    non_empty: true,
+    ..rule("header", &["p"])
+  },
+  Rule {
+    non_empty: true,
+    ..rule("judgmentBody", &JUDGMENT_BODY)
+  },
+  Rule {
+    non_empty: true,
+    ..rule("introduction", &["p"])
+  },
+  Rule {
+    non_empty: true,
+    ..rule("background", &["p"])
+  },
+  Rule {
+    non_empty: true,
+    ..rule("motivation", &["p"])
+  },
+  Rule {
+    non_empty: true,
+    ..rule("decision", &["p"])
+  },
+  Rule {
+    non_empty: true,
+    ..rule("mainBody", &["p"])
+  },
+  Rule {
+    allows_text: true,
+    ..rule("p", &["docNumber", "eop"])
+  },
+  Rule {
+    allows_text: true,
+    ..rule("docNumber", &[])
+  },
+  rule("eop", &[]),
+  Rule {
+    non_empty: true,
+    ..rule("attachments", &["attachment"])
+  },
+  Rule {
+    required_children: &["doc"],
+    ..rule("attachment", &["doc"])
+  },
+  Rule {
+    ordered: true,
+    required_children: &["meta", "mainBody"],
+    required_attrs: &["name"],
+    ..rule("doc", &["meta", "mainBody"])
+  },
+];
+
+/// 要素とその子孫がスキーマの決まりに従っているかを調べる
+fn validate(element: &Element) -> Result<()> {
+  let name = element.name;
+  let rule = RULES
+    .iter()
+    .find(|rule| rule.name == name)
+    .ok_or_else(|| anyhow!("Akoma Ntoso: 未対応の要素<{name}>"))?;
+  if let Some(attr) = rule
+    .required_attrs
+    .iter()
+    .find(|attr| element.attrs.iter().all(|(key, _)| key != *attr))
+  {
+    return Err(anyhow!("Akoma Ntoso: <{name}>に{attr}属性が無い"));
+  }
+  if element.has_text() && !rule.allows_text {
+    return Err(anyhow!("Akoma Ntoso: <{name}>はテキストを含められない"));
+  }
+  let mut last_index = 0;
+  for child in element.elements() {
+    let index = rule
+      .children
+      .iter()
+      .position(|c| *c == child.name)
+      .ok_or_else(|| anyhow!("Akoma Ntoso: <{name}>は<{}>を含められない", child.name))?;
+    if rule.ordered && index < last_index {
+      return Err(anyhow!(
+        "Akoma Ntoso: <{name}>の<{}>の位置が誤っている",
+        child.name
+      ));
+    }
+    last_index = index;
+    validate(child)?;
+  }
+  if let Some(required) = rule
+    .required_children
+    .iter()
+    .find(|c| element.elements().all(|e| e.name != **c))
+  {
+    return Err(anyhow!("Akoma Ntoso: <{name}>に<{required}>が無い"));
+  }
+  if rule.non_empty && element.elements().next().is_none() {
+    return Err(anyhow!("Akoma Ntoso: <{name}>が空"));
+  }
+  Ok(())
+}
+
+fn escape(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+/// 段落とテキストを含む要素は一行に、それ以外は子要素ごとに字下げして書き出す
+fn write_element(element: &Element, depth: usize, s: &mut String) {
+  let indent = "  ".repeat(depth);
+  s.push_str(&format!("{indent}<{}", element.name));
+  for (key, value) in &element.attrs {
+    s.push_str(&format!(" {key}=\"{}\"", escape(value)));
+  }
+  if element.children.is_empty() {
+    s.push_str("/>\n");
+    return;
+  }
+  s.push('>');
+  if element.has_text() || element.name == "p" {
+    write_inline(element, s);
+  } else {
+    s.push('\n');
+    for child in element.elements() {
+      write_element(child, depth + 1, s);
+    }
+    s.push_str(&indent);
+  }
+  s.push_str(&format!("</{}>\n", element.name));
+}
+
+fn write_inline(element: &Element, s: &mut String) {
+  for node in &element.children {
+    match node {
+      Node::Text(text) => s.push_str(&escape(text)),
+      Node::Element(child) if child.children.is_empty() => {
+        s.push_str(&format!("<{}/>", child.name));
+      }
+      Node::Element(child) => {
+        s.push_str(&format!("<{}>", child.name));
+        write_inline(child, s);
+        s.push_str(&format!("</{}>", child.name));
+      }
+    }
+  }
+}
+
+fn identification(work_uri: &str, date: &str) -> Element {
+  let expression_uri = format!("{work_uri}/jpn@");
+  let manifestation_uri = format!("{expression_uri}/main.xml");
+  let frbr = |name, uri: &str| {
+    Element::new(name)
+      .child(Element::new("FRBRthis").attr("value", uri))
+      .child(Element::new("FRBRuri").attr("value", uri))
+      .child(
+        Element::new("FRBRdate")
+          .attr("date", date)
+          .attr("name", "judgment"),
+      )
+      .child(Element::new("FRBRauthor").attr("href", &format!("#{COURT_ID}")))
+  };
+  Element::new("identification")
+    .attr("source", &format!("#{SOURCE_ID}"))
+    .child(frbr("FRBRWork", work_uri).child(Element::new("FRBRcountry").attr("value", "jp")))
+    .child(
+      frbr("FRBRExpression", &expression_uri)
+        .child(Element::new("FRBRlanguage").attr("language", "jpn")),
+    )
+    .child(frbr("FRBRManifestation", &manifestation_uri))
+}
+
+fn references() -> Element {
+  Element::new("references")
+    .attr("source", &format!("#{SOURCE_ID}"))
+    .child(
+      Element::new("TLCOrganization")
+        .attr("eId", SOURCE_ID)
+        .attr("href", &format!("/ontology/organization/{SOURCE_ID}"))
+        .attr("showAs", SOURCE_ID),
+    )
+    .child(
+      Element::new("TLCOrganization")
+        .attr("eId", COURT_ID)
+        .attr("href", "/ontology/organization/jp/court")
+        .attr("showAs", "裁判所"),
+    )
+}
+
+/// ページの終わりの目印`<eop/>`を直前の段落に付ける 段落が無ければ次の段落の先頭に付ける
+fn push_paragraph(container: &mut Element, pending_eop: &mut bool, p: Element) {
+  let mut p = p;
+  if *pending_eop {
+    p.children.insert(0, Node::Element(Element::new("eop")));
+    *pending_eop = false;
+  }
+  container.push(p);
+}
+
+fn mark_page_end(container: &mut Element, pending_eop: &mut bool) {
+  match container.children.last_mut() {
+    Some(Node::Element(p)) if p.name == "p" => p.push(Element::new("eop")),
+    _ => *pending_eop = true,
+  }
+}
+
+fn paragraph(level: usize, number: Option<String>, text: &str) -> Element {
+  let text = match number {
+    Some(number) => format!("{number}　{text}"),
+    None => text.to_string(),
+  };
+  Element::new("p")
+    .attr("class", &format!("level-{level}"))
+    .text(&text)
+}
+
+/// 主文より前を`<header>`に、主文を`<decision>`に、事実及び理由を`<motivation>`に、
+/// 別紙を`<attachments>`の文書にした`<judgment>`文書を作る
+///
+/// `metadata`は一覧のJSONファイルの事件の情報で、`case_number`を`<docNumber>`に使う
+pub fn render(text: &str, name: &str, metadata: &Map<String, Value>) -> Result<String> {
+  let date = metadata::judgment_date(name, metadata)
+    .ok_or_else(|| anyhow!("Akoma Ntoso: 判決日が分からない({name})"))?;
+  let work_uri = format!("/akn/jp/judgment/{date}/{}", name.replace(' ', "_"));
+  let mut header = Element::new("header");
+  if let Some(case_number) = metadata.get("case_number").and_then(|v| v.as_str()) {
+    header.push(Element::new("p").child(Element::new("docNumber").text(case_number)));
+  }
+  let mut body = Element::new("judgmentBody");
+  let mut appendices: Vec<Element> = Vec::new();
+  // 現在の部分の要素 主文より前の部分はNone
+  let mut current: Option<Element> = None;
+  let mut pending_eop = false;
+  let finish =
+    |current: Option<Element>, body: &mut Element, appendices: &mut Vec<Element>| match current {
+      Some(e) if e.name == "mainBody" => appendices.push(e),
+      Some(e) => body.push(e),
+      None => (),
+    };
+  for block in document::blocks(text) {
+    match block {
+      Block::PageBreak => mark_page_end(current.as_mut().unwrap_or(&mut header), &mut pending_eop),
+      Block::Heading { kind, text } => {
+        finish(current.take(), &mut body, &mut appendices);
+        let element_name = match kind {
+          SectionKind::Header => "introduction",
+          SectionKind::MainText => "decision",
+          SectionKind::FactsAndReasons => "motivation",
+          SectionKind::Appendix => "mainBody",
+        };
+        let mut section = Element::new(element_name);
+        push_paragraph(
+          &mut section,
+          &mut pending_eop,
+          Element::new("p").attr("class", "heading").text(&text),
+        );
+        current = Some(section);
+      }
+      Block::Line(line) => push_paragraph(
+        current.as_mut().unwrap_or(&mut header),
+        &mut pending_eop,
+        Element::new("p").text(&line),
+      ),
+      Block::Paragraph {
+        level,
+        number,
+        text,
+      } => push_paragraph(
+        current.as_mut().unwrap_or(&mut header),
+        &mut pending_eop,
+        paragraph(level, number, &text),
+      ),
+    }
+  }
+  finish(current.take(), &mut body, &mut appendices);
+  if body.children.is_empty() {
+    // 主文などの見出しが見つからなかった場合は全文を導入部分とする
+    let mut introduction = Element::new("introduction");
+    let (doc_number, lines): (Vec<_>, Vec<_>) = header.children.drain(..).partition(
+      |node| matches!(node, Node::Element(e) if e.elements().any(|c| c.name == "docNumber")),
+    );
+    header.children = doc_number;
+    introduction.children = lines;
+    if introduction.children.is_empty() {
+      introduction.push(Element::new("p"));
+    }
+    body.push(introduction);
+  }
+  let mut judgment = Element::new("judgment").attr("name", "judgment").child(
+    Element::new("meta")
+      .child(identification(&work_uri, &date))
+      .child(references()),
+  );
+  if header.elements().next().is_some() {
+    judgment.push(header);
+  }
+  judgment.push(body);
+  if !appendices.is_empty() {
+    let mut attachments = Element::new("attachments");
+    for (i, main_body) in appendices.into_iter().enumerate() {
+      let doc_uri = format!("{work_uri}/attachment_{}", i + 1);
+      attachments.push(
+        Element::new("attachment").child(
+          Element::new("doc")
+            .attr("name", "appendix")
+            .child(Element::new("meta").child(identification(&doc_uri, &date)))
+            .child(main_body),
+        ),
+      );
+    }
+    judgment.push(attachments);
+  }
+  let root = Element::new("akomaNtoso")
+    .attr("xmlns", NAMESPACE)
+    .child(judgment);
+  validate(&root)?;
+  let mut s = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+  write_element(&root, 0, &mut s);
+  Ok(s)
+}
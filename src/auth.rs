@@ -0,0 +1,60 @@
+//! ミラーサーバなどの認証が必要なホストに送る認証情報
+
+use reqwest::{RequestBuilder, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// 1つのホストの認証情報
+///
+/// 設定ファイルでは`{"token": "..."}`か`{"basic": "user:pass"}`と書く
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Credential {
+  /// `Authorization: Bearer`で送るトークン
+  Token(String),
+  /// `user:pass`の形式のBasic認証のユーザー名とパスワード
+  Basic(String),
+}
+
+/// ホスト名（`host`か`host:port`）ごとの認証情報
+#[derive(Debug, Clone, Default)]
+pub struct Credentials(BTreeMap<String, Credential>);
+
+impl Credentials {
+  pub fn insert(&mut self, host: &str, credential: Credential) {
+    self.0.insert(host.to_ascii_lowercase(), credential);
+  }
+
+  fn find(&self, url: &str) -> Option<&Credential> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?.to_ascii_lowercase();
+    let host_port = url.port().map(|port| format!("{host}:{port}"));
+    host_port
+      .and_then(|host_port| self.0.get(&host_port))
+      .or_else(|| self.0.get(&host))
+  }
+
+  /// `url`のホストに認証情報があれば、リクエストに付ける
+  ///
+  /// 他のホストへのリクエストには付けないため、認証情報が別のサーバに送られることはない
+  pub fn apply(&self, url: &str, request: RequestBuilder) -> RequestBuilder {
+    match self.find(url) {
+      Some(Credential::Token(token)) => request.bearer_auth(token),
+      Some(Credential::Basic(user_pass)) => match user_pass.split_once(':') {
+        Some((user, pass)) => request.basic_auth(user, Some(pass)),
+        None => request.basic_auth(user_pass, None::<&str>),
+      },
+      None => request,
+    }
+  }
+}
+
+/// URLのホスト名（ポート番号があれば`host:port`）
+pub fn host_of(url: &str) -> Option<String> {
+  let url = Url::parse(url).ok()?;
+  let host = url.host_str()?;
+  Some(match url.port() {
+    Some(port) => format!("{host}:{port}"),
+    None => host.to_string(),
+  })
+}
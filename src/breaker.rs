@@ -0,0 +1,92 @@
+//! `--abort-on-failure-rate`の際に、最近処理した事件のうち失敗した割合が上限を超えたら残りの事件の処理を止める
+//!
+//! 失敗が続く場合は、ほとんどがサイトにアクセスを拒まれているか、tesseractなどの依存するコマンドが壊れているためで、
+//! 処理を続けても時間を無駄にするだけになる
+
+use crate::messages::Message;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::{
+  collections::VecDeque,
+  str::FromStr,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+  },
+};
+use tokio::sync::Notify;
+
+/// 失敗した割合の上限 `20%`か`0.2`の形式で指定する
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct FailureRate(f64);
+
+impl FromStr for FailureRate {
+  type Err = anyhow::Error;
+  fn from_str(s: &str) -> Result<Self> {
+    let rate = match s.strip_suffix('%') {
+      Some(percent) => percent.trim().parse::<f64>()? / 100.0,
+      None => s.trim().parse::<f64>()?,
+    };
+    if !(0.0..=1.0).contains(&rate) {
+      return Err(anyhow!("0%から100%の間で指定する: {s}"));
+    }
+    Ok(FailureRate(rate))
+  }
+}
+
+pub struct Breaker {
+  rate: FailureRate,
+  /// 割合を求める事件の数
+  window: usize,
+  /// 最近結果が決まった事件が失敗したかどうか
+  recent: Mutex<VecDeque<bool>>,
+  tripped: AtomicBool,
+  notify: Notify,
+}
+
+impl Breaker {
+  pub fn new(rate: FailureRate, window: usize) -> Self {
+    Breaker {
+      rate,
+      window: window.max(1),
+      recent: Mutex::new(VecDeque::new()),
+      tripped: AtomicBool::new(false),
+      notify: Notify::new(),
+    }
+  }
+
+  /// 処理した事件の結果を記録し、最近の`window`件のうち失敗した割合が上限を超えたら止める
+  pub fn record(&self, failed: bool) {
+    let mut recent = self.recent.lock().unwrap_or_else(|err| err.into_inner());
+    if recent.len() == self.window {
+      recent.pop_front();
+    }
+    recent.push_back(failed);
+    let failures = recent.iter().filter(|failed| **failed).count();
+    if recent.len() == self.window
+      && failures as f64 / self.window as f64 > self.rate.0
+      && !self.tripped.swap(true, Ordering::SeqCst)
+    {
+      crate::logging::error("Breaker", None, self.message());
+      self.notify.notify_waiters();
+    }
+  }
+
+  pub fn is_tripped(&self) -> bool {
+    self.tripped.load(Ordering::SeqCst)
+  }
+
+  /// 止めるまで待つ
+  pub async fn wait(&self) {
+    let notified = self.notify.notified();
+    if self.is_tripped() {
+      return;
+    }
+    notified.await;
+  }
+
+  /// 処理を止めた原因
+  pub fn message(&self) -> Message<'static> {
+    Message::FailureRateExceeded(self.rate.0 * 100.0, self.window)
+  }
+}
@@ -0,0 +1,357 @@
+//! 一時フォルダに保存するキャッシュファイルの読み書きを行う
+
+use crate::{logging, output};
+use anyhow::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+  collections::BTreeMap,
+  fmt,
+  io::Read,
+  path::Path,
+  time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+  fs::{self, File},
+  io::{AsyncReadExt, AsyncWriteExt},
+};
+use tokio_stream::StreamExt;
+
+/// 圧縮済みのキャッシュファイルに付ける拡張子
+const ZSTD_EXTENSION: &str = "zst";
+
+/// zstdの圧縮レベル
+const ZSTD_LEVEL: i32 = 3;
+
+/// 圧縮されたキャッシュファイルのpath
+pub fn compressed_path(path: &str) -> String {
+  format!("{path}.{ZSTD_EXTENSION}")
+}
+
+/// 圧縮の有無を問わずキャッシュファイルが存在するかどうか
+pub fn exists(path: &str) -> bool {
+  Path::new(path).exists() || Path::new(&compressed_path(path)).exists()
+}
+
+/// 途中で中断されても書きかけのファイルが残らないように、`{path}.part`に書いてから置き換える
+async fn write_file(path: &str, bytes: &[u8]) -> Result<()> {
+  let part_path = format!("{path}.part");
+  let mut f = File::create(&part_path).await?;
+  f.write_all(bytes).await?;
+  f.flush().await?;
+  fs::rename(&part_path, path).await?;
+  Ok(())
+}
+
+/// ファイルをzstdで圧縮したものに置き換える
+pub async fn compress(path: &str) -> Result<()> {
+  if !Path::new(path).exists() {
+    return Ok(());
+  }
+  let bytes = fs::read(path).await?;
+  let compressed = zstd::encode_all(bytes.as_slice(), ZSTD_LEVEL)?;
+  write_file(&compressed_path(path), &compressed).await?;
+  fs::remove_file(path).await?;
+  Ok(())
+}
+
+/// 圧縮されたキャッシュファイルしか無い場合に元のpathへ展開する
+pub async fn decompress(path: &str) -> Result<()> {
+  let zst_path = compressed_path(path);
+  if Path::new(path).exists() || !Path::new(&zst_path).exists() {
+    return Ok(());
+  }
+  let bytes = fs::read(&zst_path).await?;
+  let decompressed = zstd::decode_all(bytes.as_slice())?;
+  write_file(path, &decompressed).await
+}
+
+/// 圧縮の有無を問わずキャッシュファイルを読み込む
+pub async fn read(path: &str) -> Result<Vec<u8>> {
+  if Path::new(path).exists() {
+    Ok(fs::read(path).await?)
+  } else {
+    let bytes = fs::read(compressed_path(path)).await?;
+    Ok(zstd::decode_all(bytes.as_slice())?)
+  }
+}
+
+/// 圧縮の有無を問わずキャッシュファイルを文字列として読み込む
+pub async fn read_to_string(path: &str) -> Result<String> {
+  if Path::new(path).exists() {
+    Ok(fs::read_to_string(path).await?)
+  } else {
+    let bytes = fs::read(compressed_path(path)).await?;
+    let decompressed = zstd::decode_all(bytes.as_slice())?;
+    Ok(String::from_utf8(decompressed)?)
+  }
+}
+
+/// キャッシュの一覧を記録するファイルの名前
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// PDFファイルをダウンロードした際の情報
+///
+/// 後から取得した日時や取得元が変わったかどうかを調べられるように記録する
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fetch {
+  /// ダウンロードに使ったURL
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub source: Option<String>,
+  /// HTTPのステータスコード
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub status: Option<u16>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub content_length: Option<u64>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub content_type: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub etag: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub last_modified: Option<String>,
+  /// ダウンロードした日時（UTC、RFC 3339）
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub fetched_at: Option<String>,
+}
+
+/// 現在の日時のUTCでのRFC 3339形式の文字列
+pub fn now_rfc3339() -> String {
+  rfc3339(SystemTime::now())
+}
+
+/// 日時のUTCでのRFC 3339形式の文字列
+pub fn rfc3339(time: SystemTime) -> String {
+  let secs = time
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(0);
+  let days = (secs / 86400) as i64;
+  let time = secs % 86400;
+  // 1970年1月1日からの日数をグレゴリオ暦の年月日にする
+  let z = days + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z.rem_euclid(146097);
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + i64::from(month <= 2);
+  format!(
+    "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+    time / 3600,
+    time % 3600 / 60,
+    time % 60
+  )
+}
+
+/// キャッシュしたPDFファイルの情報
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+  /// 圧縮前のPDFファイルのSHA-256
+  pub sha256: String,
+  /// 圧縮前のPDFファイルのバイト数
+  pub size: u64,
+  #[serde(flatten)]
+  pub fetch: Fetch,
+}
+
+/// 一時フォルダにキャッシュしたPDFファイルの一覧
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+  pub entries: BTreeMap<String, ManifestEntry>,
+}
+
+fn manifest_path(tmp_name: &str) -> String {
+  format!("{tmp_name}/{MANIFEST_FILE_NAME}")
+}
+
+fn pdf_path(tmp_name: &str, name: &str) -> String {
+  format!("{tmp_name}/{name}.pdf")
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+  Sha256::digest(bytes)
+    .iter()
+    .map(|b| format!("{b:02x}"))
+    .collect()
+}
+
+impl Manifest {
+  /// 一時フォルダにある一覧を読み込む 無い場合は空の一覧を返す
+  pub async fn load(tmp_name: &str) -> Result<Self> {
+    let path = manifest_path(tmp_name);
+    if Path::new(&path).exists() {
+      let s = fs::read_to_string(&path).await?;
+      Ok(serde_json::from_str(&s)?)
+    } else {
+      Ok(Self::default())
+    }
+  }
+
+  /// 一時フォルダに一覧を書き出す
+  pub async fn save(&self, tmp_name: &str) -> Result<()> {
+    let s = serde_json::to_string_pretty(self)?;
+    write_file(&manifest_path(tmp_name), s.as_bytes()).await
+  }
+
+  /// キャッシュしたPDFファイルのハッシュ値を計算して記録する
+  pub async fn record(&mut self, tmp_name: &str, name: &str, fetch: Fetch) -> Result<()> {
+    let bytes = read(&pdf_path(tmp_name, name)).await?;
+    let entry = ManifestEntry {
+      sha256: sha256_hex(&bytes),
+      size: bytes.len() as u64,
+      fetch,
+    };
+    self.entries.insert(name.to_string(), entry);
+    Ok(())
+  }
+}
+
+async fn remove_if_exists(path: &str) -> Result<()> {
+  if Path::new(path).exists() {
+    logging::info("Remove", None, path);
+    fs::remove_file(path).await?;
+  }
+  Ok(())
+}
+
+/// 圧縮の有無を問わずキャッシュファイルを削除する
+pub async fn remove(path: &str) -> Result<()> {
+  remove_if_exists(path).await?;
+  remove_if_exists(&compressed_path(path)).await
+}
+
+/// PDFファイルのヘッダを探す範囲のバイト数
+const HEADER_SEARCH_BYTES: usize = 1024;
+
+/// キャッシュしたファイルの中身の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+  Pdf,
+  /// 404やメンテナンス中のページなど、PDFファイルの代わりに保存されたHTML
+  Html,
+  Empty,
+  Unknown,
+}
+
+impl fmt::Display for Kind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let s = match self {
+      Kind::Pdf => "pdf",
+      Kind::Html => "html",
+      Kind::Empty => "empty",
+      Kind::Unknown => "unknown",
+    };
+    write!(f, "{s}")
+  }
+}
+
+/// 先頭の`%PDF-`のヘッダなどからファイルの中身の種類を調べる
+///
+/// PDFファイルのヘッダは先頭の1024バイトの中にあればよいとされているため、その範囲を探す
+pub fn classify(bytes: &[u8]) -> Kind {
+  let head = &bytes[..bytes.len().min(HEADER_SEARCH_BYTES)];
+  if head.windows(5).any(|w| w == b"%PDF-") {
+    return Kind::Pdf;
+  }
+  let text = String::from_utf8_lossy(head).to_ascii_lowercase();
+  let text = text.trim_start_matches('\u{FEFF}').trim();
+  if text.is_empty() {
+    Kind::Empty
+  } else if text.starts_with('<') && (text.contains("<html") || text.contains("<!doctype")) {
+    Kind::Html
+  } else {
+    Kind::Unknown
+  }
+}
+
+/// 圧縮の有無を問わずキャッシュファイルの先頭の`len`バイトまでを読み込む 圧縮したファイルは先頭だけを展開する
+async fn read_head(path: &str, len: usize) -> Result<Vec<u8>> {
+  let mut head = Vec::with_capacity(len);
+  if Path::new(path).exists() {
+    File::open(path)
+      .await?
+      .take(len as u64)
+      .read_to_end(&mut head)
+      .await?;
+  } else {
+    let file = std::fs::File::open(compressed_path(path))?;
+    zstd::stream::read::Decoder::new(file)?
+      .take(len as u64)
+      .read_to_end(&mut head)?;
+  }
+  Ok(head)
+}
+
+/// 圧縮の有無を問わずキャッシュファイルの中身の種類を調べる
+///
+/// 全ての事件で実行するため、ファイル全体ではなく先頭の`HEADER_SEARCH_BYTES`バイトだけを読む
+pub async fn classify_file(path: &str) -> Result<Kind> {
+  Ok(classify(&read_head(path, HEADER_SEARCH_BYTES).await?))
+}
+
+/// キャッシュしたPDFファイルを一覧のハッシュ値と照合し、壊れているもの・PDFファイルではないものと実体の無いものを取り除く
+pub async fn verify(tmp_name: &str) -> Result<()> {
+  let mut manifest = Manifest::load(tmp_name).await?;
+  let mut verified = BTreeMap::new();
+  let mut stream = tokio_stream::iter(manifest.entries);
+  while let Some((name, entry)) = stream.next().await {
+    let path = pdf_path(tmp_name, &name);
+    if !exists(&path) {
+      logging::info("Orphaned Entry", Some(&name), &name);
+      continue;
+    }
+    let bytes = read(&path).await?;
+    let kind = classify(&bytes);
+    if kind != Kind::Pdf {
+      logging::info("Invalid Cache", Some(&name), format!("{path}: {kind}"));
+      remove(&path).await?;
+    } else if bytes.len() as u64 == entry.size && sha256_hex(&bytes) == entry.sha256 {
+      verified.insert(name, entry);
+    } else {
+      logging::info("Corrupt Cache", Some(&name), &path);
+      remove(&path).await?;
+    }
+  }
+  manifest.entries = verified;
+  manifest.save(tmp_name).await
+}
+
+/// 出力が生成済みの事件について、一時フォルダの中間ファイルを削除する
+pub async fn gc(tmp_name: &str, output_name: &str) -> Result<()> {
+  let intermediate_re =
+    Regex::new(r"^(?P<name>.+?)(-\d+(-region-\d+)?)?\.(txt|jpg|pgm|tif|png|hocr)(\.zst)?$")
+      .expect("正規表現が不正");
+  let mut dir = fs::read_dir(tmp_name).await?;
+  while let Some(dir_entry) = dir.next_entry().await? {
+    let file_name = dir_entry.file_name().to_string_lossy().to_string();
+    let Some(name) = intermediate_re
+      .captures(&file_name)
+      .and_then(|caps| caps.name("name"))
+      .map(|m| m.as_str())
+    else {
+      continue;
+    };
+    if name.ends_with("_err") {
+      continue;
+    }
+    // `--mode both`の出力は、方法ごとのフォルダの全てに揃っていれば生成済みとする
+    let is_generated = output::exists_any_format(output_name, name)
+      || output::MODE_DIRS
+        .iter()
+        .all(|dir| output::exists_any_format(&format!("{output_name}/{dir}"), name));
+    if is_generated {
+      remove_if_exists(&dir_entry.path().to_string_lossy()).await?;
+    }
+  }
+  // キャッシュしたPDFファイルの一覧に無いPDFファイルの画像も削除する
+  let manifest = Manifest::load(tmp_name).await?;
+  let sha256s = manifest
+    .entries
+    .values()
+    .map(|entry| entry.sha256.clone())
+    .collect();
+  crate::images::gc(tmp_name, &sha256s).await
+}
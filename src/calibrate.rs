@@ -0,0 +1,118 @@
+//! 一覧から選んだ事件の最初のページに切り取る範囲を描き、範囲を確かめながら調整して設定ファイルの`crop`に書き出す
+//!
+//! 範囲を描いた画像を出力フォルダの`calibrate/`に書き出し、標準入力から新しい範囲を受け取って描き直すことを、
+//! 範囲が決まるまで繰り返す
+
+use crate::{convert_pdf_page, crop, fetch_pdf, logging, pdf_urls, Args, Context};
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+use std::io::Write;
+use tokio::{
+  fs,
+  io::{AsyncBufReadExt, BufReader},
+};
+
+/// 出力フォルダの中の、範囲を描いた画像を書き出すフォルダ
+const CALIBRATE_DIR: &str = "calibrate";
+
+/// 一覧のキーの順に並べた事件から、偏らないように等間隔で`samples`件を選ぶ
+fn choose_samples(names: &[String], samples: usize) -> Vec<&String> {
+  let samples = samples.clamp(1, names.len().max(1));
+  (0..samples)
+    .filter_map(|i| names.get(i * names.len() / samples))
+    .collect()
+}
+
+/// 設定ファイルの他の項目を残したまま`crop`を書き換える
+async fn write_crop(config_path: &str, crop: &crop::Crop) -> Result<()> {
+  let s = fs::read_to_string(config_path).await?;
+  let mut config: Map<String, Value> = serde_json::from_str(&s)?;
+  config.insert("crop".to_string(), Value::String(crop.to_string()));
+  let mut s = serde_json::to_string_pretty(&config)?;
+  s.push('\n');
+  fs::write(config_path, s).await?;
+  Ok(())
+}
+
+pub async fn run(samples: usize, args: &Args) -> Result<()> {
+  let config_path = args
+    .config
+    .as_deref()
+    .ok_or_else(|| anyhow!("calibrateには--configで書き出す設定ファイルを指定する"))?;
+  let input_file_path = args
+    .input
+    .as_ref()
+    .ok_or_else(|| anyhow!("calibrateには--inputで一覧のJSONファイルを指定する"))?;
+  if fs::metadata(config_path).await.is_err() {
+    fs::write(config_path, "{}\n").await?;
+  }
+  let calibrate_dir = format!("{}/{CALIBRATE_DIR}", args.output);
+  fs::create_dir_all(&args.tmp).await?;
+  fs::create_dir_all(&calibrate_dir).await?;
+  let context = Context::new(args).await?;
+  let mut crop = match args.crop {
+    Some(crop) => crop,
+    None => crate::config::Config::load(Some(config_path))
+      .await?
+      .crop
+      .unwrap_or_default(),
+  };
+  let input_json = fs::read_to_string(input_file_path).await?;
+  let input_json_lst: Map<String, Value> = serde_json::from_str(&input_json)?;
+  let mut names = input_json_lst.keys().cloned().collect::<Vec<_>>();
+  names.sort();
+  let mut pages = Vec::new();
+  for name in choose_samples(&names, samples) {
+    let file_name = format!("{}/{name}", args.tmp);
+    let is_downloads = fs::metadata(format!("{file_name}.pdf")).await.is_err();
+    fetch_pdf(
+      name,
+      &pdf_urls(&input_json_lst[name], args)?,
+      is_downloads,
+      args,
+      &context,
+    )
+    .await?;
+    if let Some(err_msg) = convert_pdf_page(&file_name, 1, args).await {
+      logging::warn("Calibrate", Some(name), format!("{name}: {err_msg}"));
+    }
+    pages.push((name, file_name));
+  }
+  let mut lines = BufReader::new(tokio::io::stdin()).lines();
+  loop {
+    for (name, file_name) in &pages {
+      let output_path = format!("{calibrate_dir}/{name}.jpg");
+      let rect = crop
+        .resolve_page(&format!("{file_name}.pdf"), 1, args.dpi, args.scale_crop)
+        .await;
+      let page_path = format!("{file_name}-1.{}", args.image_format.extension());
+      match crop::overlay(&page_path, &output_path, &rect).await {
+        None => println!("[Calibrate] {output_path}: {rect}"),
+        Some(err_msg) => logging::warn("Calibrate", Some(name), format!("{name}: {err_msg}")),
+      }
+    }
+    print!("crop {crop}（Enterで確定、{{幅}}x{{高さ}}+{{左}}+{{上}}で描き直し、qで中止）> ");
+    std::io::stdout().flush()?;
+    let Some(line) = lines.next_line().await? else {
+      break;
+    };
+    match line.trim() {
+      "" => {
+        write_crop(config_path, &crop).await?;
+        println!("[END] calibrate: {config_path}: crop {crop}");
+        break;
+      }
+      "q" => break,
+      geometry => match geometry.parse() {
+        Ok(new_crop) => crop = new_crop,
+        Err(err) => println!("{err}"),
+      },
+    }
+  }
+  for (_, file_name) in &pages {
+    fs::remove_file(format!("{file_name}-1.{}", args.image_format.extension()))
+      .await
+      .ok();
+  }
+  Ok(())
+}
@@ -0,0 +1,32 @@
+//! 事件名か事件番号を1行に1件ずつ書いたリストファイルを読み込む
+
+use anyhow::Result;
+use std::collections::HashSet;
+use tokio::fs;
+
+/// 事件名（一覧のJSONファイルのキー）か事件番号の集合
+#[derive(Debug, Clone, Default)]
+pub struct CaseList {
+  entries: HashSet<String>,
+}
+
+impl CaseList {
+  /// 空行と`#`で始まる行は無視する pathが無い場合は空のリストを返す
+  pub async fn load(path: Option<&str>) -> Result<Self> {
+    let Some(path) = path else {
+      return Ok(CaseList::default());
+    };
+    let s = fs::read_to_string(path).await?;
+    let entries = s
+      .lines()
+      .map(|line| line.trim())
+      .filter(|line| !line.is_empty() && !line.starts_with('#'))
+      .map(|line| line.to_string())
+      .collect();
+    Ok(CaseList { entries })
+  }
+
+  pub fn contains(&self, name: &str, case_number: &str) -> bool {
+    self.entries.contains(name) || self.entries.contains(case_number)
+  }
+}
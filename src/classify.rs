@@ -0,0 +1,162 @@
+//! `--classify-pdf`の際に、PDFファイルがスキャンしたものか、文書作成ソフトから直接作られたものか、その混在かを判定する
+//!
+//! ページごとに`pdftotext`で取り出せる文字の数と`pdfimages -list`の画像の有無を調べ、
+//! 文字がほとんど無く画像のあるページをスキャンしたページとみなす。
+//! スキャナーやOCRソフトが重ねた透明なテキストしか無いPDFファイルは、フォントの一覧からスキャンしたものとする
+
+use crate::{fonts, pdftotext_pages};
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::{collections::HashSet, process::Output};
+use tokio::process::Command;
+
+/// テキストレイヤーがあるとみなす、ページの空白以外の文字の最小数
+///
+/// スキャンしたページに押された頁番号やスタンプのみのページを除くため、`ensemble`よりやや多めにする
+const MIN_TEXT_CHARS: usize = 30;
+
+/// OCRソフトが画像に重ねる透明なテキストのフォント
+const INVISIBLE_FONTS: [&str; 2] = ["GlyphLessFont", "OCRFont"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Kind {
+  /// 全てのページがスキャンしたもの
+  Scanned,
+  /// 全てのページにテキストレイヤーがある
+  BornDigital,
+  /// スキャンしたページとテキストレイヤーのあるページが混在する
+  Hybrid,
+}
+
+/// 事件ごとのJSONファイルに記録する判定の結果と根拠
+#[derive(Debug, Clone, Serialize)]
+pub struct Classification {
+  pub kind: Kind,
+  pub pages: usize,
+  /// テキストレイヤーのあるページの数
+  pub text_pages: usize,
+  /// 文字がほとんど無く、画像のあるページの数
+  pub scanned_pages: usize,
+  /// 使われているフォントの数
+  pub fonts: usize,
+}
+
+/// `pdfimages -list`の出力から、画像のあるページ番号を読む 透過用のマスクは数えない
+fn image_pages(text: &str) -> HashSet<usize> {
+  text
+    .lines()
+    .skip_while(|line| !line.starts_with("---"))
+    .skip(1)
+    .filter_map(|line| {
+      let mut fields = line.split_whitespace();
+      let page_num = fields.next()?.parse::<usize>().ok()?;
+      (fields.nth(1) == Some("image")).then_some(page_num)
+    })
+    .collect()
+}
+
+fn judge(
+  page_texts: &[&str],
+  image_pages: &HashSet<usize>,
+  fonts: &[fonts::Font],
+) -> Classification {
+  let mut text_pages = 0;
+  let mut scanned_pages = 0;
+  for (i, text) in page_texts.iter().enumerate() {
+    if text.chars().filter(|c| !c.is_whitespace()).count() >= MIN_TEXT_CHARS {
+      text_pages += 1;
+    } else if image_pages.contains(&(i + 1)) {
+      scanned_pages += 1;
+    }
+  }
+  let is_invisible_text = !fonts.is_empty()
+    && fonts
+      .iter()
+      .all(|font| INVISIBLE_FONTS.iter().any(|name| font.name.ends_with(name)));
+  let kind = if text_pages == 0 || is_invisible_text {
+    Kind::Scanned
+  } else if scanned_pages == 0 {
+    Kind::BornDigital
+  } else {
+    Kind::Hybrid
+  };
+  Classification {
+    kind,
+    pages: page_texts.len(),
+    text_pages,
+    scanned_pages,
+    fonts: fonts.len(),
+  }
+}
+
+fn stdout(program: &str, output: Output) -> Result<String> {
+  if !output.status.success() {
+    return Err(anyhow!(
+      "{program}: {}",
+      String::from_utf8_lossy(&output.stderr).trim()
+    ));
+  }
+  Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// `file_path_pdf`を判定する
+pub async fn classify(file_path_pdf: &str) -> Result<Classification> {
+  let output = Command::new("pdftotext")
+    .kill_on_drop(true)
+    .arg("-raw")
+    .arg(file_path_pdf)
+    .arg("-")
+    .output()
+    .await?;
+  let txt = stdout("pdftotext", output)?;
+  let output = Command::new("pdfimages")
+    .kill_on_drop(true)
+    .arg("-list")
+    .arg(file_path_pdf)
+    .output()
+    .await?;
+  let images = image_pages(&stdout("pdfimages", output)?);
+  let fonts = fonts::list(file_path_pdf).await?;
+  Ok(judge(&pdftotext_pages(&txt), &images, &fonts))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn judge_pages() {
+    let list = "\
+page   num  type   width height color comp bpc  enc interp  object ID x-ppi y-ppi size ratio
+--------------------------------------------------------------------------------------------
+   2     0 image    2480  3508  gray    1   8  jpeg   no         7  0   300   300  412K 4.8%
+   2     1 smask    2480  3508  gray    1   8  image  no         8  0   300   300  1K 0.1%
+   3     2 smask    2480  3508  gray    1   8  image  no         9  0   300   300  1K 0.1%
+";
+    let images = image_pages(list);
+    assert_eq!(images, HashSet::from([2]));
+    let font = |name: &str| fonts::Font {
+      name: name.to_string(),
+      font_type: "CID TrueType".to_string(),
+      encoding: "Identity-H".to_string(),
+      embedded: true,
+      unicode: true,
+    };
+    let text = "主文 本件控訴を棄却する。控訴費用は控訴人の負担とする。事実及び理由 第1 控訴の趣旨";
+    let digital = [font("ABCDEF+MS-Mincho")];
+    let classification = judge(&[text, "- 2 -", ""], &images, &digital);
+    assert_eq!(classification.kind, Kind::Hybrid);
+    assert_eq!(
+      (classification.text_pages, classification.scanned_pages),
+      (1, 1)
+    );
+    assert_eq!(
+      judge(&[text, ""], &HashSet::new(), &digital).kind,
+      Kind::BornDigital
+    );
+    assert_eq!(judge(&["", ""], &images, &[]).kind, Kind::Scanned);
+    let ocr = [font("GlyphLessFont")];
+    assert_eq!(judge(&[text, text], &images, &ocr).kind, Kind::Scanned);
+  }
+}
@@ -0,0 +1,126 @@
+//! `--mode cloud-vision`の際に、ページの画像をGoogle Cloud VisionのAPIに送ってOCRを行う
+//!
+//! APIキーはコマンドラインや設定ファイルに残さないよう、環境変数`GOOGLE_CLOUD_VISION_API_KEY`で渡す。
+//! 画像は`--dpi`の解像度のJPEGで送り、`--crop`などの画像の加工は行わない
+
+use crate::Message;
+use anyhow::{anyhow, Result};
+use reqwest::{header::CONTENT_TYPE, Client};
+use serde_json::{json, Value};
+use tokio::process::Command;
+
+/// APIキーを渡す環境変数
+pub const API_KEY_VAR: &str = "GOOGLE_CLOUD_VISION_API_KEY";
+
+const ENDPOINT: &str = "https://vision.googleapis.com/v1/images:annotate";
+
+pub fn api_key() -> Result<String> {
+  std::env::var(API_KEY_VAR).map_err(|_| anyhow!("{}", Message::CloudVisionKeyMissing(API_KEY_VAR)))
+}
+
+/// APIに画像を送る際のBase64（パディングあり）
+fn base64(bytes: &[u8]) -> String {
+  const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+  let mut s = String::with_capacity(bytes.len().div_ceil(3) * 4);
+  for chunk in bytes.chunks(3) {
+    let n = chunk
+      .iter()
+      .enumerate()
+      .fold(0u32, |n, (i, b)| n | u32::from(*b) << (16 - 8 * i));
+    for i in 0..4 {
+      if i <= chunk.len() {
+        s.push(TABLE[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+      } else {
+        s.push('=');
+      }
+    }
+  }
+  s
+}
+
+/// 文書向けの文字認識（`DOCUMENT_TEXT_DETECTION`）を日本語として行うリクエスト
+fn request_body(image: &[u8]) -> Value {
+  json!({
+    "requests": [{
+      "image": { "content": base64(image) },
+      "features": [{ "type": "DOCUMENT_TEXT_DETECTION" }],
+      "imageContext": { "languageHints": ["ja"] }
+    }]
+  })
+}
+
+/// レスポンスから認識したテキストを取り出す 文字の無いページでは`fullTextAnnotation`が無い
+fn response_text(response: &Value) -> Result<String> {
+  if let Some(message) = response["error"]["message"].as_str() {
+    return Err(anyhow!("cloud-vision: {message}"));
+  }
+  let response = &response["responses"][0];
+  if let Some(message) = response["error"]["message"].as_str() {
+    return Err(anyhow!("cloud-vision: {message}"));
+  }
+  Ok(
+    response["fullTextAnnotation"]["text"]
+      .as_str()
+      .unwrap_or_default()
+      .to_string(),
+  )
+}
+
+/// `file_path_pdf`の`page_num`ページ目をOCRしたテキストを返す
+pub async fn recognize(
+  client: &Client,
+  api_key: &str,
+  file_path_pdf: &str,
+  page_num: usize,
+  dpi: u32,
+) -> Result<String> {
+  // 出力先を指定しない場合は標準出力に書き出す
+  let output = Command::new("pdftoppm")
+    .kill_on_drop(true)
+    .arg("-r")
+    .arg(dpi.to_string())
+    .arg("-jpeg")
+    .arg("-f")
+    .arg(page_num.to_string())
+    .arg("-l")
+    .arg(page_num.to_string())
+    .arg("-singlefile")
+    .arg(file_path_pdf)
+    .output()
+    .await?;
+  if output.stdout.is_empty() {
+    return Err(anyhow!(
+      "pdftoppm: {}",
+      String::from_utf8_lossy(&output.stderr).trim()
+    ));
+  }
+  let response = client
+    .post(ENDPOINT)
+    .query(&[("key", api_key)])
+    .header(CONTENT_TYPE, "application/json")
+    .body(serde_json::to_vec(&request_body(&output.stdout))?)
+    .send()
+    .await?;
+  let status = response.status();
+  let response = serde_json::from_slice::<Value>(&response.bytes().await?)
+    .map_err(|err| anyhow!("cloud-vision: {status}: {err}"))?;
+  response_text(&response)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn encode_and_parse() {
+    assert_eq!(base64(b""), "");
+    assert_eq!(base64(b"f"), "Zg==");
+    assert_eq!(base64(b"fo"), "Zm8=");
+    assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    let response = json!({"responses": [{"fullTextAnnotation": {"text": "主文\n"}}]});
+    assert_eq!(response_text(&response).unwrap(), "主文\n");
+    assert_eq!(response_text(&json!({"responses": [{}]})).unwrap(), "");
+    let error = json!({"error": {"code": 403, "message": "API key not valid"}});
+    assert!(response_text(&error).is_err());
+  }
+}
@@ -0,0 +1,38 @@
+//! `--config`で指定する設定ファイル（JSON）を読み込む
+
+use crate::{auth::Credential, crop::Crop, profile::Profile};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tokio::fs;
+
+/// 設定ファイルの内容
+///
+/// 書かれていない項目は既定値とする
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+  /// ページ番号やヘッダ・フッタなど、取り除く行に一致する正規表現
+  pub filter_patterns: Vec<String>,
+  /// `--suppress-seals`の際に最初と最後のページから取り除く、組み込みのもの以外の行に一致する正規表現
+  pub seal_patterns: Vec<String>,
+  /// ホスト名（`host`か`host:port`）ごとの、PDFファイルをダウンロードする際の認証情報
+  pub credentials: BTreeMap<String, Credential>,
+  /// OCRの前にページの画像から切り取る範囲 `calibrate`で書き出せる
+  pub crop: Option<Crop>,
+  /// 裁判所や判決の年ごとに切り取る範囲などを置き換えるプロファイル 当てはまる最初のものを使う
+  pub profiles: Vec<Profile>,
+}
+
+impl Config {
+  /// 設定ファイルを読み込む pathが無い場合は既定値を返す
+  pub async fn load(path: Option<&str>) -> Result<Self> {
+    match path {
+      Some(path) => {
+        let s = fs::read_to_string(path).await?;
+        Ok(serde_json::from_str(&s)?)
+      }
+      None => Ok(Config::default()),
+    }
+  }
+}
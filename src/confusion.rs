@@ -0,0 +1,137 @@
+//! `--fix-confusions`の際に、OCRで取り違えやすい形の似た文字を、前後の文字から意図が明らかな場合に限って直す
+//!
+//! - 長音「ー」と漢数字「一」
+//! - カタカナ「ロ」と漢字「口」
+//! - カタカナ「ニ」と漢数字「二」
+//! - 英字「O」と漢数字「〇」
+//!
+//! 前後の文字は、直前は直した後の文字、直後は直す前の文字を見る
+
+/// 漢数字のうち、位取りで並べるもの
+fn is_kanji_digit(c: char) -> bool {
+  matches!(
+    c,
+    '〇' | '一' | '二' | '三' | '四' | '五' | '六' | '七' | '八' | '九'
+  )
+}
+
+fn is_kanji_numeral(c: char) -> bool {
+  is_kanji_digit(c) || matches!(c, '十' | '百' | '千' | '万' | '億' | '兆')
+}
+
+fn is_kanji(c: char) -> bool {
+  matches!(c, '々' | '〇' | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}')
+}
+
+/// 長音を含むカタカナ
+fn is_katakana(c: char) -> bool {
+  matches!(c, 'ー' | '\u{30A1}'..='\u{30FA}')
+}
+
+fn is_kana(c: char) -> bool {
+  is_katakana(c) || matches!(c, '\u{3041}'..='\u{3096}')
+}
+
+/// 英字の「O」と取り違えやすい文字
+fn is_letter_o(c: char) -> bool {
+  matches!(c, 'O' | 'o' | 'Ｏ' | 'ｏ')
+}
+
+fn is_between(before: Option<char>, after: Option<char>, f: fn(char) -> bool) -> bool {
+  before.is_some_and(f) && after.is_some_and(f)
+}
+
+/// 直前が漢字で、直後がカタカナではない（1文字のカタカナの語とみなせない）
+fn follows_kanji(before: Option<char>, after: Option<char>) -> bool {
+  before.is_some_and(is_kanji) && !after.is_some_and(is_katakana)
+}
+
+/// 1文字を前後の文字から直す 直さない場合は`None`を返す
+fn fix_char(before: Option<char>, c: char, after: Option<char>) -> Option<char> {
+  match c {
+    // 「第ー条」「統ー」「ー般」など カタカナの語は長音から始まらない
+    'ー'
+      if !before.is_some_and(is_kana) && after.is_some_and(is_kanji)
+        || follows_kanji(before, after) =>
+    {
+      Some('一')
+    }
+    '一' if is_between(before, after, is_katakana) => Some('ー'),
+    // 「人ロ」「出入ロ」など 「イ」「ロ」「ハ」の項目の番号と区別するため、直前が漢字の場合に限る
+    'ロ' if follows_kanji(before, after) => Some('口'),
+    '口' if is_between(before, after, is_katakana) => Some('ロ'),
+    // 「第ニ条」「ニ十」など
+    'ニ'
+      if follows_kanji(before, after)
+        || !before.is_some_and(is_kana) && after.is_some_and(is_kanji_numeral) =>
+    {
+      Some('二')
+    }
+    '二' if is_between(before, after, is_katakana) => Some('ニ'),
+    // 「二Ｏ二三年」「平成二Ｏ年」など
+    c if is_letter_o(c)
+      && before.is_some_and(is_kanji_digit)
+      && after.is_some_and(|c| {
+        is_kanji_digit(c) || is_letter_o(c) || matches!(c, '年' | '月' | '日' | '号')
+      }) =>
+    {
+      Some('〇')
+    }
+    '〇' if is_between(before, after, |c| c.is_ascii_alphabetic()) => Some('O'),
+    '〇' if is_between(before, after, |c| matches!(c, 'Ａ'..='Ｚ' | 'ａ'..='ｚ')) => {
+      Some('Ｏ')
+    }
+    _ => None,
+  }
+}
+
+/// 取り違えを直した文字列を返す
+pub fn fix(text: &str) -> String {
+  let chars = text.chars().collect::<Vec<_>>();
+  let mut fixed = String::with_capacity(text.len());
+  let mut before = None;
+  for (i, &c) in chars.iter().enumerate() {
+    let c = fix_char(before, c, chars.get(i + 1).copied()).unwrap_or(c);
+    fixed.push(c);
+    before = Some(c);
+  }
+  fixed
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn fix_long_vowel_and_kanji_one() {
+    assert_eq!(fix("第ー条の統ー"), "第一条の統一");
+    assert_eq!(fix("二十ー、"), "二十一、");
+    assert_eq!(fix("コンピュ一タ"), "コンピュータ");
+    assert_eq!(fix("コーヒー"), "コーヒー");
+    assert_eq!(fix("メモリ一枚"), "メモリ一枚");
+  }
+
+  #[test]
+  fn fix_katakana_ro_and_kanji_kuchi() {
+    assert_eq!(fix("人ロの増加"), "人口の増加");
+    assert_eq!(fix("ブ口グ"), "ブログ");
+    assert_eq!(fix("（ロ）被告は"), "（ロ）被告は");
+    assert_eq!(fix("ロ被告は"), "ロ被告は");
+  }
+
+  #[test]
+  fn fix_katakana_ni_and_kanji_two() {
+    assert_eq!(fix("第ニ条"), "第二条");
+    assert_eq!(fix("ニ十万円"), "二十万円");
+    assert_eq!(fix("ユ二ット"), "ユニット");
+    assert_eq!(fix("ニ　被告は"), "ニ　被告は");
+  }
+
+  #[test]
+  fn fix_letter_o_and_kanji_zero() {
+    assert_eq!(fix("二ＯＯ五年"), "二〇〇五年");
+    assert_eq!(fix("平成二O年"), "平成二〇年");
+    assert_eq!(fix("D〇G"), "DOG");
+    assert_eq!(fix("Ｏ社"), "Ｏ社");
+  }
+}
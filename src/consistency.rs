@@ -0,0 +1,53 @@
+//! `--check-consistency`の際に、抽出したテキストにある事件番号と判決日を一覧のJSONファイルの情報と比べる
+//!
+//! 食い違う場合は、PDFファイルのリンクが別の事件のものか、OCRが大きく崩れていることが多い
+
+use crate::{metadata, precedent};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// 一覧のJSONファイルの情報がテキストに見つからなかった項目
+#[derive(Debug, Clone, Serialize)]
+pub struct Mismatch {
+  /// `case_number`か`date`
+  pub field: &'static str,
+  /// 一覧のJSONファイルの値（判決日は`YYYY-MM-DD`形式）
+  pub expected: String,
+  /// テキストに最初に現れた値 テキストに事件番号や日付が無い場合は無い
+  pub found: Option<String>,
+}
+
+/// 一覧のJSONファイルの事件番号（併合された事件はいずれか）と判決日が、テキストのどこかに現れるかを調べる
+///
+/// 一覧のJSONファイルに無いか読めない項目は比べない
+pub fn check(text: &str, name: &str, metadata: &Map<String, Value>) -> Vec<Mismatch> {
+  let text = text
+    .chars()
+    .filter(|c| !c.is_whitespace())
+    .collect::<String>();
+  let mut mismatches = Vec::new();
+  let case_number = metadata.get("case_number").and_then(|v| v.as_str());
+  if let Some((case_number, expected)) = case_number.and_then(|case_number| {
+    precedent::parse_case_numbers(case_number).map(|expected| (case_number, expected))
+  }) {
+    let found = precedent::find_case_numbers(&text);
+    if !found.iter().any(|c| expected.contains(c)) {
+      mismatches.push(Mismatch {
+        field: "case_number",
+        expected: case_number.to_string(),
+        found: found.first().map(|c| c.to_string()),
+      });
+    }
+  }
+  if let Some(expected) = metadata::judgment_date(name, metadata) {
+    let found = precedent::find_dates(&text);
+    if !found.contains(&expected) {
+      mismatches.push(Mismatch {
+        field: "date",
+        expected,
+        found: found.into_iter().next(),
+      });
+    }
+  }
+  mismatches
+}
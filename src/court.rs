@@ -0,0 +1,218 @@
+//! 「東京地方裁判所」「東京地裁」「東京地判」などの裁判所の表記を、正式名称と裁判所のコードに揃える
+//!
+//! コードは`{所在地}-{種類}`（`tokyo-district`など）とし、最高裁判所は`supreme`、知的財産高等裁判所は`ip-high`、大審院は`daishinin`とする。
+//! 支部はコードに含めず、`branch`に「立川支部」のように記録する。所在地が分からない簡易裁判所などはコードを付けない
+
+use regex::Regex;
+use serde::Serialize;
+
+/// 地方裁判所（と家庭裁判所）の所在地とそのローマ字
+const LOCATIONS: [(&str, &str); 50] = [
+  ("札幌", "sapporo"),
+  ("函館", "hakodate"),
+  ("旭川", "asahikawa"),
+  ("釧路", "kushiro"),
+  ("青森", "aomori"),
+  ("盛岡", "morioka"),
+  ("仙台", "sendai"),
+  ("秋田", "akita"),
+  ("山形", "yamagata"),
+  ("福島", "fukushima"),
+  ("水戸", "mito"),
+  ("宇都宮", "utsunomiya"),
+  ("前橋", "maebashi"),
+  ("さいたま", "saitama"),
+  ("千葉", "chiba"),
+  ("東京", "tokyo"),
+  ("横浜", "yokohama"),
+  ("新潟", "niigata"),
+  ("富山", "toyama"),
+  ("金沢", "kanazawa"),
+  ("福井", "fukui"),
+  ("甲府", "kofu"),
+  ("長野", "nagano"),
+  ("岐阜", "gifu"),
+  ("静岡", "shizuoka"),
+  ("名古屋", "nagoya"),
+  ("津", "tsu"),
+  ("大津", "otsu"),
+  ("京都", "kyoto"),
+  ("大阪", "osaka"),
+  ("神戸", "kobe"),
+  ("奈良", "nara"),
+  ("和歌山", "wakayama"),
+  ("鳥取", "tottori"),
+  ("松江", "matsue"),
+  ("岡山", "okayama"),
+  ("広島", "hiroshima"),
+  ("山口", "yamaguchi"),
+  ("徳島", "tokushima"),
+  ("高松", "takamatsu"),
+  ("松山", "matsuyama"),
+  ("高知", "kochi"),
+  ("福岡", "fukuoka"),
+  ("佐賀", "saga"),
+  ("長崎", "nagasaki"),
+  ("熊本", "kumamoto"),
+  ("大分", "oita"),
+  ("宮崎", "miyazaki"),
+  ("鹿児島", "kagoshima"),
+  ("那覇", "naha"),
+];
+
+/// 高等裁判所の所在地
+const HIGH_COURTS: [&str; 8] = [
+  "東京",
+  "大阪",
+  "名古屋",
+  "広島",
+  "福岡",
+  "仙台",
+  "札幌",
+  "高松",
+];
+
+/// 揃えた裁判所
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Court {
+  /// 「東京地方裁判所」のような正式名称 支部を含まない
+  pub name: String,
+  pub code: Option<String>,
+  /// 「立川支部」のような支部
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub branch: Option<String>,
+}
+
+impl Court {
+  /// 支部を含めた正式名称
+  pub fn full_name(&self) -> String {
+    format!(
+      "{}{}",
+      self.name,
+      self.branch.as_deref().unwrap_or_default()
+    )
+  }
+}
+
+fn romanize(location: &str) -> Option<&'static str> {
+  LOCATIONS
+    .iter()
+    .find(|(name, _)| *name == location)
+    .map(|(_, roman)| *roman)
+}
+
+/// 裁判所の表記を揃える 裁判所の表記として読めない場合は`None`を返す
+///
+/// 「最判」「東京地立川支決」のような裁判の種類を付けた略称も読む
+pub fn normalize(s: &str) -> Option<Court> {
+  let s = s.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+  let supreme = Regex::new(
+    r"^(?:最高裁判所|最高裁|最)(?:大法廷|第[一二三1-3１-３]小法廷|大)?(?:判決|決定|判|決)?$",
+  )
+  .unwrap();
+  if supreme.is_match(&s) {
+    return Some(Court {
+      name: "最高裁判所".to_string(),
+      code: Some("supreme".to_string()),
+      branch: None,
+    });
+  }
+  if Regex::new(r"^大審院(?:判決|決定|判|決)?$|^大[判決]$")
+    .unwrap()
+    .is_match(&s)
+  {
+    return Some(Court {
+      name: "大審院".to_string(),
+      code: Some("daishinin".to_string()),
+      branch: None,
+    });
+  }
+  let re = Regex::new(
+    r"^(?P<location>[\p{Han}\p{Hiragana}]+?)(?:(?P<high>高等裁判所|高裁|高)|(?P<district>地方裁判所|地裁|地)|(?P<family>家庭裁判所|家裁|家)|(?P<summary>簡易裁判所|簡裁|簡))(?:(?P<branch>[\p{Han}\p{Hiragana}]+?)(?:支部|支))?(?:判決|決定|審判|[判決審])?$",
+  )
+  .unwrap();
+  let caps = re.captures(&s)?;
+  let location = &caps["location"];
+  let branch = caps.name("branch").map(|m| format!("{}支部", m.as_str()));
+  let (kind, kind_code) = if caps.name("high").is_some() {
+    if matches!(location, "知的財産" | "知財") {
+      return Some(Court {
+        name: "知的財産高等裁判所".to_string(),
+        code: Some("ip-high".to_string()),
+        branch: None,
+      });
+    }
+    ("高等裁判所", "high")
+  } else if caps.name("district").is_some() {
+    ("地方裁判所", "district")
+  } else if caps.name("family").is_some() {
+    ("家庭裁判所", "family")
+  } else {
+    ("簡易裁判所", "summary")
+  };
+  let is_known = kind_code != "high" || HIGH_COURTS.contains(&location);
+  Some(Court {
+    name: format!("{location}{kind}"),
+    code: romanize(location)
+      .filter(|_| is_known)
+      .map(|roman| format!("{roman}-{kind_code}")),
+    branch,
+  })
+}
+
+/// `--court`で指定した裁判所（表記かコード）に`court`が当てはまるか
+///
+/// 支部を指定しない場合はその支部も当てはまるものとする
+pub fn matches(filter: &str, court: &Court) -> bool {
+  match normalize(filter) {
+    Some(filter) => {
+      filter.name == court.name
+        && filter
+          .branch
+          .is_none_or(|branch| court.branch == Some(branch))
+    }
+    None => court.code.as_deref() == Some(filter.trim()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn code(s: &str) -> Option<String> {
+    normalize(s).and_then(|court| court.code)
+  }
+
+  #[test]
+  fn normalize_variants() {
+    for s in ["東京地方裁判所", "東京地裁", "東京地判", "東京地決"] {
+      assert_eq!(normalize(s).unwrap().name, "東京地方裁判所");
+      assert_eq!(code(s).as_deref(), Some("tokyo-district"));
+    }
+    assert_eq!(code("最高裁判所第三小法廷").as_deref(), Some("supreme"));
+    assert_eq!(code("最大判").as_deref(), Some("supreme"));
+    assert_eq!(code("知財高判").as_deref(), Some("ip-high"));
+    assert_eq!(code("高松高裁").as_deref(), Some("takamatsu-high"));
+    assert_eq!(code("高知地裁").as_deref(), Some("kochi-district"));
+    assert_eq!(code("さいたま家裁").as_deref(), Some("saitama-family"));
+  }
+
+  #[test]
+  fn normalize_branch() {
+    let court = normalize("東京地立川支判").unwrap();
+    assert_eq!(court.full_name(), "東京地方裁判所立川支部");
+    assert_eq!(court.code.as_deref(), Some("tokyo-district"));
+    assert!(matches("東京地裁", &court));
+    assert!(matches("tokyo-district", &court));
+    assert!(!matches("東京地裁八王子支部", &court));
+    assert!(!matches("大阪地裁", &court));
+  }
+
+  #[test]
+  fn unknown_location_has_no_code() {
+    let court = normalize("松戸簡易裁判所").unwrap();
+    assert_eq!(court.name, "松戸簡易裁判所");
+    assert_eq!(court.code, None);
+    assert_eq!(normalize("被告"), None);
+  }
+}
@@ -0,0 +1,302 @@
+//! OCRの前にページの画像から切り取る範囲
+//!
+//! ImageMagickの`-crop`と同じ`{幅}x{高さ}+{左}+{上}`の形式で、`--crop`か設定ファイルの`crop`で指定する。
+//! それぞれの値はピクセルか、`85%`のようにページの幅・高さに対する割合で書く。
+//! ピクセルの値は`--dpi`の解像度で変換したA4のページに対するもので、`--scale-crop`の場合は
+//! pdfinfoで読んだページの大きさに合わせて伸び縮みさせる
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, str::FromStr};
+use tokio::process::Command;
+
+/// A4のページの大きさ（ポイント）
+const A4_POINTS: (f64, f64) = (595.28, 841.89);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+  Pixels(u32),
+  /// ページの幅か高さに対する百分率
+  Percent(f64),
+}
+
+impl fmt::Display for Length {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Length::Pixels(pixels) => write!(f, "{pixels}"),
+      Length::Percent(percent) => write!(f, "{percent}%"),
+    }
+  }
+}
+
+impl FromStr for Length {
+  type Err = ();
+  fn from_str(s: &str) -> Result<Self, ()> {
+    match s.strip_suffix('%') {
+      Some(percent) => percent
+        .parse::<f64>()
+        .ok()
+        .filter(|percent| (0.0..=100.0).contains(percent))
+        .map(Length::Percent)
+        .ok_or(()),
+      None => s.parse::<u32>().map(Length::Pixels).map_err(|_| ()),
+    }
+  }
+}
+
+impl Length {
+  /// `page`はページの幅か高さ（ポイント）、`a4`はA4のページの同じ向きの長さ（ポイント）
+  fn resolve(&self, page: f64, a4: f64, dpi: u32, scale: bool) -> u32 {
+    match self {
+      Length::Pixels(pixels) if scale => (f64::from(*pixels) * page / a4).round() as u32,
+      Length::Pixels(pixels) => *pixels,
+      Length::Percent(percent) => (percent / 100.0 * page / 72.0 * f64::from(dpi)).round() as u32,
+    }
+  }
+
+  fn is_zero(&self) -> bool {
+    matches!(self, Length::Pixels(0)) || matches!(self, Length::Percent(p) if *p == 0.0)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Crop {
+  pub width: Length,
+  pub height: Length,
+  pub x: Length,
+  pub y: Length,
+}
+
+impl Default for Crop {
+  /// pdftoppmの既定の解像度で変換したA4のページから、余白と欄外のページ番号を除く範囲
+  fn default() -> Self {
+    Crop {
+      width: Length::Pixels(1000),
+      height: Length::Pixels(1475),
+      x: Length::Pixels(150),
+      y: Length::Pixels(150),
+    }
+  }
+}
+
+impl fmt::Display for Crop {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}x{}+{}+{}", self.width, self.height, self.x, self.y)
+  }
+}
+
+impl FromStr for Crop {
+  type Err = anyhow::Error;
+  fn from_str(s: &str) -> Result<Self> {
+    let err = || anyhow!("{{幅}}x{{高さ}}+{{左}}+{{上}}の形式で指定する: {s}");
+    let (size, offset) = s.trim().split_once('+').ok_or_else(err)?;
+    let (width, height) = size.split_once('x').ok_or_else(err)?;
+    let (x, y) = offset.split_once('+').ok_or_else(err)?;
+    let parse = |n: &str| n.parse::<Length>().map_err(|_| err());
+    let crop = Crop {
+      width: parse(width)?,
+      height: parse(height)?,
+      x: parse(x)?,
+      y: parse(y)?,
+    };
+    if crop.width.is_zero() || crop.height.is_zero() {
+      return Err(err());
+    }
+    Ok(crop)
+  }
+}
+
+impl Serialize for Crop {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(self)
+  }
+}
+
+impl<'de> Deserialize<'de> for Crop {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(serde::de::Error::custom)
+  }
+}
+
+/// ページの画像のピクセルで表した範囲
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+  pub width: u32,
+  pub height: u32,
+  pub x: u32,
+  pub y: u32,
+}
+
+impl fmt::Display for Rect {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}x{}+{}+{}", self.width, self.height, self.x, self.y)
+  }
+}
+
+impl Crop {
+  fn needs_page_size(&self, scale: bool) -> bool {
+    scale
+      || [self.width, self.height, self.x, self.y]
+        .iter()
+        .any(|length| matches!(length, Length::Percent(_)))
+  }
+
+  /// `page`は回転を反映したページの幅と高さ（ポイント） 分からない場合はA4とみなす
+  pub fn resolve(&self, page: Option<(f64, f64)>, dpi: u32, scale: bool) -> Rect {
+    let (page_width, page_height) = page.unwrap_or(A4_POINTS);
+    let horizontal = |length: &Length| length.resolve(page_width, A4_POINTS.0, dpi, scale);
+    let vertical = |length: &Length| length.resolve(page_height, A4_POINTS.1, dpi, scale);
+    Rect {
+      width: horizontal(&self.width).max(1),
+      height: vertical(&self.height).max(1),
+      x: horizontal(&self.x),
+      y: vertical(&self.y),
+    }
+  }
+
+  /// PDFファイルの`page_num`ページ目を切り取る範囲 ページの大きさが必要な場合のみpdfinfoで読む
+  pub async fn resolve_page(
+    &self,
+    file_path_pdf: &str,
+    page_num: usize,
+    dpi: u32,
+    scale: bool,
+  ) -> Rect {
+    let page = if self.needs_page_size(scale) {
+      page_size(file_path_pdf, page_num).await
+    } else {
+      None
+    };
+    self.resolve(page, dpi, scale)
+  }
+}
+
+/// pdfinfoの出力から、回転を反映したページの幅と高さ（ポイント）を読む
+fn parse_page_size(pdfinfo: &str) -> Option<(f64, f64)> {
+  let size = Regex::new(r"(?m)^Page\s+\d+\s+size:\s+([\d.]+)\s+x\s+([\d.]+)").unwrap();
+  let caps = size.captures(pdfinfo)?;
+  let (width, height) = (caps[1].parse().ok()?, caps[2].parse().ok()?);
+  let rotation = Regex::new(r"(?m)^Page\s+\d+\s+rot:\s+(\d+)")
+    .unwrap()
+    .captures(pdfinfo)
+    .and_then(|caps| caps[1].parse::<u32>().ok())
+    .unwrap_or(0);
+  if rotation % 180 == 90 {
+    Some((height, width))
+  } else {
+    Some((width, height))
+  }
+}
+
+async fn page_size(file_path_pdf: &str, page_num: usize) -> Option<(f64, f64)> {
+  let output = Command::new("pdfinfo")
+    .kill_on_drop(true)
+    .arg("-f")
+    .arg(page_num.to_string())
+    .arg("-l")
+    .arg(page_num.to_string())
+    .arg(file_path_pdf)
+    .output()
+    .await
+    .ok()?;
+  parse_page_size(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// `file_path`の画像を`rect`の範囲に切り取って置き換える
+pub async fn apply(file_path: &str, rect: &Rect) -> Option<String> {
+  let output = Command::new("convert")
+    .kill_on_drop(true)
+    .arg("-crop")
+    .arg(rect.to_string())
+    .arg(file_path)
+    .arg(file_path)
+    .output()
+    .await
+    .ok();
+  output.and_then(|output| {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.as_ref().is_empty() {
+      None
+    } else {
+      Some(stderr.to_string())
+    }
+  })
+}
+
+/// `file_path`の画像に`rect`の範囲を赤い枠で描いた画像を`output_path`に書き出す
+pub async fn overlay(file_path: &str, output_path: &str, rect: &Rect) -> Option<String> {
+  let rectangle = format!(
+    "rectangle {},{} {},{}",
+    rect.x,
+    rect.y,
+    rect.x + rect.width - 1,
+    rect.y + rect.height - 1
+  );
+  let output = Command::new("convert")
+    .kill_on_drop(true)
+    .arg(file_path)
+    .arg("-fill")
+    .arg("none")
+    .arg("-stroke")
+    .arg("red")
+    .arg("-strokewidth")
+    .arg("4")
+    .arg("-draw")
+    .arg(rectangle)
+    .arg(output_path)
+    .output()
+    .await;
+  match output {
+    Ok(output) if output.status.success() => None,
+    Ok(output) => Some(String::from_utf8_lossy(&output.stderr).to_string()),
+    Err(err) => Some(err.to_string()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_geometry() {
+    let crop = "1000x1475+150+150".parse::<Crop>().unwrap();
+    assert_eq!(crop, Crop::default());
+    assert_eq!(crop.to_string(), "1000x1475+150+150");
+    assert_eq!(
+      "80%x84.5%+10%+150".parse::<Crop>().unwrap().to_string(),
+      "80%x84.5%+10%+150"
+    );
+    assert!("1000x1475".parse::<Crop>().is_err());
+    assert!("0x10+0+0".parse::<Crop>().is_err());
+    assert!("120%x10+0+0".parse::<Crop>().is_err());
+    assert!("axb+1+2".parse::<Crop>().is_err());
+  }
+
+  #[test]
+  fn resolve_by_page_size() {
+    let a4 = Crop::default().resolve(Some((595.32, 841.92)), 150, true);
+    assert_eq!(a4.to_string(), "1000x1475+150+150");
+    // B5（182mm x 257mm）
+    let b5 = Crop::default().resolve(Some((515.91, 728.5)), 150, true);
+    assert_eq!(b5.to_string(), "867x1276+130+130");
+    let unscaled = Crop::default().resolve(Some((515.91, 728.5)), 150, false);
+    assert_eq!(unscaled, a4);
+    let percent = "50%x50%+25%+0".parse::<Crop>().unwrap();
+    assert_eq!(
+      percent
+        .resolve(Some((720.0, 1440.0)), 100, false)
+        .to_string(),
+      "500x1000+250+0"
+    );
+  }
+
+  #[test]
+  fn parse_pdfinfo() {
+    let pdfinfo =
+      "Pages:          3\nPage    1 size: 595.32 x 841.92 pts (A4)\nPage    1 rot:  90\n";
+    assert_eq!(parse_page_size(pdfinfo), Some((841.92, 595.32)));
+    assert_eq!(parse_page_size("Pages: 3\n"), None);
+  }
+}
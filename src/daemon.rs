@@ -0,0 +1,142 @@
+//! `--daemon`の際に、一覧を処理し終えても終了せずに、一定の間隔か一覧のファイルが更新されるたびに処理し直す
+//!
+//! 生成済みの出力ファイルがある事件は処理しないため、処理し直すたびに新しく加わった事件のみを処理することになる。
+//! systemdの`Type=notify`のサービスとして動かせるように、`NOTIFY_SOCKET`に準備ができたことと動き続けていることを知らせる。
+//! 設定ファイルは処理し直すたびに読み込み直し、SIGHUPを受け取った場合は待たずに読み込み直して処理し直す
+
+use crate::{logging, messages::Message, Args, Context};
+use anyhow::Result;
+use std::{sync::Arc, time::Duration};
+use tokio::{fs, sync::Notify, time::sleep};
+
+/// 一覧のファイルが更新されたかを調べる間隔
+const INPUT_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// systemdに状態を知らせる `NOTIFY_SOCKET`が無い場合は何もしない
+#[cfg(unix)]
+fn sd_notify(state: &str) {
+  use std::os::unix::net::UnixDatagram;
+  let Some(socket_path) = std::env::var_os("NOTIFY_SOCKET") else {
+    return;
+  };
+  let Ok(socket) = UnixDatagram::unbound() else {
+    return;
+  };
+  let socket_path = socket_path.to_string_lossy().to_string();
+  let result = match socket_path.strip_prefix('@') {
+    // `@`で始まる場合はLinuxの抽象名前空間のソケット
+    #[cfg(target_os = "linux")]
+    Some(name) => {
+      use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+      SocketAddr::from_abstract_name(name.as_bytes())
+        .and_then(|addr| socket.send_to_addr(state.as_bytes(), &addr))
+    }
+    _ => socket.send_to(state.as_bytes(), &socket_path),
+  };
+  if let Err(err) = result {
+    logging::warn(
+      "Daemon",
+      None,
+      format!("{}: {err}", Message::SdNotifyFailed),
+    );
+  }
+}
+
+#[cfg(not(unix))]
+fn sd_notify(_state: &str) {}
+
+/// `WATCHDOG_USEC`が指定されていれば、その半分の間隔でsystemdに動き続けていることを知らせる
+async fn watchdog() {
+  let Some(usec) = std::env::var("WATCHDOG_USEC")
+    .ok()
+    .and_then(|usec| usec.parse::<u64>().ok())
+    .filter(|usec| *usec > 0)
+  else {
+    return;
+  };
+  loop {
+    sleep(Duration::from_micros(usec / 2)).await;
+    sd_notify("WATCHDOG=1");
+  }
+}
+
+/// SIGHUPを受け取るたびに`reload`に知らせる
+async fn listen_hangup(reload: &Notify) {
+  #[cfg(unix)]
+  {
+    use tokio::signal::unix::{signal, SignalKind};
+    let Ok(mut hangup) = signal(SignalKind::hangup()) else {
+      return;
+    };
+    while hangup.recv().await.is_some() {
+      reload.notify_one();
+    }
+  }
+  #[cfg(not(unix))]
+  {
+    let _ = reload;
+  }
+}
+
+async fn modified(path: &str) -> Option<std::time::SystemTime> {
+  fs::metadata(path).await.ok()?.modified().ok()
+}
+
+/// 一覧のファイルの更新日時が`since`から変わるまで待つ
+async fn wait_input_changed(path: &str, since: Option<std::time::SystemTime>) {
+  loop {
+    sleep(INPUT_POLL_INTERVAL).await;
+    if modified(path).await != since {
+      return;
+    }
+  }
+}
+
+pub async fn run(args: Arc<Args>, context: Context) -> Result<()> {
+  let input_file_path = args.input.clone().expect("inputは必須");
+  let interrupt = Arc::clone(&context.interrupt);
+  let reload = Arc::new(Notify::new());
+  {
+    let reload = Arc::clone(&reload);
+    tokio::spawn(async move { listen_hangup(&reload).await });
+  }
+  tokio::spawn(watchdog());
+  sd_notify("READY=1");
+  let mut context = Arc::new(context);
+  loop {
+    sd_notify("STATUS=processing");
+    if let Err(err) = crate::run_once(&args, Arc::clone(&context)).await {
+      if interrupt.is_interrupted() {
+        break;
+      }
+      logging::error("Daemon", None, format!("{err:#}"));
+    }
+    sd_notify("STATUS=waiting");
+    logging::info("Daemon", None, Message::DaemonWaiting(args.daemon_interval));
+    let since = modified(&input_file_path).await;
+    let is_reload = tokio::select! {
+      () = interrupt.wait() => break,
+      () = reload.notified() => true,
+      () = sleep(Duration::from_secs(args.daemon_interval)) => false,
+      () = wait_input_changed(&input_file_path, since) => false,
+    };
+    if is_reload {
+      logging::info("Reload", None, Message::Reloading);
+      sd_notify("RELOADING=1");
+    }
+    match context.reload(&args).await {
+      Ok(reloaded) => context = Arc::new(reloaded),
+      // 設定ファイルを書き換えている途中などで読み込めない場合は、前の設定のまま続ける
+      Err(err) => logging::warn(
+        "Daemon",
+        None,
+        format!("{}: {err:#}", Message::ConfigReloadFailed),
+      ),
+    }
+    if is_reload {
+      sd_notify("READY=1");
+    }
+  }
+  sd_notify("STOPPING=1");
+  Ok(())
+}
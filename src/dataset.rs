@@ -0,0 +1,121 @@
+//! 出力フォルダの全ての出力ファイルのハッシュ値などを`manifest.json`に書き出す
+//!
+//! 出力フォルダを受け取った側が、ファイルが壊れていないことや同期が途中で止まっていないことを確かめられるようにする
+
+use crate::{cache, output};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::Path};
+use tokio::fs;
+
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// 出力ファイルを生成した際の設定
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Parameters {
+  /// このソフトウェアのバージョン
+  pub version: String,
+  pub mode: String,
+  pub format: String,
+  /// コマンドライン引数
+  pub arguments: Vec<String>,
+  /// 既定値を含めた全てのオプション（認証情報などを除く）
+  #[serde(default)]
+  pub options: serde_json::Value,
+  /// 外部のツールごとのバージョン
+  #[serde(default)]
+  pub tools: BTreeMap<String, String>,
+}
+
+/// 1つの出力ファイルの情報
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileEntry {
+  /// 出力フォルダにあるままの（圧縮した場合は圧縮後の）ファイルのSHA-256
+  pub sha256: String,
+  pub size: u64,
+  /// 元の事件の名前
+  pub case: String,
+  /// 記録した日時（UTC、RFC 3339）
+  pub generated_at: String,
+  /// 以前の実行で生成したファイルを後から記録した場合は分からないため、`None`とする
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub parameters: Option<Parameters>,
+}
+
+/// 出力ファイルの名前ごとの情報
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+  pub files: BTreeMap<String, FileEntry>,
+}
+
+fn manifest_path(output_name: &str) -> String {
+  format!("{output_name}/{MANIFEST_FILE_NAME}")
+}
+
+impl Manifest {
+  /// 出力フォルダにある一覧を読み込む 無い場合は空の一覧を返す
+  pub async fn load(output_name: &str) -> Result<Self> {
+    let path = manifest_path(output_name);
+    if Path::new(&path).exists() {
+      let s = fs::read_to_string(&path).await?;
+      Ok(serde_json::from_str(&s)?)
+    } else {
+      Ok(Self::default())
+    }
+  }
+
+  /// 出力ファイルが無くなったものを取り除いてから書き出す
+  pub async fn save(&mut self, output_name: &str) -> Result<()> {
+    self
+      .files
+      .retain(|file_name, _| Path::new(output_name).join(file_name).exists());
+    let s = serde_json::to_string_pretty(self)?;
+    let path = manifest_path(output_name);
+    let part_path = format!("{path}.part");
+    fs::write(&part_path, s).await?;
+    fs::rename(&part_path, &path).await?;
+    Ok(())
+  }
+
+  pub fn contains_case(&self, name: &str) -> bool {
+    self.files.values().any(|entry| entry.case == name)
+  }
+
+  pub fn remove_case(&mut self, name: &str) {
+    self.files.retain(|_, entry| entry.case != name);
+  }
+
+  /// 事件の出力ファイルのハッシュ値を計算して記録する
+  ///
+  /// 内容が変わらなかったファイルは、生成した日時と設定を以前の記録のままにする
+  pub async fn record(
+    &mut self,
+    output_name: &str,
+    name: &str,
+    parameters: Option<&Parameters>,
+  ) -> Result<()> {
+    let previous = self
+      .files
+      .iter()
+      .filter(|(_, entry)| entry.case == name)
+      .map(|(file_name, entry)| (file_name.clone(), entry.clone()))
+      .collect::<BTreeMap<_, _>>();
+    self.remove_case(name);
+    for file_name in output::case_files(output_name, name).await? {
+      let bytes = fs::read(Path::new(output_name).join(&file_name)).await?;
+      let sha256 = cache::sha256_hex(&bytes);
+      let entry = match previous.get(&file_name) {
+        Some(entry) if entry.sha256 == sha256 => entry.clone(),
+        _ => FileEntry {
+          sha256,
+          size: bytes.len() as u64,
+          case: name.to_string(),
+          generated_at: cache::now_rfc3339(),
+          parameters: parameters.cloned(),
+        },
+      };
+      self.files.insert(file_name, entry);
+    }
+    Ok(())
+  }
+}
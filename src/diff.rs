@@ -0,0 +1,177 @@
+//! テキストの差分を求め、二つの出力フォルダのテキストファイルを比べる
+
+use anyhow::Result;
+use flate2::read::GzDecoder;
+use std::{collections::BTreeMap, io::Read};
+use tokio::fs;
+
+/// 差分の要素 番号は0から始まる位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+  Removed(usize),
+  Added(usize),
+}
+
+/// Myersの方法で`old`から`new`への最短の編集を求める
+///
+/// 差分の大きさをDとしてO((N+M)D)の時間とO(D^2)の記憶領域を使う
+pub fn edits<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+  let (n, m) = (old.len() as isize, new.len() as isize);
+  let max = (n + m) as usize;
+  let mut v = vec![0isize; 2 * max + 3];
+  let offset = max as isize + 1;
+  let idx = |k: isize| (k + offset) as usize;
+  // 各段階の開始時点の`v`のうち、その段階で参照する範囲
+  let mut trace: Vec<Vec<isize>> = Vec::new();
+  let mut found = None;
+  for d in 0..=(max as isize) {
+    trace.push(v[idx(-d)..=idx(d)].to_vec());
+    for k in (-d..=d).step_by(2) {
+      let mut x = if k == -d || (k != d && v[idx(k - 1)] < v[idx(k + 1)]) {
+        v[idx(k + 1)]
+      } else {
+        v[idx(k - 1)] + 1
+      };
+      let mut y = x - k;
+      while x < n && y < m && old[x as usize] == new[y as usize] {
+        x += 1;
+        y += 1;
+      }
+      v[idx(k)] = x;
+      if x >= n && y >= m {
+        found = Some(d);
+        break;
+      }
+    }
+    if found.is_some() {
+      break;
+    }
+  }
+  let mut result = Vec::new();
+  let (mut x, mut y) = (n, m);
+  for d in (1..=found.unwrap_or_default()).rev() {
+    let snapshot = &trace[d as usize];
+    let at = |k: isize| snapshot[(k + d) as usize];
+    let k = x - y;
+    let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+      k + 1
+    } else {
+      k - 1
+    };
+    let prev_x = at(prev_k);
+    let prev_y = prev_x - prev_k;
+    while x > prev_x && y > prev_y {
+      x -= 1;
+      y -= 1;
+    }
+    if x == prev_x {
+      result.push(Edit::Added(prev_y as usize));
+    } else {
+      result.push(Edit::Removed(prev_x as usize));
+    }
+    x = prev_x;
+    y = prev_y;
+  }
+  result.reverse();
+  result
+}
+
+/// 変化した文字の数を、両方の文字数の合計で割った割合
+///
+/// 行単位で差分を求め、削除と追加が続く部分は文字単位で比べ直す
+pub fn change_ratio(old: &str, new: &str) -> f64 {
+  let total = old.chars().count() + new.chars().count();
+  if total == 0 {
+    return 0.0;
+  }
+  let old_lines = old.lines().collect::<Vec<_>>();
+  let new_lines = new.lines().collect::<Vec<_>>();
+  let mut changed = 0;
+  let mut removed = String::new();
+  let mut added = String::new();
+  let mut flush = |removed: &mut String, added: &mut String| {
+    let old_chars = removed.chars().collect::<Vec<_>>();
+    let new_chars = added.chars().collect::<Vec<_>>();
+    changed += edits(&old_chars, &new_chars).len();
+    removed.clear();
+    added.clear();
+  };
+  let mut last_removed = None;
+  for edit in edits(&old_lines, &new_lines) {
+    match edit {
+      Edit::Removed(i) => {
+        if !added.is_empty() || last_removed.map(|last| last + 1 != i).unwrap_or(false) {
+          flush(&mut removed, &mut added);
+        }
+        removed.push_str(old_lines[i]);
+        removed.push('\n');
+        last_removed = Some(i);
+      }
+      Edit::Added(j) => {
+        added.push_str(new_lines[j]);
+        added.push('\n');
+      }
+    }
+  }
+  flush(&mut removed, &mut added);
+  changed as f64 / total as f64
+}
+
+/// 出力フォルダの`{name}.txt`と`{name}.txt.gz`を名前順に読み込む
+async fn read_texts(dir: &str) -> Result<BTreeMap<String, String>> {
+  let mut texts = BTreeMap::new();
+  let mut entries = fs::read_dir(dir).await?;
+  while let Some(entry) = entries.next_entry().await? {
+    let file_name = entry.file_name().to_string_lossy().to_string();
+    let bytes = if let Some(name) = file_name.strip_suffix(".txt.gz") {
+      let compressed = fs::read(entry.path()).await?;
+      let mut bytes = Vec::new();
+      GzDecoder::new(compressed.as_slice()).read_to_end(&mut bytes)?;
+      Some((name.to_string(), bytes))
+    } else if let Some(name) = file_name.strip_suffix(".txt") {
+      Some((name.to_string(), fs::read(entry.path()).await?))
+    } else {
+      None
+    };
+    if let Some((name, bytes)) = bytes {
+      texts.insert(name, String::from_utf8_lossy(&bytes).to_string());
+    }
+  }
+  Ok(texts)
+}
+
+/// 二つの出力フォルダのテキストファイルを比べ、追加・削除・変更された事件と変更された割合を表示する
+pub async fn run(old_dir: &str, new_dir: &str) -> Result<()> {
+  let old = read_texts(old_dir).await?;
+  let new = read_texts(new_dir).await?;
+  let (mut added, mut removed, mut changed, mut unchanged) = (0, 0, 0, 0);
+  let mut ratio_sum = 0.0;
+  for name in old.keys().filter(|name| !new.contains_key(*name)) {
+    println!("[Removed] {name}");
+    removed += 1;
+  }
+  for (name, new_text) in &new {
+    match old.get(name) {
+      None => {
+        println!("[Added] {name}");
+        added += 1;
+      }
+      Some(old_text) if old_text == new_text => unchanged += 1,
+      Some(old_text) => {
+        let ratio = change_ratio(old_text, new_text);
+        println!("[Changed] {name}: {:.2}%", ratio * 100.0);
+        changed += 1;
+        ratio_sum += ratio;
+      }
+    }
+  }
+  let average = if changed > 0 {
+    ratio_sum / changed as f64 * 100.0
+  } else {
+    0.0
+  };
+  println!(
+    "added: {added}, removed: {removed}, changed: {changed}(平均{average:.2}%), unchanged: {unchanged}"
+  );
+  Ok(())
+}
@@ -0,0 +1,144 @@
+//! 判決文を見出し・段落・ページの区切りの並びに分け、MarkdownやHTMLなどの形式で出力する際に使う
+
+use crate::{
+  output::PAGE_BREAK,
+  sections::{self, SectionKind},
+  segment,
+};
+
+/// 判決文を構成する要素
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Block {
+  /// ページの区切り
+  PageBreak,
+  /// 主文・事実及び理由・別紙の見出し
+  Heading { kind: SectionKind, text: String },
+  /// 主文より前の部分の行
+  Line(String),
+  /// 項目番号で区切られた段落
+  ///
+  /// 途中にページの区切りがある場合は区切りの後を項目番号の無い段落とする
+  Paragraph {
+    level: usize,
+    number: Option<String>,
+    text: String,
+  },
+}
+
+/// 空白以外の文字の数
+fn count_chars(text: &str) -> usize {
+  text.chars().filter(|c| !c.is_whitespace()).count()
+}
+
+/// 空白以外の文字を`n`個含む位置でテキストを分ける
+fn split_at_chars(text: &str, n: usize) -> (&str, &str) {
+  let mut count = 0;
+  for (i, c) in text.char_indices() {
+    if count == n {
+      return (&text[..i], &text[i..]);
+    }
+    if !c.is_whitespace() {
+      count += 1;
+    }
+  }
+  (text, "")
+}
+
+/// 改ページの前にある空白以外の文字の数を並べる
+fn page_break_positions(text: &str) -> Vec<usize> {
+  let mut positions = Vec::new();
+  let mut count = 0;
+  for c in text.chars() {
+    if c == PAGE_BREAK {
+      positions.push(count);
+    } else if !c.is_whitespace() {
+      count += 1;
+    }
+  }
+  positions
+}
+
+fn push_lines(text: &str, blocks: &mut Vec<Block>) {
+  for line in text.lines() {
+    if line.contains(PAGE_BREAK) {
+      blocks.push(Block::PageBreak);
+    }
+    let line = line.trim();
+    if !line.is_empty() {
+      blocks.push(Block::Line(line.to_string()));
+    }
+  }
+}
+
+/// 段落に分けると空白と改ページが失われるため、改ページの位置は空白以外の文字の数で対応付ける
+fn push_paragraphs(text: &str, blocks: &mut Vec<Block>) {
+  let mut page_breaks = page_break_positions(text).into_iter().peekable();
+  let mut count = 0;
+  for paragraph in segment::segment(text) {
+    while page_breaks.next_if(|position| *position <= count).is_some() {
+      blocks.push(Block::PageBreak);
+    }
+    count += paragraph.number.as_deref().map(count_chars).unwrap_or(0);
+    let mut number = paragraph.number;
+    let mut rest = paragraph.text.as_str();
+    let end = count + count_chars(rest);
+    while let Some(position) = page_breaks.next_if(|position| *position < end) {
+      let (before, after) = split_at_chars(rest, position.saturating_sub(count));
+      blocks.push(Block::Paragraph {
+        level: paragraph.level,
+        number: number.take(),
+        text: before.to_string(),
+      });
+      blocks.push(Block::PageBreak);
+      count = position;
+      rest = after;
+    }
+    blocks.push(Block::Paragraph {
+      level: paragraph.level,
+      number,
+      text: rest.to_string(),
+    });
+    count = end;
+  }
+  if page_breaks.next().is_some() {
+    blocks.push(Block::PageBreak);
+  }
+}
+
+fn push_section(kind: SectionKind, text: &str, blocks: &mut Vec<Block>) {
+  if kind == SectionKind::Header {
+    push_lines(text, blocks);
+  } else {
+    push_paragraphs(text, blocks);
+  }
+}
+
+/// 見出しの行で部分に分け、主文より前は行ごとに、それ以降は項目番号をもとに段落に分ける
+pub fn blocks(text: &str) -> Vec<Block> {
+  let mut blocks = Vec::new();
+  let mut current = SectionKind::Header;
+  let mut buf = String::new();
+  for line in text.lines() {
+    match sections::heading_kind(line, current) {
+      Some(kind) => {
+        push_section(current, &buf, &mut blocks);
+        buf.clear();
+        if line.contains(PAGE_BREAK) {
+          blocks.push(Block::PageBreak);
+        }
+        let text = line
+          .chars()
+          .filter(|c| !c.is_whitespace())
+          .collect::<String>();
+        blocks.push(Block::Heading { kind, text });
+        current = kind;
+      }
+      None => {
+        buf.push_str(line);
+        buf.push('\n');
+      }
+    }
+  }
+  push_section(current, &buf, &mut blocks);
+  blocks
+}
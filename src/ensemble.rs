@@ -0,0 +1,108 @@
+//! テキストレイヤーのあるページとスキャンされたページが混在するPDFファイルで、ページごとに
+//! `pdftotext`とOCRのどちらの結果を使うかを決める
+
+/// テキストレイヤーがあるとみなす空白以外の文字の最小数
+const MIN_CHARS: usize = 20;
+
+/// `p2t`でこれより少ない空白以外の文字しか取り出せなかったページは、テキストレイヤーが無いとみなしてOCRを行う
+const MAX_BLANK_CHARS: usize = 5;
+
+/// 日本語の文書で使われる文字の割合がこれ以上であれば文字化けしていないとみなす
+const MIN_EXPECTED_RATIO: f64 = 0.9;
+
+/// `(cid:123)`と私用領域の文字の割合がこれ以上であれば、テキストレイヤーが文字化けしているとみなす
+///
+/// 私用領域の文字は外字として人名などに使われることもあるため、少しであれば許す
+const MIN_GARBLED_RATIO: f64 = 0.02;
+
+/// 日本語の文書で使われる文字かどうか
+///
+/// 置換文字・制御文字・私用領域の文字などはフォントの対応表が壊れたPDFファイルでよく現れる
+fn is_expected_char(c: char) -> bool {
+  c.is_ascii_graphic()
+    || ('\u{3000}'..='\u{30FF}').contains(&c)
+    || ('\u{4E00}'..='\u{9FFF}').contains(&c)
+    || ('\u{3400}'..='\u{4DBF}').contains(&c)
+    || ('\u{FF01}'..='\u{FF9F}').contains(&c)
+    || ('\u{2010}'..='\u{2312}').contains(&c)
+    || ('\u{2460}'..='\u{24FF}').contains(&c)
+    || ('\u{25A0}'..='\u{25FF}').contains(&c)
+}
+
+fn is_private_use(c: char) -> bool {
+  ('\u{E000}'..='\u{F8FF}').contains(&c) || c >= '\u{F0000}'
+}
+
+/// `s`が`(cid:123)`で始まる場合はその長さ
+///
+/// pdftotextは、フォントにUnicodeへの対応表が無く文字が分からない場合に文字のIDをこの形で出力する
+fn cid_len(s: &str) -> Option<usize> {
+  let digits = s.strip_prefix("(cid:")?;
+  let n = digits.find(|c: char| !c.is_ascii_digit())?;
+  (n > 0 && digits[n..].starts_with(')')).then_some("(cid:".len() + n + 1)
+}
+
+/// `pdftotext`で取り出したテキストが、`(cid:123)`や私用領域の文字で文字化けしているかどうか
+///
+/// `(cid:123)`は1文字として数える
+pub fn is_garbled(text: &str) -> bool {
+  let mut garbled = 0;
+  let mut total = 0;
+  let mut rest = text;
+  while let Some(c) = rest.chars().next() {
+    if let Some(len) = cid_len(rest) {
+      garbled += 1;
+      total += 1;
+      rest = &rest[len..];
+      continue;
+    }
+    if !c.is_whitespace() {
+      total += 1;
+      if is_private_use(c) {
+        garbled += 1;
+      }
+    }
+    rest = &rest[c.len_utf8()..];
+  }
+  garbled > 0 && garbled as f64 / total as f64 >= MIN_GARBLED_RATIO
+}
+
+/// `pdftotext`で取り出したページのテキストを使えるかどうか
+///
+/// 十分な文字数があり、文字化けとみられる文字が少ない場合に使えるとする
+pub fn is_usable_text_layer(text: &str) -> bool {
+  let chars = text
+    .chars()
+    .filter(|c| !c.is_whitespace())
+    .collect::<Vec<_>>();
+  if chars.len() < MIN_CHARS {
+    return false;
+  }
+  let expected = chars.iter().filter(|c| is_expected_char(**c)).count();
+  expected as f64 / chars.len() as f64 >= MIN_EXPECTED_RATIO && !is_garbled(text)
+}
+
+/// `pdftotext`でほとんど文字を取り出せなかったページかどうか
+pub fn is_blank_page(text: &str) -> bool {
+  text.chars().filter(|c| !c.is_whitespace()).count() < MAX_BLANK_CHARS
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn detect_garbled() {
+    let text = "主文 本件控訴を棄却する。控訴費用は控訴人の負担とする。";
+    assert!(!is_garbled(text));
+    assert!(is_usable_text_layer(text));
+    let cid = "(cid:1234)(cid:567)(cid:89) 本件控訴を棄却する。控訴費用は控訴人の負担とする。";
+    assert!(is_garbled(cid));
+    assert!(!is_usable_text_layer(cid));
+    assert!(is_garbled("\u{E001}\u{E002}本件控訴を棄却する。"));
+    // 外字が1文字だけ使われている程度であれば文字化けとはみなさない
+    let gaiji = format!("原告\u{E000}田{}", "本件控訴を棄却する。".repeat(10));
+    assert!(!is_garbled(&gaiji));
+    assert!(!is_garbled("(cid:) (cid:12"));
+  }
+}
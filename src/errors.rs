@@ -0,0 +1,101 @@
+//! 外部コマンドのエラー出力や事件の処理の失敗を、全ての事件で1つの`errors.jsonl`に1行ずつ追記する
+//!
+//! 事件ごとの`_err.txt`も今まで通り書き出すが、数千の小さなファイルを検索しなくても済むように、
+//! 事件・段階・エラー出力・日時を持つJSONオブジェクトとしてまとめる
+
+use crate::cache;
+use anyhow::Result;
+use serde::Serialize;
+use tokio::{
+  fs::{File, OpenOptions},
+  io::AsyncWriteExt,
+  sync::Mutex,
+};
+
+pub const FILE_NAME: &str = "errors.jsonl";
+
+#[derive(Debug, Serialize)]
+struct Record<'a> {
+  time: String,
+  case: &'a str,
+  /// エラーが起きた段階 `pdftotext`・`tesseract`などのコマンドの名前か、事件の処理自体が失敗した場合は`failed`
+  stage: &'a str,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  page: Option<usize>,
+  stderr: &'a str,
+}
+
+/// `errors.jsonl`
+pub struct ErrorLog {
+  path: String,
+  /// 同時に処理している事件の記録が混ざらないように、1行ずつ書き出す
+  lock: Mutex<()>,
+}
+
+impl ErrorLog {
+  pub fn new(path: String) -> Self {
+    ErrorLog {
+      path,
+      lock: Mutex::new(()),
+    }
+  }
+
+  /// 空のエラー出力は記録しない
+  pub async fn record(
+    &self,
+    case: &str,
+    stage: &str,
+    page: Option<usize>,
+    stderr: &str,
+  ) -> Result<()> {
+    let stderr = stderr.trim_end();
+    if stderr.is_empty() {
+      return Ok(());
+    }
+    let record = Record {
+      time: cache::now_rfc3339(),
+      case,
+      stage,
+      page,
+      stderr,
+    };
+    let mut line = serde_json::to_string(&record)?;
+    line.push('\n');
+    let _lock = self.lock.lock().await;
+    let mut file = OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(&self.path)
+      .await?;
+    file.write_all(line.as_bytes()).await?;
+    file.flush().await?;
+    Ok(())
+  }
+}
+
+/// 1つの事件のエラー出力 `_err.txt`と`errors.jsonl`の両方に書き出す
+pub struct CaseErrors<'a> {
+  file: File,
+  log: &'a ErrorLog,
+  case: &'a str,
+}
+
+impl<'a> CaseErrors<'a> {
+  pub async fn create(path: &str, log: &'a ErrorLog, case: &'a str) -> Result<Self> {
+    Ok(CaseErrors {
+      file: File::create(path).await?,
+      log,
+      case,
+    })
+  }
+
+  pub async fn write(&mut self, stage: &str, page: Option<usize>, stderr: &str) -> Result<()> {
+    self.file.write_all(stderr.as_bytes()).await?;
+    self.log.record(self.case, stage, page, stderr).await
+  }
+
+  pub async fn flush(&mut self) -> Result<()> {
+    self.file.flush().await?;
+    Ok(())
+  }
+}
@@ -0,0 +1,146 @@
+//! 生成したテキストファイルなどを一つのアーカイブファイルにまとめる
+
+use crate::output;
+use anyhow::{anyhow, Result};
+use flate2::{write::GzEncoder, Compression, GzBuilder};
+use std::{io::Write, path::Path};
+use tokio::fs;
+use tokio_stream::StreamExt;
+use zip::{write::SimpleFileOptions, CompressionMethod, DateTime, ZipWriter};
+
+/// アーカイブに含める一時フォルダのファイル
+const TMP_FILES: [&str; 1] = ["manifest.json"];
+
+/// 一時フォルダのファイルを置くアーカイブ内のフォルダ 出力フォルダの`manifest.json`と区別する
+const TMP_DIR: &str = "tmp";
+
+/// アーカイブに含める出力フォルダのファイルの拡張子
+const OUTPUT_EXTENSIONS: [&str; 6] = ["txt", "md", "html", "xml", "json", "gz"];
+
+enum ArchiveFormat {
+  Zip,
+  TarGz,
+}
+
+impl ArchiveFormat {
+  fn from_path(path: &str) -> Result<Self> {
+    if path.ends_with(".zip") {
+      Ok(Self::Zip)
+    } else if path.ends_with(".tar.gz") || path.ends_with(".tgz") {
+      Ok(Self::TarGz)
+    } else {
+      Err(anyhow!("アーカイブの形式は.zipか.tar.gzのみ対応: {path}"))
+    }
+  }
+}
+
+/// アーカイブ内のファイル名と実際のファイルのpathの組を名前順で集める
+async fn collect_files(
+  tmp_name: &str,
+  output_name: &str,
+  archive: &str,
+) -> Result<Vec<(String, String)>> {
+  let archive_path = Path::new(archive).canonicalize().ok();
+  let mut files = Vec::new();
+  // `--mode both`の出力は方法ごとのフォルダの中にあるため、フォルダ名を付けて含める
+  for dir_name in std::iter::once("").chain(output::MODE_DIRS) {
+    let dir_path = Path::new(output_name).join(dir_name);
+    if !dir_name.is_empty() && !dir_path.is_dir() {
+      continue;
+    }
+    let mut dir = fs::read_dir(&dir_path).await?;
+    while let Some(entry) = dir.next_entry().await? {
+      let path = entry.path();
+      let is_target = path
+        .extension()
+        .map(|ext| OUTPUT_EXTENSIONS.iter().any(|e| ext == *e))
+        .unwrap_or(false);
+      if !is_target || !entry.file_type().await?.is_file() {
+        continue;
+      }
+      if archive_path.is_some() && path.canonicalize().ok() == archive_path {
+        continue;
+      }
+      let file_name = entry.file_name().to_string_lossy().to_string();
+      let file_name = if dir_name.is_empty() {
+        file_name
+      } else {
+        format!("{dir_name}/{file_name}")
+      };
+      files.push((file_name, path.to_string_lossy().to_string()));
+    }
+  }
+  let mut tmp_files_stream = tokio_stream::iter(TMP_FILES);
+  while let Some(file_name) = tmp_files_stream.next().await {
+    let path = format!("{tmp_name}/{file_name}");
+    if Path::new(&path).exists() {
+      files.push((format!("{TMP_DIR}/{file_name}"), path));
+    }
+  }
+  files.sort();
+  if let Some(pair) = files.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+    return Err(anyhow!(
+      "アーカイブ内のファイル名が重複する: {}: {}・{}",
+      pair[0].0,
+      pair[0].1,
+      pair[1].1
+    ));
+  }
+  Ok(files)
+}
+
+fn write_tar_gz(archive: &str, files: &[(String, Vec<u8>)]) -> Result<()> {
+  let f = std::fs::File::create(archive)?;
+  // 再現性のためにgzipヘッダの時刻は0に固定する
+  let encoder: GzEncoder<std::fs::File> =
+    GzBuilder::new().mtime(0).write(f, Compression::default());
+  let mut builder = tar::Builder::new(encoder);
+  for (name, bytes) in files {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(bytes.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(0);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_cksum();
+    builder.append_data(&mut header, name, bytes.as_slice())?;
+  }
+  builder.into_inner()?.finish()?.flush()?;
+  Ok(())
+}
+
+fn write_zip(archive: &str, files: &[(String, Vec<u8>)]) -> Result<()> {
+  let f = std::fs::File::create(archive)?;
+  let mut writer = ZipWriter::new(f);
+  let options = SimpleFileOptions::default()
+    .compression_method(CompressionMethod::Deflated)
+    .last_modified_time(DateTime::default())
+    .unix_permissions(0o644);
+  for (name, bytes) in files {
+    writer.start_file(name, options)?;
+    writer.write_all(bytes)?;
+  }
+  writer.finish()?.flush()?;
+  Ok(())
+}
+
+/// 出力フォルダのテキストファイル・JSONファイルとキャッシュの一覧をアーカイブにまとめる
+///
+/// ファイルは名前順に並べ、時刻や権限は固定するため、同じ入力からは同じアーカイブが生成される
+pub async fn export(tmp_name: &str, output_name: &str, archive: &str) -> Result<()> {
+  let format = ArchiveFormat::from_path(archive)?;
+  let file_lst = collect_files(tmp_name, output_name, archive).await?;
+  let mut files = Vec::new();
+  let mut stream = tokio_stream::iter(file_lst);
+  while let Some((name, path)) = stream.next().await {
+    let bytes = fs::read(&path).await?;
+    files.push((name, bytes));
+  }
+  println!("[START] export: {archive}");
+  match format {
+    ArchiveFormat::Zip => write_zip(archive, &files)?,
+    ArchiveFormat::TarGz => write_tar_gz(archive, &files)?,
+  }
+  println!("[END] export: {archive}({} files)", files.len());
+  Ok(())
+}
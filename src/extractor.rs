@@ -0,0 +1,143 @@
+//! PDFファイルからテキストを取り出す方法（`--mode`の値）を、共通の`TextExtractor`として扱う
+//!
+//! 新しい方法は`TextExtractor`を実装して`for_mode`に加えれば、`--fallback-chain`などの方法を選ぶ側を変えずに使える。
+//! `dyn TextExtractor`として扱えるよう、`extract`は`async fn`ではなくBoxに入れたFutureを返す
+
+use crate::{
+  cloud_vision_pdf, ensemble_pdf, ocr_pdf, pdftotext_pdf, report::Timings, sidecar::Sidecar, Args,
+  Context, Mode,
+};
+use anyhow::Result;
+use std::{future::Future, pin::Pin};
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// テキストを取り出すPDFファイル `--tmp`の`{name}.pdf`に置かれている
+pub struct Pdf<'a> {
+  pub name: &'a str,
+}
+
+/// 抽出の設定と、抽出の間に記録する事件ごとのJSONファイルの内容と段階ごとの時間
+pub struct Options<'a> {
+  pub args: &'a Args,
+  pub context: &'a Context,
+  pub sidecar: &'a mut Sidecar,
+  pub timings: &'a mut Timings,
+}
+
+/// 抽出したテキスト ページの間は`output::PAGE_BREAK`で区切る
+#[derive(Debug, Clone, Default)]
+pub struct Pages {
+  text: String,
+}
+
+impl Pages {
+  /// 既にページの間を`output::PAGE_BREAK`で区切ったテキスト
+  pub fn from_text(text: String) -> Self {
+    Pages { text }
+  }
+
+  pub fn into_text(self) -> String {
+    self.text
+  }
+}
+
+pub trait TextExtractor: Send + Sync {
+  fn extract<'a>(&'a self, pdf: &'a Pdf<'a>, options: Options<'a>) -> BoxFuture<'a, Result<Pages>>;
+}
+
+/// `pdftotext`でテキストレイヤーから取り出し、ほとんど文字の無いページはOCRで補う
+pub struct PdfToText;
+
+impl TextExtractor for PdfToText {
+  fn extract<'a>(&'a self, pdf: &'a Pdf<'a>, options: Options<'a>) -> BoxFuture<'a, Result<Pages>> {
+    Box::pin(async move {
+      let Options {
+        args,
+        context,
+        sidecar,
+        timings,
+      } = options;
+      let txt = pdftotext_pdf(
+        pdf.name,
+        args,
+        &context.filters,
+        &context.errors,
+        &context.raster_permits,
+        sidecar,
+        timings,
+      )
+      .await?;
+      Ok(Pages::from_text(txt))
+    })
+  }
+}
+
+/// ページの画像をtesseractでOCRする
+pub struct Tesseract;
+
+impl TextExtractor for Tesseract {
+  fn extract<'a>(&'a self, pdf: &'a Pdf<'a>, options: Options<'a>) -> BoxFuture<'a, Result<Pages>> {
+    Box::pin(async move {
+      let Options {
+        args,
+        context,
+        sidecar,
+        timings,
+      } = options;
+      let txt = ocr_pdf(
+        pdf.name,
+        args,
+        &context.filters,
+        &context.errors,
+        &context.raster_permits,
+        sidecar,
+        timings,
+      )
+      .await?;
+      Ok(Pages::from_text(txt))
+    })
+  }
+}
+
+/// ページごとに`pdftotext`とtesseractの結果のどちらかを使う
+pub struct Ensemble;
+
+impl TextExtractor for Ensemble {
+  fn extract<'a>(&'a self, pdf: &'a Pdf<'a>, options: Options<'a>) -> BoxFuture<'a, Result<Pages>> {
+    Box::pin(async move {
+      let Options {
+        args,
+        context,
+        sidecar,
+        timings,
+      } = options;
+      let filters = &context.filters;
+      let txt = ensemble_pdf(pdf.name, args, filters, &context.errors, sidecar, timings).await?;
+      Ok(Pages::from_text(txt))
+    })
+  }
+}
+
+/// ページの画像をGoogle Cloud VisionのAPIでOCRする
+pub struct CloudVision;
+
+impl TextExtractor for CloudVision {
+  fn extract<'a>(&'a self, pdf: &'a Pdf<'a>, options: Options<'a>) -> BoxFuture<'a, Result<Pages>> {
+    Box::pin(async move {
+      let txt = cloud_vision_pdf(pdf.name, options.args, options.context, options.timings).await?;
+      Ok(Pages::from_text(txt))
+    })
+  }
+}
+
+/// `mode`の方法 `--mode both`は方法ごとに出力を分けるため、1つの方法としては扱わない
+pub fn for_mode(mode: &Mode) -> Option<Box<dyn TextExtractor>> {
+  match mode {
+    Mode::P2T => Some(Box::new(PdfToText)),
+    Mode::OCR => Some(Box::new(Tesseract)),
+    Mode::Ensemble => Some(Box::new(Ensemble)),
+    Mode::CloudVision => Some(Box::new(CloudVision)),
+    Mode::Both => None,
+  }
+}
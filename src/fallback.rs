@@ -0,0 +1,46 @@
+//! `--fallback-chain`の際に、事件ごとに方法を順に試し、抽出したテキストの質を調べて次の方法に進むかを決める
+
+use crate::{ensemble, suspicious};
+use serde::Serialize;
+
+/// 事件ごとのJSONファイルに記録する、試した方法とその結果
+#[derive(Debug, Clone, Serialize)]
+pub struct Attempt {
+  /// `--mode`の値
+  pub engine: String,
+  /// 抽出したテキストの語のうち、日本語の文書に現れにくい語ではないものの割合（0〜1）
+  pub quality: Option<f64>,
+  /// 質の検査を通らなかった理由 通った場合は無い
+  pub rejected: Option<String>,
+}
+
+/// 抽出したテキストが質の検査を通らない場合はその理由を返す
+///
+/// ほとんど文字が無いもの、`(cid:123)`などで文字化けしたもの、`quality`が`min_quality`より低いものは通らない
+pub fn reject_reason(txt: &str, min_quality: f64) -> Option<String> {
+  if ensemble::is_blank_page(txt) {
+    return Some("empty".to_string());
+  }
+  if ensemble::is_garbled(txt) {
+    return Some("garbled".to_string());
+  }
+  match suspicious::quality(txt) {
+    Some(quality) if quality < min_quality => Some(format!("quality {quality:.3} < {min_quality}")),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn check_quality() {
+    let text = "主文\n本件控訴を棄却する。\n控訴費用は控訴人の負担とする。\n";
+    assert_eq!(reject_reason(text, 0.9), None);
+    assert_eq!(reject_reason(" \n", 0.9), Some("empty".to_string()));
+    let cid = "(cid:12)(cid:34)(cid:56) 本件控訴を棄却する。";
+    assert_eq!(reject_reason(cid, 0.9), Some("garbled".to_string()));
+    assert!(reject_reason("本件ロ頭弁論 l 終結 ロ日 ※※ 棄却", 0.9).is_some());
+  }
+}
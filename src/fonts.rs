@@ -0,0 +1,118 @@
+//! `pdffonts`の出力からPDFファイルで使われているフォントの一覧を読む
+//!
+//! `--inspect-fonts`の際は、pdftotextで正しい文字を取り出せないフォントを含むPDFファイルを、pdftotextを使わずにOCRで処理する
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use tokio::process::Command;
+
+/// PDFファイルで使われているフォント
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Font {
+  pub name: String,
+  /// `CID TrueType`・`Type 3`など
+  pub font_type: String,
+  pub encoding: String,
+  /// フォントがPDFファイルに埋め込まれているかどうか
+  pub embedded: bool,
+  /// 文字コードからUnicodeへの対応表（ToUnicode）があるかどうか
+  pub unicode: bool,
+}
+
+/// ToUnicodeが無くても、フォント名と文字コードの組み合わせから文字が分かる符号化方式
+const STANDARD_ENCODINGS: [&str; 4] = ["WinAnsi", "MacRoman", "Standard", "Symbol"];
+
+impl Font {
+  /// pdftotextで正しい文字を取り出せないフォントかどうか
+  ///
+  /// ToUnicodeが無く、Type 3・CIDフォントや、標準的でない符号化方式で埋め込まれていないフォントは、
+  /// 文字コードからUnicodeの文字が分からないため、`(cid:123)`のような出力や文字化けになる
+  pub fn is_unextractable(&self) -> bool {
+    if self.unicode {
+      return false;
+    }
+    self.font_type.starts_with("Type 3")
+      || self.font_type.starts_with("CID")
+      || (!self.embedded && !STANDARD_ENCODINGS.contains(&self.encoding.as_str()))
+  }
+}
+
+/// `pdffonts`の出力を読む
+///
+/// フォントの種類には空白が含まれ、長いフォント名は桁がずれるため、右端の`emb`・`sub`・`uni`・`object ID`から順に区切る
+fn parse(text: &str) -> Vec<Font> {
+  text
+    .lines()
+    .skip_while(|line| !line.starts_with("---"))
+    .skip(1)
+    .filter_map(|line| {
+      let fields = line.split_whitespace().collect::<Vec<_>>();
+      let [name, rest @ .., encoding, emb, _sub, uni, _object, _generation] = fields.as_slice()
+      else {
+        return None;
+      };
+      Some(Font {
+        name: name.to_string(),
+        font_type: rest.join(" "),
+        encoding: encoding.to_string(),
+        embedded: *emb == "yes",
+        unicode: *uni == "yes",
+      })
+    })
+    .collect()
+}
+
+/// `file_path_pdf`で使われているフォントの一覧
+pub async fn list(file_path_pdf: &str) -> Result<Vec<Font>> {
+  let output = Command::new("pdffonts")
+    .kill_on_drop(true)
+    .arg(file_path_pdf)
+    .output()
+    .await?;
+  if !output.status.success() {
+    return Err(anyhow!(
+      "pdffonts: {}",
+      String::from_utf8_lossy(&output.stderr).trim()
+    ));
+  }
+  Ok(parse(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// `file_path_pdf`で使われている、pdftotextで正しい文字を取り出せないフォントの名前
+pub async fn unextractable(file_path_pdf: &str) -> Result<Vec<String>> {
+  let fonts = list(file_path_pdf).await?;
+  Ok(
+    fonts
+      .into_iter()
+      .filter(Font::is_unextractable)
+      .map(|font| font.name)
+      .collect(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_pdffonts() {
+    let text = "\
+name                                 type              encoding         emb sub uni object ID
+------------------------------------ ----------------- ---------------- --- --- --- ---------
+ABCDEF+MS-Mincho                     CID TrueType      Identity-H       yes yes yes     12  0
+[none]                               Type 3            Custom           yes no  no      20  0
+Century                              TrueType          WinAnsi          no  no  no      31  0
+";
+    let fonts = parse(text);
+    assert_eq!(fonts.len(), 3);
+    assert_eq!(fonts[0].name, "ABCDEF+MS-Mincho");
+    assert_eq!(fonts[0].font_type, "CID TrueType");
+    assert!(fonts[0].embedded && fonts[0].unicode);
+    assert_eq!(fonts[1].font_type, "Type 3");
+    assert!(!fonts[1].unicode);
+    assert_eq!(fonts[2].encoding, "WinAnsi");
+    assert!(!fonts[2].embedded);
+    let unextractable = fonts.iter().map(Font::is_unextractable).collect::<Vec<_>>();
+    assert_eq!(unextractable, vec![false, true, false]);
+  }
+}
@@ -0,0 +1,225 @@
+//! tesseractが出力するhOCRファイルを読み込み、行ごとの位置や大きさの情報を扱う
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// 画像上の矩形領域
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BBox {
+  pub x0: u32,
+  pub y0: u32,
+  pub x1: u32,
+  pub y1: u32,
+}
+
+impl BBox {
+  pub fn height(&self) -> u32 {
+    self.y1.saturating_sub(self.y0)
+  }
+}
+
+/// 認識された単語
+#[derive(Debug, Clone)]
+pub struct Word {
+  pub bbox: BBox,
+  pub text: String,
+  /// tesseractの`x_wconf`（0〜100）
+  pub confidence: Option<u32>,
+}
+
+/// 認識された行
+#[derive(Debug, Clone)]
+pub struct Line {
+  pub bbox: BBox,
+  pub words: Vec<Word>,
+}
+
+impl Line {
+  /// 単語を繋げた行のテキスト
+  ///
+  /// 日本語の文字同士は空白を入れずに繋げる
+  pub fn text(&self) -> String {
+    let mut s = String::new();
+    for word in &self.words {
+      let is_ascii_boundary = s.chars().last().map(|c| c.is_ascii()).unwrap_or(false)
+        && word
+          .text
+          .chars()
+          .next()
+          .map(|c| c.is_ascii())
+          .unwrap_or(false);
+      if is_ascii_boundary {
+        s.push(' ');
+      }
+      s.push_str(&word.text);
+    }
+    s
+  }
+}
+
+/// 認識された段落
+#[derive(Debug, Clone)]
+pub struct Paragraph {
+  pub lines: Vec<Line>,
+}
+
+/// 1ページ分の認識結果
+#[derive(Debug, Clone, Default)]
+pub struct Page {
+  pub paragraphs: Vec<Paragraph>,
+}
+
+impl Page {
+  /// 段落の間に空行を入れたページのテキスト
+  pub fn text(&self) -> String {
+    self
+      .paragraphs
+      .iter()
+      .filter(|par| !par.lines.is_empty())
+      .map(|par| {
+        par
+          .lines
+          .iter()
+          .map(|line| line.text())
+          .collect::<Vec<_>>()
+          .join("\n")
+      })
+      .collect::<Vec<_>>()
+      .join("\n\n")
+  }
+
+  pub fn lines(&self) -> impl Iterator<Item = &Line> {
+    self.paragraphs.iter().flat_map(|par| par.lines.iter())
+  }
+
+  /// 単語の信頼度の平均 信頼度のある単語が無い場合は`None`
+  pub fn mean_confidence(&self) -> Option<f64> {
+    let confidences = self
+      .lines()
+      .flat_map(|line| line.words.iter())
+      .filter_map(|word| word.confidence)
+      .collect::<Vec<_>>();
+    (!confidences.is_empty())
+      .then(|| confidences.iter().sum::<u32>() as f64 / confidences.len() as f64)
+  }
+}
+
+/// `title`属性の`bbox`を読み込む
+pub fn parse_bbox(title: &str) -> Option<BBox> {
+  let re = Regex::new(r"bbox (\d+) (\d+) (\d+) (\d+)").unwrap();
+  let caps = re.captures(title)?;
+  let n = |i: usize| caps.get(i).and_then(|m| m.as_str().parse::<u32>().ok());
+  Some(BBox {
+    x0: n(1)?,
+    y0: n(2)?,
+    x1: n(3)?,
+    y1: n(4)?,
+  })
+}
+
+/// `title`属性の`x_wconf`を読み込む
+fn parse_wconf(title: &str) -> Option<u32> {
+  let re = Regex::new(r"x_wconf (\d+)").unwrap();
+  re.captures(title)?.get(1)?.as_str().parse().ok()
+}
+
+fn unescape(s: &str) -> String {
+  s.replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&quot;", "\"")
+    .replace("&#39;", "'")
+    .replace("&apos;", "'")
+    .replace("&amp;", "&")
+}
+
+/// hOCRの文字列を読み込む
+///
+/// tesseractが出力する`ocr_par`・`ocr_line`・`ocrx_word`の構造のみを扱う
+pub fn parse(hocr: &str) -> Page {
+  let element_re = Regex::new(
+    r#"<span class=['"](?P<class>ocr_line|ocr_textfloat|ocr_caption|ocr_header|ocrx_word)['"][^>]*title=['"](?P<title>[^'"]*)['"][^>]*>(?P<text>[^<]*)|<p class=['"]ocr_par['"]"#,
+  )
+  .unwrap();
+  let mut page = Page::default();
+  for caps in element_re.captures_iter(hocr) {
+    let Some(class) = caps.name("class") else {
+      page.paragraphs.push(Paragraph { lines: Vec::new() });
+      continue;
+    };
+    let title = caps.name("title").map(|m| m.as_str()).unwrap_or_default();
+    let Some(bbox) = parse_bbox(title) else {
+      continue;
+    };
+    if page.paragraphs.is_empty() {
+      page.paragraphs.push(Paragraph { lines: Vec::new() });
+    }
+    let par = page.paragraphs.last_mut().unwrap();
+    if class.as_str() == "ocrx_word" {
+      let text = caps.name("text").map(|m| m.as_str()).unwrap_or_default();
+      let text = unescape(text.trim());
+      if text.is_empty() {
+        continue;
+      }
+      if let Some(line) = par.lines.last_mut() {
+        line.words.push(Word {
+          bbox,
+          text,
+          confidence: parse_wconf(title),
+        });
+      }
+    } else {
+      par.lines.push(Line {
+        bbox,
+        words: Vec::new(),
+      });
+    }
+  }
+  for par in page.paragraphs.iter_mut() {
+    par.lines.retain(|line| !line.words.is_empty());
+  }
+  page
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_paragraphs_and_words() {
+    let hocr = "\
+<div class='ocr_page' title='bbox 0 0 1000 1400'>
+<p class='ocr_par' id='par_1_1' title='bbox 100 100 900 200'>
+<span class='ocr_line' id='line_1_1' title='bbox 100 100 900 140; baseline 0 -8'>
+<span class='ocrx_word' id='word_1_1' title='bbox 100 100 300 140; x_wconf 90'>本件</span>
+<span class='ocrx_word' id='word_1_2' title='bbox 310 100 400 140; x_wconf 80'>A&amp;B</span>
+<span class='ocrx_word' id='word_1_3' title='bbox 410 100 450 140; x_wconf 70'>C</span>
+</span>
+<span class='ocr_line' id='line_1_2' title='bbox 100 160 900 200; baseline 0 -8'>
+</span>
+</p>
+<p class='ocr_par' id='par_1_2' title='bbox 100 300 900 340'>
+<span class='ocr_line' id='line_1_3' title='bbox 100 300 900 340; baseline 0 -8'>
+<span class='ocrx_word' id='word_1_4' title='bbox 100 300 500 340'>棄却する。</span>
+</span>
+</p>
+</div>";
+    let page = parse(hocr);
+    assert_eq!(page.paragraphs.len(), 2);
+    assert_eq!(page.paragraphs[0].lines.len(), 1);
+    let line = &page.paragraphs[0].lines[0];
+    assert_eq!(
+      line.bbox,
+      BBox {
+        x0: 100,
+        y0: 100,
+        x1: 900,
+        y1: 140
+      }
+    );
+    assert_eq!(line.bbox.height(), 40);
+    assert_eq!(line.words[0].confidence, Some(90));
+    assert_eq!(page.paragraphs[1].lines[0].words[0].confidence, None);
+    assert_eq!(page.text(), "本件A&B C\n\n棄却する。");
+    assert_eq!(page.mean_confidence(), Some(80.0));
+  }
+}
@@ -0,0 +1,109 @@
+//! 判決文を単独で閲覧できるHTMLのページに変換する
+
+use crate::{
+  document::{self, Block},
+  sections::SectionKind,
+};
+use serde_json::{Map, Value};
+
+const STYLE: &str = "body{max-width:48em;margin:auto;padding:1em;line-height:1.8}\
+dl{display:grid;grid-template-columns:max-content auto;gap:0 1em}\
+dt{font-weight:bold}\
+.page{display:block;text-align:right;color:#888;font-size:small;border-top:1px dashed #ccc}\
+.number{margin-right:1em}";
+
+pub fn escape(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+    .replace('\'', "&#39;")
+}
+
+fn section_class(kind: SectionKind) -> &'static str {
+  match kind {
+    SectionKind::Header => "header",
+    SectionKind::MainText => "main-text",
+    SectionKind::FactsAndReasons => "facts-and-reasons",
+    SectionKind::Appendix => "appendix",
+  }
+}
+
+fn metadata_value(value: &Value) -> String {
+  match value {
+    Value::String(s) => s.clone(),
+    v => v.to_string(),
+  }
+}
+
+/// 一覧のJSONファイルにある事件の情報を見出しと定義リストにする
+fn render_metadata(title: &str, metadata: &Map<String, Value>, s: &mut String) {
+  s.push_str(&format!("<header>\n<h1>{}</h1>\n<dl>\n", escape(title)));
+  for (key, value) in metadata {
+    s.push_str(&format!(
+      "<dt>{}</dt><dd>{}</dd>\n",
+      escape(key),
+      escape(&metadata_value(value))
+    ));
+  }
+  s.push_str("</dl>\n</header>\n");
+}
+
+fn page_anchor(page: usize) -> String {
+  format!("<a class=\"page\" id=\"page-{page}\" href=\"#page-{page}\">{page}</a>\n")
+}
+
+/// 部分を`<section>`に、段落を入れ子の深さをclassに持つ`<p>`に、ページの始まりを`#page-{n}`のアンカーにする
+///
+/// `metadata`は一覧のJSONファイルの事件の情報で、ページの冒頭に出力する
+pub fn render(text: &str, name: &str, metadata: &Map<String, Value>) -> String {
+  let title = metadata
+    .get("case_number")
+    .and_then(|v| v.as_str())
+    .unwrap_or(name);
+  let mut s = format!(
+    "<!DOCTYPE html>\n<html lang=\"ja\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n",
+    escape(title)
+  );
+  render_metadata(title, metadata, &mut s);
+  s.push_str("<main>\n");
+  let mut page = 1;
+  s.push_str(&page_anchor(page));
+  s.push_str(&format!(
+    "<section class=\"{}\">\n",
+    section_class(SectionKind::Header)
+  ));
+  for block in document::blocks(text) {
+    match block {
+      Block::PageBreak => {
+        page += 1;
+        s.push_str(&page_anchor(page));
+      }
+      Block::Heading { kind, text } => {
+        s.push_str(&format!(
+          "</section>\n<section class=\"{}\">\n<h2>{}</h2>\n",
+          section_class(kind),
+          escape(&text)
+        ));
+      }
+      Block::Line(line) => s.push_str(&format!("<p>{}</p>\n", escape(&line))),
+      Block::Paragraph {
+        level,
+        number,
+        text,
+      } => {
+        let number = number
+          .map(|number| format!("<span class=\"number\">{}</span>", escape(&number)))
+          .unwrap_or_default();
+        s.push_str(&format!(
+          "<p class=\"level-{level}\" style=\"margin-left:{}em\">{number}{}</p>\n",
+          level.saturating_sub(1),
+          escape(&text)
+        ));
+      }
+    }
+  }
+  s.push_str("</section>\n</main>\n</body>\n</html>\n");
+  s
+}
@@ -0,0 +1,122 @@
+//! `--image-cache`の際に、pdftoppmでページを変換した画像をPDFファイルのSHA-256ごとに一時フォルダに残す
+//!
+//! OCRの設定を変えて処理し直す場合に、時間のかかるpdftoppmでの変換を繰り返さずに済む。
+//! 画像は`{tmp}/images/{PDFファイルのSHA-256}/{解像度}-{形式}/{ページ番号}.{拡張子}`に置き、
+//! キャッシュしたPDFファイルの一覧に無いPDFファイルの画像は`cache gc`で削除する
+
+use crate::cache;
+use anyhow::Result;
+use clap::ValueEnum;
+use std::{collections::BTreeSet, path::Path};
+use tokio::{fs, process::Command};
+
+const DIR_NAME: &str = "images";
+
+/// OCRに渡すページの画像の形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+  /// pdftoppmが出力するJPEG
+  Jpeg,
+  /// 2値化してGroup4で圧縮したTIFF tesseractが速く読め、一時フォルダの容量も小さい
+  TiffG4,
+}
+
+impl Format {
+  pub fn extension(&self) -> &'static str {
+    match self {
+      Format::Jpeg => "jpg",
+      Format::TiffG4 => "tif",
+    }
+  }
+}
+
+/// pdftoppmでグレースケールに変換した`{name}.pgm`を2値化し、Group4で圧縮した`{name}.tif`に変換する
+pub async fn to_tiff_g4(name: &str) -> Option<String> {
+  let pgm_path = format!("{name}.pgm");
+  let output = Command::new("convert")
+    .kill_on_drop(true)
+    .arg(&pgm_path)
+    .arg("-threshold")
+    .arg("50%")
+    .arg("-type")
+    .arg("bilevel")
+    .arg("-compress")
+    .arg("Group4")
+    .arg(format!("{name}.tif"))
+    .output()
+    .await;
+  fs::remove_file(&pgm_path).await.ok();
+  match output {
+    Ok(output) if output.status.success() => None,
+    Ok(output) => Some(String::from_utf8_lossy(&output.stderr).to_string()),
+    Err(err) => Some(err.to_string()),
+  }
+}
+
+/// 1つのPDFファイルの画像のキャッシュ
+pub struct ImageCache {
+  dir: String,
+  extension: &'static str,
+}
+
+impl ImageCache {
+  /// `file_name`は`{tmp}/{事件の名前}` `dpi`と`variant`はpdftoppmで変換する解像度と画像の形式
+  pub async fn open(
+    tmp_name: &str,
+    file_name: &str,
+    dpi: u32,
+    variant: &str,
+    format: Format,
+  ) -> Result<Self> {
+    let sha256 = cache::sha256_hex(&cache::read(&format!("{file_name}.pdf")).await?);
+    Ok(ImageCache {
+      dir: format!("{tmp_name}/{DIR_NAME}/{sha256}/{dpi}-{variant}"),
+      extension: format.extension(),
+    })
+  }
+
+  fn path(&self, page_num: usize) -> String {
+    format!("{}/{page_num}.{}", self.dir, self.extension)
+  }
+
+  /// キャッシュした画像があれば`image_path`に複製して`true`を返す
+  pub async fn restore(&self, page_num: usize, image_path: &str) -> Result<bool> {
+    let path = self.path(page_num);
+    if !Path::new(&path).exists() {
+      return Ok(false);
+    }
+    fs::copy(&path, image_path).await?;
+    Ok(true)
+  }
+
+  /// 変換した画像を残す 変換に失敗して画像が無い場合は何もしない
+  pub async fn store(&self, page_num: usize, image_path: &str) -> Result<()> {
+    if !Path::new(image_path).exists() {
+      return Ok(());
+    }
+    fs::create_dir_all(&self.dir).await?;
+    // 書きかけの画像を使わないように、別のファイルに複製してから置き換える
+    let path = self.path(page_num);
+    let part_path = format!("{path}.part");
+    fs::copy(image_path, &part_path).await?;
+    fs::rename(&part_path, &path).await?;
+    Ok(())
+  }
+}
+
+/// `sha256s`に無いPDFファイルの画像を削除する
+pub async fn gc(tmp_name: &str, sha256s: &BTreeSet<String>) -> Result<()> {
+  let root = format!("{tmp_name}/{DIR_NAME}");
+  if !Path::new(&root).exists() {
+    return Ok(());
+  }
+  let mut dir = fs::read_dir(&root).await?;
+  while let Some(dir_entry) = dir.next_entry().await? {
+    let sha256 = dir_entry.file_name().to_string_lossy().to_string();
+    if !sha256s.contains(&sha256) {
+      crate::logging::info("Remove", None, dir_entry.path().display());
+      fs::remove_dir_all(dir_entry.path()).await?;
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,70 @@
+//! Ctrl+CやSIGTERMを受け取った際に、処理中の事件に中断を知らせる
+//!
+//! 1回目は処理中の事件を中断して記録を書き出してから終了し、2回目はすぐに終了する。
+//! SIGTERMはsystemdなどがサービスを止める際に送る
+
+use crate::{logging, messages::Message};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::Notify;
+
+/// Ctrl+Cか、Unixの場合はSIGTERMを受け取るまで待つ 待ち受けられない場合は`false`を返す
+async fn wait_signal() -> bool {
+  #[cfg(unix)]
+  {
+    use tokio::signal::unix::{signal, SignalKind};
+    let Ok(mut terminate) = signal(SignalKind::terminate()) else {
+      return false;
+    };
+    tokio::select! {
+      result = tokio::signal::ctrl_c() => result.is_ok(),
+      _ = terminate.recv() => true,
+    }
+  }
+  #[cfg(not(unix))]
+  {
+    tokio::signal::ctrl_c().await.is_ok()
+  }
+}
+
+/// 2回目のCtrl+Cで終了する際の終了コード
+const FORCE_EXIT_CODE: i32 = 130;
+
+#[derive(Default)]
+pub struct Interrupt {
+  interrupted: AtomicBool,
+  notify: Notify,
+}
+
+impl Interrupt {
+  pub fn is_interrupted(&self) -> bool {
+    self.interrupted.load(Ordering::SeqCst)
+  }
+
+  fn trigger(&self) {
+    self.interrupted.store(true, Ordering::SeqCst);
+    self.notify.notify_waiters();
+  }
+
+  /// 中断されるまで待つ
+  pub async fn wait(&self) {
+    // 確かめてから待ち始めるまでの間に中断されても取りこぼさないように、先に待つ準備をする
+    let notified = self.notify.notified();
+    if self.is_interrupted() {
+      return;
+    }
+    notified.await;
+  }
+
+  /// Ctrl+C・SIGTERMを待ち受けて中断を知らせる
+  pub async fn listen(&self) {
+    if !wait_signal().await {
+      return;
+    }
+    logging::info("Interrupt", None, Message::InterruptRequested);
+    self.trigger();
+    if wait_signal().await {
+      logging::info("Interrupt", None, Message::ForceExit);
+      std::process::exit(FORCE_EXIT_CODE);
+    }
+  }
+}
@@ -0,0 +1,199 @@
+//! 当事者などを匿名化した記号（Ａ、Ｂ、Ｘ１など）の使われ方を調べ、OCRの誤認識とみられるものを検出・修正する
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// 確立した記号とみなす最小の出現回数
+const ESTABLISHED_MIN_COUNT: usize = 2;
+
+/// ラテン文字と見た目が同じギリシャ文字・キリル文字
+const LOOK_ALIKES: [(char, char); 26] = [
+  ('Α', 'A'),
+  ('Β', 'B'),
+  ('Ε', 'E'),
+  ('Ζ', 'Z'),
+  ('Η', 'H'),
+  ('Ι', 'I'),
+  ('Κ', 'K'),
+  ('Μ', 'M'),
+  ('Ν', 'N'),
+  ('Ο', 'O'),
+  ('Ρ', 'P'),
+  ('Τ', 'T'),
+  ('Υ', 'Y'),
+  ('Χ', 'X'),
+  ('А', 'A'),
+  ('В', 'B'),
+  ('Е', 'E'),
+  ('К', 'K'),
+  ('М', 'M'),
+  ('Н', 'H'),
+  ('О', 'O'),
+  ('Р', 'P'),
+  ('С', 'C'),
+  ('Т', 'T'),
+  ('Х', 'X'),
+  ('У', 'Y'),
+];
+
+/// 検出された不自然な記号
+#[derive(Debug, Clone, Serialize)]
+pub struct LabelIssue {
+  /// 判決文中での表記
+  pub label: String,
+  pub count: usize,
+  /// 本来の記号とみられるもの
+  pub suggestion: Option<String>,
+  /// 自動で修正したかどうか
+  pub corrected: bool,
+}
+
+fn is_letter(c: char) -> bool {
+  c.is_ascii_alphabetic()
+    || ('Ａ'..='Ｚ').contains(&c)
+    || ('ａ'..='ｚ').contains(&c)
+    || ('\u{0370}'..='\u{03FF}').contains(&c)
+    || ('\u{0400}'..='\u{04FF}').contains(&c)
+}
+
+fn is_digit(c: char) -> bool {
+  c.is_ascii_digit() || ('０'..='９').contains(&c)
+}
+
+fn is_label_head(c: char) -> bool {
+  c.is_ascii_uppercase() || ('Ａ'..='Ｚ').contains(&c) || look_alike(c).is_some()
+}
+
+fn look_alike(c: char) -> Option<char> {
+  LOOK_ALIKES
+    .iter()
+    .find(|(from, _)| *from == c)
+    .map(|(_, to)| *to)
+}
+
+fn is_full_width(c: char) -> bool {
+  ('Ａ'..='Ｚ').contains(&c) || ('０'..='９').contains(&c)
+}
+
+fn shift(c: char, from: char, to: char) -> char {
+  char::from_u32(c as u32 - from as u32 + to as u32).unwrap_or(c)
+}
+
+/// 表記の違いを無視した記号（半角の大文字と数字）
+fn canonical(label: &str) -> String {
+  label
+    .chars()
+    .map(|c| match look_alike(c).unwrap_or(c) {
+      c @ 'Ａ'..='Ｚ' => shift(c, 'Ａ', 'A'),
+      c @ '０'..='９' => shift(c, '０', '0'),
+      c => c,
+    })
+    .collect()
+}
+
+/// 確立した記号の表記（全角か半角か）にそろえる
+fn with_width(canonical: &str, full_width: bool) -> String {
+  canonical
+    .chars()
+    .map(|c| match c {
+      'A'..='Z' if full_width => shift(c, 'A', 'Ａ'),
+      '0'..='9' if full_width => shift(c, '0', '０'),
+      c => c,
+    })
+    .collect()
+}
+
+/// 前後を文字に挟まれていない1文字の大文字（と続く数字）を記号として、その位置をバイト単位で返す
+fn find_labels(text: &str) -> Vec<(usize, usize)> {
+  let chars = text.char_indices().collect::<Vec<_>>();
+  let mut labels = Vec::new();
+  let mut i = 0;
+  while i < chars.len() {
+    let (start, c) = chars[i];
+    let prev_is_letter = i > 0 && is_letter(chars[i - 1].1);
+    if !is_label_head(c) || prev_is_letter {
+      i += 1;
+      continue;
+    }
+    let mut j = i + 1;
+    while j < chars.len() && is_digit(chars[j].1) {
+      j += 1;
+    }
+    let next_is_letter = j < chars.len() && is_letter(chars[j].1);
+    if !next_is_letter {
+      let end = chars.get(j).map(|(pos, _)| *pos).unwrap_or(text.len());
+      labels.push((start, end));
+    }
+    i = j;
+  }
+  labels
+}
+
+/// 記号の使われ方を調べ、ギリシャ文字・キリル文字の混入や全角・半角の混在を検出する
+///
+/// `fix`が真の場合は、確立した記号に対応するものを確立した表記に書き換えたテキストも返す
+pub fn check(text: &str, fix: bool) -> (Vec<LabelIssue>, Option<String>) {
+  let positions = find_labels(text);
+  let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+  for (start, end) in &positions {
+    *counts.entry(&text[*start..*end]).or_default() += 1;
+  }
+  // 正しい文字で書かれ、複数回出現する記号を確立した記号とし、その表記（全角か半角か）を記録する
+  let mut established: BTreeMap<String, (bool, usize)> = BTreeMap::new();
+  for (label, count) in &counts {
+    let is_proper = !label.chars().any(|c| look_alike(c).is_some());
+    if is_proper && *count >= ESTABLISHED_MIN_COUNT {
+      let full_width = label.chars().next().map(is_full_width).unwrap_or(false);
+      let entry = established
+        .entry(canonical(label))
+        .or_insert((full_width, 0));
+      if *count > entry.1 {
+        *entry = (full_width, *count);
+      }
+    }
+  }
+  let mut issues = Vec::new();
+  let mut replacements: BTreeMap<&str, String> = BTreeMap::new();
+  for (label, count) in &counts {
+    let key = canonical(label);
+    let has_look_alike = label.chars().any(|c| look_alike(c).is_some());
+    let suggestion = established
+      .get(&key)
+      .map(|(full_width, _)| with_width(&key, *full_width))
+      .filter(|s| s != label);
+    match suggestion {
+      Some(suggestion) => {
+        if fix {
+          replacements.insert(label, suggestion.clone());
+        }
+        issues.push(LabelIssue {
+          label: label.to_string(),
+          count: *count,
+          suggestion: Some(suggestion),
+          corrected: fix,
+        });
+      }
+      None if has_look_alike => issues.push(LabelIssue {
+        label: label.to_string(),
+        count: *count,
+        suggestion: Some(key),
+        corrected: false,
+      }),
+      None => (),
+    }
+  }
+  let fixed = (fix && !replacements.is_empty()).then(|| {
+    let mut s = String::with_capacity(text.len());
+    let mut last = 0;
+    for (start, end) in &positions {
+      if let Some(replacement) = replacements.get(&text[*start..*end]) {
+        s.push_str(&text[last..*start]);
+        s.push_str(replacement);
+        last = *end;
+      }
+    }
+    s.push_str(&text[last..]);
+    s
+  });
+  (issues, fixed)
+}
@@ -0,0 +1,168 @@
+//! `--extract-statutes`で取り出した引用の法令名を、e-Gov法令検索の法令IDに対応付ける
+//!
+//! 判決でよく引用される法令は組み込みの表で対応付け、それ以外は`--law-ids`で指定したJSONファイル（法令名から法令IDへのオブジェクト）で補う。
+//! 対応付けた引用には法令IDとe-Gov法令検索のURLを付ける
+
+use crate::statute::StatuteCitation;
+use anyhow::Result;
+use std::collections::BTreeMap;
+use tokio::fs;
+
+/// e-Gov法令検索で法令を表示するURLの前半
+const EGOV_LAW_URL: &str = "https://laws.e-gov.go.jp/law/";
+
+/// 法令名（略称を含む）と法令ID
+const BUNDLED: [(&str, &str); 58] = [
+  ("日本国憲法", "321CONSTITUTION"),
+  ("憲法", "321CONSTITUTION"),
+  ("民法", "129AC0000000089"),
+  ("商法", "132AC0000000048"),
+  ("刑法", "140AC0000000045"),
+  ("会社法", "417AC0000000086"),
+  ("民事訴訟法", "408AC0000000109"),
+  ("民訴法", "408AC0000000109"),
+  ("刑事訴訟法", "323AC0000000131"),
+  ("刑訴法", "323AC0000000131"),
+  ("行政事件訴訟法", "337AC0000000139"),
+  ("行訴法", "337AC0000000139"),
+  ("国家賠償法", "322AC0000000125"),
+  ("国賠法", "322AC0000000125"),
+  ("行政手続法", "405AC0000000088"),
+  ("行政不服審査法", "426AC0000000068"),
+  ("地方自治法", "322AC0000000067"),
+  ("裁判所法", "322AC0000000059"),
+  ("弁護士法", "324AC1000000205"),
+  ("民事執行法", "354AC0000000004"),
+  ("民事保全法", "401AC0000000091"),
+  ("破産法", "416AC0000000075"),
+  ("人事訴訟法", "415AC0000000109"),
+  ("家事事件手続法", "423AC0000000052"),
+  ("借地借家法", "403AC0000000090"),
+  ("消費者契約法", "412AC0000000061"),
+  ("労働基準法", "322AC0000000049"),
+  ("労基法", "322AC0000000049"),
+  ("労働契約法", "419AC0000000128"),
+  ("労契法", "419AC0000000128"),
+  ("労働組合法", "324AC0000000174"),
+  ("労組法", "324AC0000000174"),
+  ("著作権法", "345AC0000000048"),
+  ("特許法", "334AC0000000121"),
+  ("商標法", "334AC0000000127"),
+  ("不正競争防止法", "405AC0000000047"),
+  ("不競法", "405AC0000000047"),
+  (
+    "私的独占の禁止及び公正取引の確保に関する法律",
+    "322AC0000000054",
+  ),
+  ("独占禁止法", "322AC0000000054"),
+  ("独禁法", "322AC0000000054"),
+  ("所得税法", "340AC0000000033"),
+  ("法人税法", "340AC0000000034"),
+  ("相続税法", "325AC0000000073"),
+  ("消費税法", "363AC0000000108"),
+  ("国税通則法", "337AC0000000066"),
+  ("公職選挙法", "325AC1000000100"),
+  ("公選法", "325AC1000000100"),
+  ("道路交通法", "335AC0000000105"),
+  ("道交法", "335AC0000000105"),
+  ("少年法", "323AC0000000168"),
+  ("覚醒剤取締法", "326AC0000000252"),
+  ("覚せい剤取締法", "326AC0000000252"),
+  ("出入国管理及び難民認定法", "326CO0000000319"),
+  ("入管法", "326CO0000000319"),
+  (
+    "行政機関の保有する情報の公開に関する法律",
+    "411AC0000000042",
+  ),
+  ("情報公開法", "411AC0000000042"),
+  ("個人情報の保護に関する法律", "415AC0000000057"),
+  ("個人情報保護法", "415AC0000000057"),
+];
+
+pub struct LawIds {
+  ids: BTreeMap<String, String>,
+}
+
+impl LawIds {
+  /// 組み込みの表を`path`のJSONファイルで上書きする
+  pub async fn load(path: Option<&str>) -> Result<Self> {
+    let mut ids = BUNDLED
+      .iter()
+      .map(|(law, id)| (law.to_string(), id.to_string()))
+      .collect::<BTreeMap<_, _>>();
+    if let Some(path) = path {
+      let s = fs::read_to_string(path).await?;
+      ids.extend(serde_json::from_str::<BTreeMap<String, String>>(&s)?);
+    }
+    Ok(LawIds { ids })
+  }
+
+  /// 対応付けられた引用に法令IDとURLを付ける
+  pub fn resolve(&self, citations: &mut [StatuteCitation]) {
+    for citation in citations {
+      if let Some(id) = self.ids.get(&citation.law) {
+        citation.url = Some(format!("{EGOV_LAW_URL}{id}"));
+        citation.law_id = Some(id.clone());
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::statute;
+
+  #[tokio::test]
+  async fn load_and_resolve() {
+    let path = std::env::temp_dir().join(format!(
+      "pdf2txt_precedent_law_ids_{}.json",
+      std::process::id()
+    ));
+    fs::write(
+      &path,
+      r#"{"民法": "USER00000000001", "架空法": "USER00000000002"}"#,
+    )
+    .await
+    .unwrap();
+    let law_ids = LawIds::load(Some(&path.to_string_lossy())).await;
+    fs::remove_file(&path).await.unwrap();
+    let law_ids = law_ids.unwrap();
+    let mut citations = statute::extract(
+      "民法709条、弁護士法72条、公職選挙法138条1項、架空法3条及び未登録法5条によれば",
+    );
+    law_ids.resolve(&mut citations);
+    let resolved = citations
+      .iter()
+      .map(|c| (c.law.as_str(), c.law_id.as_deref(), c.url.as_deref()))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      resolved,
+      vec![
+        (
+          "民法",
+          Some("USER00000000001"),
+          Some("https://laws.e-gov.go.jp/law/USER00000000001")
+        ),
+        (
+          "弁護士法",
+          Some("324AC1000000205"),
+          Some("https://laws.e-gov.go.jp/law/324AC1000000205")
+        ),
+        (
+          "公職選挙法",
+          Some("325AC1000000100"),
+          Some("https://laws.e-gov.go.jp/law/325AC1000000100")
+        ),
+        (
+          "架空法",
+          Some("USER00000000002"),
+          Some("https://laws.e-gov.go.jp/law/USER00000000002")
+        ),
+        ("未登録法", None, None),
+      ]
+    );
+    let bundled = LawIds::load(None).await.unwrap();
+    assert_eq!(bundled.ids["民法"], "129AC0000000089");
+  }
+}
@@ -0,0 +1,171 @@
+//! 縦書きの本文と横書きの表が混在するページを領域に分け、領域ごとに文字の向きに合わせたOCRを行う
+
+use crate::{
+  hocr::{self, BBox},
+  tesseract,
+};
+use anyhow::Result;
+use regex::Regex;
+use tokio::{fs, process::Command};
+
+/// 文字の向き
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+  Horizontal,
+  Vertical,
+}
+
+/// tesseractのレイアウト解析で得られたテキストの領域
+#[derive(Debug, Clone, Copy)]
+pub struct Region {
+  pub bbox: BBox,
+  pub direction: Direction,
+}
+
+/// 行の`title`属性から縦書きの行かどうかを判定する
+///
+/// tesseractは縦書きと判定した行に`textangle 90`を付ける。付いていない場合も、1文字分の幅よりも十分に縦長な行は縦書きとする
+fn is_vertical_line(title: &str, bbox: &BBox) -> bool {
+  let textangle_re = Regex::new(r"textangle (90|270)").unwrap();
+  let width = bbox.x1.saturating_sub(bbox.x0);
+  textangle_re.is_match(title) || bbox.height() > width * 3
+}
+
+/// hOCRの`ocr_carea`を領域とし、含まれる行の過半数が縦書きであれば縦書きの領域とする
+pub fn regions(hocr: &str) -> Vec<Region> {
+  let element_re = Regex::new(
+    r#"class=['"](?P<class>ocr_carea|ocr_line|ocr_textfloat|ocr_caption|ocr_header)['"][^>]*title=['"](?P<title>[^'"]*)['"]"#,
+  )
+  .unwrap();
+  // 領域と、その領域に含まれる縦書きの行の数・行の数
+  let mut areas: Vec<(BBox, usize, usize)> = Vec::new();
+  for caps in element_re.captures_iter(hocr) {
+    let title = caps.name("title").map(|m| m.as_str()).unwrap_or_default();
+    let Some(bbox) = hocr::parse_bbox(title) else {
+      continue;
+    };
+    if &caps["class"] == "ocr_carea" {
+      areas.push((bbox, 0, 0));
+    } else if let Some((_, vertical, total)) = areas.last_mut() {
+      if is_vertical_line(title, &bbox) {
+        *vertical += 1;
+      }
+      *total += 1;
+    }
+  }
+  areas
+    .into_iter()
+    .filter(|(_, _, total)| *total > 0)
+    .map(|(bbox, vertical, total)| Region {
+      bbox,
+      direction: if vertical * 2 > total {
+        Direction::Vertical
+      } else {
+        Direction::Horizontal
+      },
+    })
+    .collect()
+}
+
+/// 領域を読む順に並べる
+///
+/// 上下に重なる領域を同じ段とし、段は上から順に、段の中は縦書きの領域を含めば右から、含まなければ左から読む
+pub fn reading_order(mut regions: Vec<Region>) -> Vec<Region> {
+  regions.sort_by_key(|region| (region.bbox.y0, region.bbox.x0));
+  let mut bands: Vec<(u32, Vec<Region>)> = Vec::new();
+  for region in regions {
+    match bands.last_mut() {
+      Some((bottom, band)) if region.bbox.y0 < *bottom => {
+        *bottom = (*bottom).max(region.bbox.y1);
+        band.push(region);
+      }
+      _ => bands.push((region.bbox.y1, vec![region])),
+    }
+  }
+  bands
+    .into_iter()
+    .flat_map(|(_, mut band)| {
+      if band
+        .iter()
+        .any(|region| region.direction == Direction::Vertical)
+      {
+        band.sort_by_key(|region| std::cmp::Reverse(region.bbox.x1));
+      } else {
+        band.sort_by_key(|region| region.bbox.x0);
+      }
+      band
+    })
+    .collect()
+}
+
+/// 画像の一部を切り出し、文字の向きに合わせた言語モデルと解析方法でOCRを行う
+///
+/// 縦書きの領域は`jpn_vert`を使い、縦書きの1ブロックとして解析する
+async fn recognize_region(
+  file_path: &str,
+  region_path: &str,
+  region: &Region,
+  threads: Option<usize>,
+) -> Result<(String, String)> {
+  let BBox { x0, y0, x1, y1 } = region.bbox;
+  let output = Command::new("convert")
+    .kill_on_drop(true)
+    .arg(file_path)
+    .arg("-crop")
+    .arg(format!(
+      "{}x{}+{x0}+{y0}",
+      x1.saturating_sub(x0),
+      y1.saturating_sub(y0)
+    ))
+    .arg("+repage")
+    .arg(region_path)
+    .output()
+    .await?;
+  let mut err = String::from_utf8_lossy(&output.stderr).to_string();
+  let (lang, psm) = match region.direction {
+    Direction::Horizontal => ("jpn", "6"),
+    Direction::Vertical => ("jpn_vert", "5"),
+  };
+  let output = tesseract::command(threads)
+    .arg(region_path)
+    .arg("stdout")
+    .arg("-l")
+    .arg(lang)
+    .arg("--psm")
+    .arg(psm)
+    .output()
+    .await?;
+  err.push_str(&String::from_utf8_lossy(&output.stderr));
+  Ok((String::from_utf8_lossy(&output.stdout).to_string(), err))
+}
+
+/// `{name}.hocr`の領域に縦書きのものがあれば、ページの画像`image_path`を領域ごとに認識し直したテキストを返す
+///
+/// 縦書きの領域が無いページは`None`を返し、元の認識結果をそのまま使う。2つ目の値はエラー出力
+pub async fn recognize(
+  name: &str,
+  image_path: &str,
+  hocr: &str,
+  threads: Option<usize>,
+) -> Result<(Option<String>, String)> {
+  let regions = regions(hocr);
+  if !regions
+    .iter()
+    .any(|region| region.direction == Direction::Vertical)
+  {
+    return Ok((None, String::new()));
+  }
+  let mut texts = Vec::new();
+  let mut err = String::new();
+  for (i, region) in reading_order(regions).iter().enumerate() {
+    let region_path = format!("{name}-region-{}.jpg", i + 1);
+    let (text, region_err) = recognize_region(image_path, &region_path, region, threads).await?;
+    err.push_str(&region_err);
+    fs::remove_file(&region_path).await.ok();
+    let text = text.trim();
+    if !text.is_empty() {
+      texts.push(text.to_string());
+    }
+  }
+  Ok((Some(texts.join("\n\n")), err))
+}
@@ -0,0 +1,123 @@
+//! 処理の進み具合や警告を表示する
+//!
+//! `--log-format json`の場合は、クラスタで動かした際のログをElasticsearchやLokiなどにそのまま取り込めるように、
+//! 1行に1つのJSONオブジェクト（日時・レベル・段階・事件・メッセージ・所要時間）を標準エラー出力か`--log-file`に書き出す
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{
+  fmt,
+  fs::{File, OpenOptions},
+  io::Write,
+  sync::Mutex,
+  time::Duration,
+};
+
+/// ログの形式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+  /// `[START] write: {事件の名前}`のような行
+  #[default]
+  Text,
+  /// 1行に1つのJSONオブジェクト
+  Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Level {
+  Info,
+  Warn,
+  Error,
+}
+
+struct Logger {
+  format: LogFormat,
+  /// 指定されていない場合は、`text`では情報を標準出力に、警告とエラーを標準エラー出力に、`json`では全て標準エラー出力に書き出す
+  file: Option<File>,
+}
+
+static LOGGER: Mutex<Logger> = Mutex::new(Logger {
+  format: LogFormat::Text,
+  file: None,
+});
+
+/// 起動時に一度だけ呼ぶ `file`は追記する
+pub fn init(format: LogFormat, file: Option<&str>) -> Result<()> {
+  let file = match file {
+    Some(path) => Some(OpenOptions::new().create(true).append(true).open(path)?),
+    None => None,
+  };
+  let mut logger = LOGGER.lock().unwrap_or_else(|err| err.into_inner());
+  *logger = Logger { format, file };
+  Ok(())
+}
+
+#[derive(Serialize)]
+struct Event<'a> {
+  time: String,
+  level: Level,
+  stage: &'a str,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  case: Option<&'a str>,
+  message: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  duration_ms: Option<u128>,
+}
+
+/// 1つの出来事を書き出す
+///
+/// `stage`は`START`・`Download`などの段階の名前で、`text`の場合は`[{stage}] {message}`と表示する。警告とエラーは`[WARNING]`・`[ERROR]`とする
+pub fn emit(
+  level: Level,
+  stage: &str,
+  case: Option<&str>,
+  duration: Option<Duration>,
+  message: impl fmt::Display,
+) {
+  let mut logger = LOGGER.lock().unwrap_or_else(|err| err.into_inner());
+  let line = match logger.format {
+    LogFormat::Text => {
+      let tag = match level {
+        Level::Info => stage,
+        Level::Warn => "WARNING",
+        Level::Error => "ERROR",
+      };
+      format!("[{tag}] {message}")
+    }
+    LogFormat::Json => {
+      let event = Event {
+        time: crate::cache::now_rfc3339(),
+        level,
+        stage,
+        case,
+        message: message.to_string(),
+        duration_ms: duration.map(|duration| duration.as_millis()),
+      };
+      serde_json::to_string(&event).unwrap_or_default()
+    }
+  };
+  let format = logger.format;
+  // ログを書き出せなくても処理は続ける
+  match &mut logger.file {
+    Some(file) => {
+      let _ = writeln!(file, "{line}");
+    }
+    None if format == LogFormat::Text && level == Level::Info => println!("{line}"),
+    None => eprintln!("{line}"),
+  }
+}
+
+pub fn info(stage: &str, case: Option<&str>, message: impl fmt::Display) {
+  emit(Level::Info, stage, case, None, message);
+}
+
+pub fn warn(stage: &str, case: Option<&str>, message: impl fmt::Display) {
+  emit(Level::Warn, stage, case, None, message);
+}
+
+pub fn error(stage: &str, case: Option<&str>, message: impl fmt::Display) {
+  emit(Level::Error, stage, case, None, message);
+}
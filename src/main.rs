@@ -14,6 +14,8 @@
 //! cargo install --git "https://github.com/japanese-law-analysis/pdf2txt_precedent.git"
 //! ```
 //!
+//! 実行の結果をSlackやメールで知らせる`--notify-*`を使う場合は`--features notify`を付けてインストールします。
+//!
 //! # How to use
 //!
 //! ## 基本的な使い方
@@ -24,51 +26,574 @@
 //!
 //! で起動します。与えるJSONファイルは[listup_precedent](https://github.com/japanese-law-analysis/listup_precedent)で生成されるものです。
 //!
-//! 起動するとその場にtmpフォルダが作られ、そこに各PDFファイルなどがダウンロード・生成されます。
+//! 起動するとその場にtmpフォルダが作られ、そこに各PDFファイルなどがダウンロード・生成されます。OCRに用いるページの画像は1ページずつ生成し、そのページのOCRが終わると削除します。キャッシュしたPDFファイルに`%PDF-`のヘッダが無い場合（以前に404やメンテナンス中のページを保存してしまった場合など）はダウンロードし直し、ダウンロードしたファイルにヘッダが無い場合はエラーとします。キャッシュしたPDFファイルからの抽出に失敗した場合（`pdfinfo`や画像への変換ができない途中で切れたファイルなど）は、キャッシュを削除して一度だけダウンロードからやり直します。
 //!
 //! そして`pdf2txt_precedent`を起動したディレクトリに各判例テキストファイルが生成されます。
 //!
 //! ファイル名は`{事件番号}_{year}_{month}_{day}_{裁判の種類}.txt`形式です。年月日は判決日です。
 //!
+//! すでに同じ内容のファイルがある場合は、後段の更新日時を使うキャッシュを無効にしないように書き出しません。
+//!
+//! 実行が終わると、事件ごとの結果（`written`：書き出した、`unchanged`：内容が変わらなかったため書き出さなかった、`skipped`：生成済みのため処理しなかった、`excluded`：`--exclude-list`に含まれるため処理しなかった、`before_since`：判決日が`--since`より前のため処理しなかった、`too_large`：PDFファイルが`--max-pdf-size`より大きいため処理しなかった、`failed`：処理に失敗した、`interrupted`：Ctrl+CかSIGTERMで中断した）を処理した順に並べた`report.json`が出力フォルダに書き出されます。処理した事件には、段階ごとにかかった時間（`download_ms`：ダウンロード、`conversion_ms`：pdftotext・pdftoppmなどによる変換と画像の加工、`ocr_ms`：OCR、`postprocess_ms`：テキストの加工と出力ファイルの書き出し、`total_ms`：同時に処理する数の制限で待った時間も含めた全体）も記録するため、極端に時間がかかる文書を見つけたり、全ての判例を処理するのに必要な計算資源を見積もったりできます。処理した事件には、PDFファイルのページ数（`pages`）と、抽出したテキストの語のうち日本語の文書に現れにくい語ではないものの割合（`quality`、0〜1）も記録します。同じ内容を事件ごとに1行（事件の名前・結果・`--mode`・ページ数・段階ごとの時間・`quality`・出力ファイルのpath）にまとめた`summary.csv`も書き出すため、表計算ソフトで結果を確かめられます。処理に失敗した事件があった場合も残りの事件は処理を続け、最後にエラーとして終了します。
+//!
+//! pdftotextやtesseractなどのコマンドのエラー出力は事件ごとの`tmp/{事件の名前}_err.txt`に加えて、全ての事件の分を`tmp/errors.jsonl`（`--err-dir`を指定した場合はそのフォルダ）に1行に1つのJSONオブジェクト（`time`・`case`・`stage`・`page`・`stderr`）として追記します。処理に失敗した事件はその理由を`stage`が`failed`の行として追記します。
+//!
+//! また、出力フォルダの全ての出力ファイルについて、ファイルのSHA-256・大きさ・元の事件・記録した日時・生成した際の設定（このソフトウェアのバージョン、`--mode`・`--format`、コマンドライン引数、既定値を含めた全てのオプション、tesseract・poppler・ImageMagickのバージョン。認証情報は記録しません）を出力フォルダの`manifest.json`に書き出します。出力フォルダを受け取った側が、ファイルが壊れていないことや同期が途中で止まっていないことを確かめたり、何年か後に結果を再現したりするために使えます。内容が変わらなかったファイルは以前の記録のままにし、以前の実行で生成したファイルは設定を記録せずに加えます。
+//!
+//! 実行中は、進み具合（処理している事件、結果ごとの事件の数、終わるまでの見込みの時間`eta_seconds`・`eta`、最近のエラー）を出力フォルダの`status.json`に書き直し続けます。実行が終わると`state`が`finished`に、中断すると`aborted`になるため、監視のスクリプトなどから読めます。
+//!
+//! OCRを行う場合は、ページごとにOCRを終えるたびに、PDFファイルのSHA-256と終えたページを`tmp/{事件の名前}.ocr.json`に記録します。事件の処理が途中で止まった場合は、次に実行した際にPDFファイルが変わっていなければ、OCRを終えたページはOCRし直さずに残りのページのみOCRします。事件のOCRを全て終えると記録は削除します。
+//!
+//! 実行中にCtrl+Cを押すか、SIGTERMを送ると、処理中の事件を中断して（tesseractなどの子プロセスも終了させて）出力ファイルは書き出さずに、`tmp/manifest.json`・`report.json`・`status.json`を書き出してから終了します。中断した事件は出力ファイルが無いため、もう一度実行すると続きから処理されます。もう一度Ctrl+Cを押すとすぐに終了します。
+//!
+//! Unixでは、実行中のプロセスにSIGUSR1を送ると新しい事件の処理を始めるのを止め（処理中の事件はそのまま終わらせます）、SIGUSR2を送ると再開します。止めている間は`status.json`の`state`が`paused`になります。業務時間中にマシンを空ける場合などに使います。
+//!
+//! ```sh
+//! kill -USR1 $(pgrep pdf2txt_precedent)
+//! kill -USR2 $(pgrep pdf2txt_precedent)
+//! ```
+//!
+//! `--daemon`を付けると、一覧を処理し終えても終了せずに、`--daemon-interval`（秒、既定は3600）が経つか一覧のファイルが更新されるたびに処理し直します。生成済みの出力ファイルがある事件は処理しないため、一覧に加わった事件のみが処理されます。設定ファイルは処理し直すたびに読み込み直し、SIGHUPを送ると待たずに読み込み直して処理し直します。systemdの`Type=notify`のサービスとして動かすと、準備ができたことを知らせ、`WatchdogSec`を指定した場合は動き続けていることも知らせます。
+//!
+//! ```ini
+//! [Service]
+//! Type=notify
+//! ExecStart=/usr/local/bin/pdf2txt_precedent --daemon --input /srv/precedent/input.json --output /srv/precedent/output --tmp /srv/precedent/tmp
+//! ExecReload=/bin/kill -HUP $MAINPID
+//! WatchdogSec=60
+//! ```
+//!
 //! ## オプション
 //!
 //! - `--tmp`：一時フォルダのフォルダ名を変更することができる
 //! - `--output`：生成ファイルを出力するフォルダを変更することができる
 //! - `--mode`：テキスト抽出に用いる技術を選ぶことができる
-//!   - `p2t`：`pdftotext`コマンドを使用した抽出を行う
+//!   - `p2t`：`pdftotext`コマンドを使用した抽出を行う。ほとんど文字を取り出せなかったページ（スキャンしたページなど）のみOCRを用い、そのページ番号を`[Fallback]`として表示する。`(cid:123)`や私用領域の文字で文字化けしたテキストしか取り出せなかった場合は、テキストレイヤーが使えないとして`[Text Layer Unusable]`と警告し、事件全体をOCRで処理し直す。そのような事件は`report.json`で`text_layer_unusable`が`true`になる
 //!   - `ocr`：OCRを用いた抽出を行う
+//!   - `ensemble`：ページごとに`pdftotext`で取り出したテキストを調べ、十分な文字があり文字化けしていなければそのまま使い、テキストレイヤーが無いページや文字化けしたページのみOCRを用いる。スキャンしたページが混ざったPDFファイルに用いる
+//!   - `both`：事件ごとに`p2t`と`ocr`の両方で抽出し、出力フォルダの`p2t/`と`ocr/`にそれぞれ書き出す。PDFファイルは1度だけダウンロードする。2つの方法を比べる評価用のデータセットを作るために用いる。`summary.csv`と`index.html`からは`ocr/`の出力を示す
+//!   - `cloud-vision`：ページを`--dpi`の解像度のJPEGにしてGoogle Cloud VisionのAPI（`DOCUMENT_TEXT_DETECTION`）でOCRを行う。APIキーは環境変数`GOOGLE_CLOUD_VISION_API_KEY`で渡す。`--crop`などの画像の加工は行わない。PDFファイルのページの画像がGoogleのサーバに送られることに注意する
 //! - `--do-not-use-cache`：PDFファイルがtmpフォルダにすでに存在している場合でも再度ダウンロードを実行ようにする
 //! - `--force-re-run`：すでに生成済みテキストファイルが存在している場合でも再度処理を実行する
+//! - `--rerun-list`：すでに生成済みテキストファイルが存在している場合でも再度処理を実行する事件を、事件名（入力のJSONファイルのキー）か事件番号で1行に1件ずつ書いたファイルを指定する。空行と`#`で始まる行は無視される
+//! - `--exclude-list`：壊れていることが分かっているPDFファイルや対象外の事件など、ダウンロードも含めて処理しない事件を`--rerun-list`と同じ形式で書いたファイルを指定する
+//! - `--since`：判決日が指定した日付（`2024-04-01`のような形式）以降の事件のみを処理する。状態を保存するファイルを使わずに、cronなどで毎晩新しい裁判例のみを処理する際に用いる。判決日は一覧のJSONファイルの`date`か、無ければファイル名から求め、分からない事件は処理する
+//! - `--court`：指定した裁判所の事件のみを処理する。`東京地裁`・`東京地方裁判所立川支部`のような表記か、`tokyo-district`のようなコードで指定し、複数回指定できる。支部を指定しない場合はその支部の事件も処理する。裁判所は一覧のJSONファイルの`court_name`（無ければ`court`）から求め、分からない事件は処理する
+//! - `--normalize-court`：一覧のJSONファイルの裁判所名（`東京地裁`・`東京地判`などの略称を含む）を、正式名称（`name`）・コード（`code`）・支部（`branch`）に揃えてJSONファイルの`court`に出力する。コードは`{所在地}-{種類}`（`tokyo-district`・`osaka-high`・`naha-family`など）で、最高裁判所は`supreme`、知的財産高等裁判所は`ip-high`になる。`--extract-precedents`で取り出した引用の裁判所も`normalized_court`として揃える
+//! - `--parse-case-number`：一覧のJSONファイルの事件番号（`令和3年(ワ)第1234号`や`令和3(ワ)1234`のような形式）を元号（`era`）・年（`year`）・事件記号（`symbol`）・番号（`serial`）に分け、JSONファイルの`case_numbers`に出力する。併合された事件の番号が続く場合は全てを出力する。読めない事件番号は警告を表示する
+//! - `--check-consistency`：一覧のJSONファイルの事件番号（併合された事件はいずれか）と判決日が、抽出したテキストに現れるかを調べ、見つからないものを警告し、`report.json`の`mismatches`に項目（`field`）・一覧のJSONファイルの値（`expected`）・テキストに最初に現れた事件番号か日付（`found`）を出力する。PDFファイルのリンクの誤りやOCRが大きく崩れた事件を見つけるために用いる
+//! - `--html-report`：事件ごとの結果・ページ数・`quality`・最初のページの縮小画像（pdftoppmで`thumbnails/`に書き出す）・テキストの冒頭を並べた`index.html`を出力フォルダに書き出す。OCRが大きく崩れた事件や、判決文ではないPDFファイルを目で見て見つけるために用いる
+//! - `--compress-cache`：tmpフォルダに保存するPDFファイルや中間テキストファイルをzstdで圧縮する
+//! - `--compress-output`：生成するテキストファイルをgzipで圧縮し、`{事件番号}_{year}_{month}_{day}_{裁判の種類}.txt.gz`として出力する
+//! - `--drop-ruby`：OCRの際にtesseractのhOCR出力から行の大きさと位置を調べ、本文の行の直上にある小さなかなのみの行をルビ（ふりがな）として取り除く
+//! - `--detect-redactions`：OCRの際にページ画像から黒く塗りつぶされた矩形領域を検出し、その部分を`[redacted]`という目印に置き換える。枠線で囲んで中を白く抜いた領域は、空欄の表の枠と区別できないため検出しない。検出した領域のページ番号と座標は`{事件番号}_{year}_{month}_{day}_{裁判の種類}.json`に出力される
+//! - `--reocr-confidence`：OCRの際にtesseractのhOCR出力から単語の信頼度の平均（0〜100）を求め、指定した値より低いページは、画像をグレースケールにしてコントラストを整え、輪郭を強めてからOCRし直し、信頼度が高い方の結果を使う。OCRし直したページと、それぞれの信頼度、OCRし直した結果を使ったかどうかは`{事件番号}_{year}_{month}_{day}_{裁判の種類}.json`の`ocr_escalations`に出力される
+//! - `--flag-suspicious-pages`：ページのテキストを文字の種類ごとの語に分け、日本語の文書で使われない文字や、漢字に挟まれた1文字のカタカナ、日本語に挟まれた短い英字、続いた記号などの未知の語の割合（0〜1）が指定した値より大きいページを、OCRの結果が崩れているとみなして`report.json`の`suspicious_pages`にページ番号と割合を出力する。形態素解析の辞書は使わないため、別の漢字に誤った語は数えられない
+//! - `--disagreement-threshold`：`--mode both`の際に、事件ごとに`p2t/`と`ocr/`のテキストの、空白を除いた文字の2-gramの重なり（Dice係数、0〜1）を`report.json`の`similarity`に記録し、この値（既定値は0.5）より小さい事件は`disagrees`を`true`にして警告する。`--html-report`では黄色で示す。どちらかの方法で文字化けや読み落としがある文書を見つけるために用いる
+//! - `--split-layout`：OCRの際にtesseractのレイアウト解析の結果からページをテキストの領域に分け、縦書きの行が多い領域を含むページは、縦書きの領域を`jpn_vert`で、横書きの領域（表など）を`jpn`で認識し直す。領域は上から順に、縦書きの領域を含む段は右から並べる。`tesseract-ocr-jpn-vert`が必要
+//! - `--max-bandwidth`：並行して行う全てのダウンロードを合わせた速さの上限を`2MB/s`や`500KB/s`のように指定する（`K`・`M`・`G`は1024倍ずつ）。共有の回線を使い切らないようにするために用いる
+//! - `--respect-robots`：PDFファイルのホストの`robots.txt`を取得し、`pdf2txt_precedent`か`*`に対する`Disallow`で禁止されたURLはダウンロードせず、`Crawl-delay`で指定された間隔を空けてダウンロードする。禁止されたURLは見つからない場合と同様に次の候補のURLを試す。`--max-pdf-size`で大きさを調べるHEADリクエストも同じく扱う
+//! - `--auth-token`：`--auth-host`で指定したホストへのリクエストに`Authorization: Bearer`で付けるトークンを指定する
+//! - `--auth-basic`：`--auth-host`で指定したホストへのリクエストに付けるBasic認証の`user:pass`を指定する
+//! - `--auth-host`：`--auth-token`・`--auth-basic`を送るホスト名（`host`か`host:port`）を指定する。複数回指定でき、指定しない場合は`--mirror-base`のホストとなる。他のホストへのリクエストには認証情報を付けない
+//! - `--ca-cert`：TLSの証明書を検証する際に追加で信頼するCA証明書（PEM形式、複数可）のファイルを指定する。TLSを復号して署名し直すプロキシを通す場合に用いる
+//! - `--insecure-tls`：TLSの証明書を検証しない。通信の改ざんを検出できなくなるため、起動時に警告を表示する。`--ca-cert`を使えない場合に限って用いる
+//! - `--connect-timeout`：サーバへの接続を待つ時間の上限を秒で指定する（既定は30秒）
+//! - `--request-timeout`：1つのファイルのダウンロードを始めてから終わるまでの時間の上限を秒で指定する（既定は600秒）
+//! - `--mirror-base`：`full_pdf_link`のPDFファイルが見つからない（404）場合に試すミラーサーバのURLを指定する。元のURLのpathをこのURLの後に繋げたものをダウンロードする。入力のJSONファイルの各事件に`fallback_pdf_links`としてURLの配列を書いた場合は、ミラーサーバより先にその順で試す。ダウンロードに使ったURLは`tmp/manifest.json`の`source`に記録される
+//! - `--max-download-size`：ダウンロードするファイルの大きさ（バイト数）の上限を指定する。ダウンロードしたファイルは受け取った分から書き込まれ、上限を超えた時点で止めてエラーにする
+//! - `--image-cache`：pdftoppmでページを変換した画像を、PDFファイルのSHA-256・解像度・形式ごとに`tmp/images/`に残し、処理し直す際に使う。OCRの設定を変えて処理し直す場合に、時間のかかる画像への変換を繰り返さずに済む。キャッシュしたPDFファイルの一覧に無いPDFファイルの画像は`cache gc`で削除する
+//! - `--max-pdf-size`：PDFファイルの大きさ（バイト数）の上限を指定する。ダウンロードする場合は`Content-Length`を、キャッシュがある場合は`tmp/manifest.json`の記録かファイルの大きさを調べ、上限より大きいものは処理せずに`report.json`に`too_large`と記録する
+//! - `--strict`：CIなどでデータセットを作る際に、欠けた事件があることに気付けるように、処理に失敗した事件（既定でもエラーとして終了する）に加えて、`--max-pdf-size`より大きいため処理しなかった事件があった場合も、残りの事件を処理してからエラーとして終了する
+//! - `--abort-on-failure-rate`：最近処理した`--failure-window`件（既定は20件）の事件のうち、処理に失敗した割合が指定した値（`20%`のように指定する）を超えたら、処理中の事件を中断して残りの事件の処理を止め、エラーとして終了する。失敗が続く場合はサイトにアクセスを拒まれているか依存するコマンドが壊れていることが多く、続けても時間を無駄にするため。止めた事件は`interrupted`として記録するため、もう一度実行すると続きから処理される
+//! - `--max-pages`：1つのPDFファイルにつき最初のNページのみを処理する。省略したページがある場合は出力の末尾に`[truncated: N of {全体のページ数} pages]`という注記を付ける。長い記録の添付が不要な下調べの際に用いる
+//! - `--daemon`：一覧を処理し終えても終了せずに、一定の間隔か一覧のファイルが更新されるたびに処理し直す
+//! - `--daemon-interval`：`--daemon`の際に処理し直す間隔（秒）を指定する（既定は3600）
+//! - `--lang-messages`：エラー・警告・実行の結果の概要などのメッセージの言語を`ja`（既定）か`en`で指定する。日本語を読めない人がパイプラインを運用する場合に用いる。事件ごとの進み具合を示す`[START]`などの行は変わらない
+//! - `--log-format`：進み具合や警告のログの形式を`text`（既定）か`json`で指定する。`json`の場合は、1行に1つのJSONオブジェクト（`time`・`level`・`stage`・`case`・`message`・`duration_ms`）を標準エラー出力に書き出すため、クラスタで動かした際のログをElasticsearchやLokiなどにそのまま取り込める。`duration_ms`はダウンロードや事件の処理を終えた際の所要時間。`--log-file`を指定した場合は標準出力・標準エラー出力の代わりにそのファイルに追記する
+//! - `--status-file`：進み具合を書き出すファイルを指定する（既定は出力フォルダの`status.json`）
+//! - `--err-dir`：事件ごとの`_err.txt`と`errors.jsonl`を書き出すフォルダを指定する（既定は一時フォルダ）。一時フォルダを消してもエラーの記録が残るように、出力フォルダの隣などに置く場合に用いる
+//! - `--quarantine`：処理に失敗した事件のPDFファイル・エラー出力・途中までの出力ファイルを移すフォルダを指定する（既定は`quarantine`）。事件ごとのフォルダに移し、失敗した理由を`reason.txt`に書き出す
+//! - `--notify-slack-webhook`：実行が終わった、または中断した際に、処理した事件の数・失敗した事件の数・所要時間をSlackのIncoming Webhookに送る。`--daemon`の際は処理し直すたびに送る（`notify`featureが必要）
+//! - `--notify-email`：`--notify-slack-webhook`と同じ内容をメールで送る宛先を指定する。TLSや認証を使わずに`--smtp-server`（既定は`localhost:25`）に渡し、送信元は`--notify-email-from`で指定する（`notify`featureが必要）
+//! - `--download-jobs`：同時にダウンロードする事件の数（既定は1）
+//! - `--ocr-jobs`：同時にテキストの抽出（`pdftotext`やOCR）を行う事件の数（既定はCPUの数）。ダウンロードとは別に数えられるため、ダウンロードは少なく、抽出はCPUの数に合わせるといった使い方ができる
+//! - `--max-pending`：ダウンロードを始めてからテキストの抽出を終えるまでの事件の数の上限。ダウンロードが抽出より先に進みすぎて、一時フォルダが抽出を待つPDFファイルで埋まらないようにする。既定値は`--ocr-jobs`と`--download-jobs`の和
+//! - `--raster-jobs`：`ocr`の際に、8ページより長いPDFファイルのページを8ページずつの区間に分け、pdftoppmを区間ごとに並行して動かして画像に変換する（`-f`・`-l`）。同時に動かすpdftoppmの数は全ての事件を合わせてこの数以下に抑えられる。既定値は1で、区間に分けずに1ページずつ変換する。一時フォルダに一度に置かれる画像は、最大でこの数に8を掛けたページ分になる。`--image-cache`・`--pipe-images`と併用した場合は1ページずつ変換する
+//! - `--tesseract-threads`：tesseractが内部で使うスレッドの数を、子プロセスごとに環境変数`OMP_THREAD_LIMIT`で指定する。並行して処理を行う場合にCPUの取り合いを避けるために用いる。指定しない場合、`--ocr-jobs`が2以上であればCPUの数を`--ocr-jobs`で割った数が、そうでなければtesseractの既定値が使われる
+//! - `--nice`・`--ionice`：このプログラムのCPUの優先度（nice値、0から19）を下げ、`--ionice`の場合はI/Oの優先度をアイドルクラスにする（`renice`・`ionice`）。起動するtesseract・convert・popplerなどの子プロセスにも引き継がれるため、共有のマシンで大量の事件を処理する際に他の作業を妨げないようにできる。優先度を下げられない場合は警告してそのまま処理する
+//! - `--fallback-chain`：`p2t,ocr,cloud-vision`のように、事件ごとに順に試す方法をカンマで区切って指定する（`both`以外の`--mode`の値）。抽出したテキストがほとんど空のもの、`(cid:123)`などで文字化けしたもの、`quality`が`--fallback-quality`（既定値は0.9）より低いもの、抽出に失敗したものは質の検査を通らないとして次の方法を試す。どの方法も通らない場合は`quality`の最も高い結果を使う。使った方法はJSONファイルの`engine`に、試した方法と通らなかった理由は`engine_attempts`に出力され、次の方法に進んだ事件は`[Fallback]`として表示する。指定した場合は`--mode`は使わない（`--mode both`の場合を除く）。`--inspect-fonts`と併用した場合、文字を取り出せないフォントを含むPDFファイルでは`p2t`・`ensemble`を飛ばす
+//! - `--inspect-fonts`：`p2t`・`ensemble`の際に、PDFファイルで使われているフォントを`pdffonts`で調べ、Unicodeへの対応表（ToUnicode）の無いType 3・CIDフォントや、標準的でない符号化方式で埋め込まれていないフォントがあれば、pdftotextの出力は文字化けするため、その事件は`ocr`で処理する。OCRで処理した事件はフォント名と共に`[Inspect Fonts]`として表示する。`--mode both`の際は調べない
+//! - `--classify-pdf`：PDFファイルを、全てのページをスキャンしたもの（`scanned`）、文書作成ソフトから直接作られたもの（`born-digital`）、その混在（`hybrid`）に分け、ページ数・テキストレイヤーのあるページの数・スキャンしたページの数・フォントの数と共にJSONファイルの`pdf_kind`に出力する。pdftotextで30文字以上取り出せるページをテキストレイヤーのあるページ、それより少なく画像のある（`pdfimages -list`）ページをスキャンしたページとみなす。OCRソフトが重ねた透明なテキスト（`GlyphLessFont`）しか無い場合はスキャンしたものとする。`--mode`を選んだり、判例全体の統計を取ったりするために用いる
+//! - `--segment-paragraphs`：判決文の項目番号（`第1`・`1`・`(1)`・`ア`・`(ア)`・`a`・`(a)`）をもとにテキストを段落に分け、各段落の入れ子の深さと項目番号をJSONファイルに出力する
+//! - `--extract-parties`：主文より前に書かれた当事者（原告・被告・控訴人など）と代理人弁護士、末尾の裁判長・裁判官の名前を取り出し、JSONファイルに出力する
+//! - `--extract-statutes`：`民法七〇九条`や`会社法第423条第1項`のような法令の条文の引用を取り出し、法令名・条・項・号に正規化してJSONファイルに出力する。`同法`・`同条`や`民法709条及び710条`のような続けての引用も直前の法令として扱う
+//! - `--law-ids`：`--extract-statutes`で取り出した法令名を、組み込みの表（民法・刑法・民事訴訟法などのよく引用される法令と、`民訴法`・`独禁法`などの略称）に加えて、`{"法令名": "法令ID"}`の形式のJSONファイルでe-Gov法令検索の法令IDに対応付ける。対応付けた引用には`law_id`とe-Gov法令検索のURL（`url`）が付く。組み込みの表と同じ法令名は指定したファイルの方を使う
+//! - `--extract-precedents`：`最判平成10年3月24日`や`平成9年(オ)第123号`のような他の裁判例の引用を取り出し、裁判所・事件番号・日付・法廷・掲載誌に分けてJSONファイルに出力する
+//! - `--check-labels`：当事者などを匿名化した記号（`Ａ`・`Ｂ`・`Ｘ１`など）の使われ方を調べ、ギリシャ文字・キリル文字の混入や全角・半角の混在といったOCRの誤認識とみられるものをJSONファイルに出力する
+//! - `--fix-labels`：`--check-labels`で検出したもののうち、文書内で複数回使われている記号に対応するものをその表記に修正する
+//! - `--fix-confusions`：OCRで取り違えやすい長音「ー」と漢数字「一」、カタカナ「ロ」と漢字「口」、カタカナ「ニ」と漢数字「二」、英字「O」と漢数字「〇」を、前後の文字から意図が明らかな場合（「第ー条」「人ロ」「ユ二ット」「二Ｏ二三年」など）に限って直す
+//! - `--normalize-kyujitai`：古い判決やそのOCRの結果に現れる旧字体（辯護人の「辯」、「國」など）を、常用漢字表の新字体に直す
+//! - `--rewrite-rules`：`[{"find": "正規表現", "replace": "置き換えた後の文字列"}]`の形式のJSONファイルを指定すると、他の整形を終えたテキストに、書かれた順に置き換えを適用する。`replace`では`$1`や`${name}`で捕獲したグループを使える。ページの区切りは改ページ（`\f`）で表される
+//! - `--header-template`・`--footer-template`：指定したファイルの内容を、`--format`が`txt`か`md`の出力ファイルの最初と最後に加える。公開するデータセットに利用条件や出典を表示するために用いる。テンプレートの`{name}`は事件の名前、`{version}`はこのソフトウェアのバージョン、それ以外の`{項目名}`（`{case_number}`・`{full_pdf_link}`など）は入力のJSONファイルにある事件の情報に置き換えられ、無い項目は空になる。`{{`と`}}`は`{`と`}`になる
+//! - `--split-sections`：主文・事実及び理由・別紙（別表を含む）の見出しを検出し、それぞれを`{事件番号}_{year}_{month}_{day}_{裁判の種類}_主文.txt`・`_事実及び理由.txt`・`_別紙.txt`という別のファイルにも出力する
+//! - `--drop-appendix`：`別紙`・`別表`で始まる添付部分を検出し、出力から取り除く
+//! - `--separate-appendix`：`別紙`・`別表`で始まる添付部分を出力から取り除き、`{事件番号}_{year}_{month}_{day}_{裁判の種類}_別紙.txt`（複数ある場合は`_別紙1.txt`・`_別紙2.txt`…）として別に出力する
+//! - `--order`：事件を処理する順番を選ぶことができる。途中で止まる可能性がある場合に新しいものから処理するといった使い方ができる。どの場合も実行ごとに順番は変わらない
+//!   - `key`：入力のJSONファイルのキーの順（既定）
+//!   - `input`：入力のJSONファイルに書かれている順
+//!   - `date-asc`（`date`）：判決日の古い順。同じ日のものと判決日が分からないものはキーの順
+//!   - `date-desc`：判決日の新しい順
+//!   - `size`：`tmp/manifest.json`に記録されたPDFファイルの小さい順。記録が無いものは最後にキーの順
+//! - `--format`：生成するファイルの形式を選ぶことができる
+//!   - `txt`：プレーンテキストを`{事件番号}_{year}_{month}_{day}_{裁判の種類}.txt`として出力する（既定）
+//!   - `md`：主文・事実及び理由・別紙を`##`の見出しに、項目番号の入れ子をリストに、ページの区切りを水平線（`---`）にしたMarkdownを`{事件番号}_{year}_{month}_{day}_{裁判の種類}.md`として出力する
+//!   - `html`：事件の情報（入力のJSONファイルの各項目）を冒頭に置き、部分を`<section>`に、段落を入れ子の深さに応じて字下げした`<p>`に、各ページの始まりを`#page-{ページ番号}`のアンカーにした単独で閲覧できるHTMLを`{事件番号}_{year}_{month}_{day}_{裁判の種類}.html`として出力する
+//!   - `akn`：主文より前を`<header>`に、主文を`<decision>`に、事実及び理由を`<motivation>`に、別紙を`<attachments>`にしたAkoma Ntosoの`<judgment>`文書を`{事件番号}_{year}_{month}_{day}_{裁判の種類}.xml`として出力する。書き出す前に必要な要素や属性が揃っているかをスキーマの決まりと照合する
+//! - `--wrap`：テキストファイル（`--split-sections`などで分けたものを含む）の行を折り返す幅を指定する。半角文字を1、全角文字を2と数え、句読点や閉じ括弧は行頭に置かず、英数字の語は途中で分けない。`none`（既定）の場合は折り返さない
+//! - `--no-join`：OCRの結果の行を繋げずに元の改行を残す（PDFとの対応を取りたい場合に用いる）。`p2t`では元々行を繋げないため、ページ番号などを取り除いた後の行がそのまま出力される
+//! - `--keep-page-numbers`：`p2t`の際に`- 2 -`のようなページ番号の行を取り除かずに残す
+//! - `--keep-line-numbers`：`p2t`の際に行番号とみなして取り除いている数字で始まる行を残す
+//! - `--suppress-seals`：最後のページの`これは正本である。`から始まる認証文（続く日付・裁判所名・裁判所書記官名の行を含む）と、最初と最後のページの受付印・受領印や`複製禁止`などの透かしの文字の行を取り除く。設定ファイルの`seal_patterns`で取り除く行の正規表現を追加できる
+//! - `--filter-pattern`：取り除く行に一致する正規表現を指定する。裁判所ごとに異なるヘッダ・フッタなどを取り除くために用いる。複数回指定でき、設定ファイルの`filter_patterns`と合わせて`p2t`と`ocr`の両方に使われる
+//! - `--config`：設定ファイル（JSON）のpathを指定する
+//! - `--crop`：`ocr`の際にページの画像から切り取る範囲を、ImageMagickの`-crop`と同じ`{幅}x{高さ}+{左}+{上}`の形式で指定する。指定しない場合は設定ファイルの`crop`を、それも無い場合は`1000x1475+150+150`を使う
+//! - `--scale-crop`：`--crop`（設定ファイルの`crop`やプロファイルのものを含む）のピクセルの値を、pdfinfoで読んだページの大きさとA4のページの比で伸び縮みさせる。B5やリーガルサイズなどA4以外の大きさでスキャンされた文書も同じ範囲で切り取るために用いる。`--crop`の値は`80%x84%+12%+8%`のようにページの幅・高さに対する割合でも指定でき、割合の値は`--scale-crop`に関わらずページの大きさに合わせる
+//! - `--dpi`：`ocr`の際にpdftoppmでページを画像に変換する解像度を指定する。既定値は`150`で、`--crop`の範囲はこの解像度の画像のピクセルで指定する
+//! - `--gray`：`ocr`の際にpdftoppmでページをグレースケールの画像に変換する（`-gray`）。白黒の文書ではカラーの画像より一時フォルダの容量とOCRの時間を抑えられる
+//! - `--image-format`：`ocr`の際にtesseractに渡すページの画像の形式を指定する。`jpeg`（既定値）はpdftoppmが出力するJPEGを、`tiff-g4`はグレースケールで変換してから2値化し、Group4で圧縮したTIFFを使う。`tiff-g4`はtesseractが速く読め、一時フォルダの容量も小さいが、薄い文字や写真の多い文書では2値化で文字が欠けることがある
+//! - `--pipe-images`：`ocr`の際に、ページの画像を一時フォルダに書き出さず、pdftoppm・convert・tesseractの標準入出力で受け渡す（`tesseract stdin stdout`）。大量の事件を処理する際のファイルの作成と削除を減らすために用いる。画像やhOCRのファイルを使う`--drop-ruby`・`--detect-redactions`・`--split-layout`・`--reocr-confidence`・`--image-cache`と併用した場合は警告し、画像をファイルに書き出して処理する
+//! - `--in-memory`：`ocr`と`ensemble`の際に、`--pipe-images`に加えてページごとのテキストも一時フォルダに書き出さず、メモリの上で繋げる。一時フォルダにはキャッシュしたPDFファイルのみが残る。途中で止まった事件は最初のページからOCRし直す。併用できないオプションは`--pipe-images`と同じ
+//! - `--tmp-in-ram[=SIZE]`：ページの画像やOCRの結果のテキストなどの途中のファイルを、`/dev/shm`などのメモリ上のファイルシステム（tmpfs）に事件ごとのフォルダを作って置き、事件を処理し終えたら削除する。ネットワーク越しのファイルシステムを`--tmp`にした場合の書き込みと削除の遅さを避けるために用いる。`SIZE`（`512M`・`2G`など、既定値は`1G`）は処理中の事件の途中のファイルの合計の見積もりの上限で、超える事件は`--tmp`で処理する。キャッシュしたPDFファイルは`--tmp`に置いたままにする。tmpfsが見つからない場合は警告し、`--tmp`を使う
+//! - `--deskew`：`ocr`の際に、切り取ったページの画像の傾きを直してからOCRを行う。傾けてスキャンされた文書の行の取りこぼしを減らすために用いる
+//! - `--ocr-lang`：`ocr`の際にtesseractの`-l`に渡す言語を指定する。既定値は`jpn`
+//!
+//! ## 設定ファイル
+//!
+//! `--config`で指定するJSONファイルには次の項目を書くことができます。書かれていない項目は既定値が使われます。
+//!
+//! ```json
+//! {
+//!   "filter_patterns": ["^\\s*正本である", "^\\s*東京地方裁判所民事第\\d+部$"]
+//! }
+//! ```
+//!
+//! - `filter_patterns`：取り除く行に一致する正規表現の一覧
+//! - `seal_patterns`：`--suppress-seals`の際に最初と最後のページから取り除く行に一致する正規表現の一覧
+//! - `credentials`：ホスト名（`host`か`host:port`）ごとの、PDFファイルをダウンロードする際の認証情報。`{"mirror.example.local": {"token": "..."}}`や`{"mirror.example.local:8080": {"basic": "user:pass"}}`のように書く。コマンドラインに認証情報を残したくない場合に用いる
+//! - `crop`：`--crop`を指定しない場合に、ページの画像から切り取る範囲。`calibrate`で書き出せる
+//! - `profiles`：裁判所や判決の年ごとに`crop`・`dpi`・`deskew`・`language`（tesseractの言語）を置き換えるプロファイルの一覧。`[{"name": "old-tokyo", "court": ["東京地裁"], "to_year": 1988, "crop": "1000x1400+150+200", "dpi": 200, "deskew": true}]`のように書き、`court`（`--court`と同じく表記かコード）と`from_year`・`to_year`（判決の年の範囲、両端を含む）が一覧のJSONファイルの事件に当てはまる最初のプロファイルの項目で、コマンドラインの設定を事件ごとに置き換える。条件に使う裁判所名や判決日が分からない事件には当てはまらない。`preview`もプロファイルを使う
+//!
+//! ## キャッシュの整理
+//!
+//! ```sh
+//! pdf2txt_precedent cache verify --tmp "tmp"
+//! pdf2txt_precedent cache gc --tmp "tmp" --output "."
+//! ```
+//!
+//! - `cache verify`：ダウンロード時に`tmp/manifest.json`へ記録したハッシュ値とキャッシュしたPDFファイルを照合し、壊れているもの・先頭に`%PDF-`のヘッダが無いもの（保存されたエラーページなど）と実体の無い記録を取り除く
+//! - `cache gc`：生成済みテキストファイルが存在する事件について、tmpフォルダの中間ファイルを削除する。`--mode both`の出力は`p2t/`・`ocr/`の両方に揃っていれば生成済みとする。`--image-cache`で残した画像のうち、キャッシュしたPDFファイルの一覧に無いPDFファイルのものも削除する
+//!
+//! `tmp/manifest.json`には、PDFファイルごとにハッシュ値と大きさに加えて、ダウンロードした際のURL（`source`）・ステータスコード（`status`）・`content_length`・`content_type`・`etag`・`last_modified`・ダウンロードした日時（`fetched_at`、UTC）が記録されます。いつ取得したか、その後に取得元のファイルが変わったかを調べる際に用います。
+//!
+//! ## 結果のアーカイブ
+//!
+//! ```sh
+//! pdf2txt_precedent export --archive "out.tar.gz" --tmp "tmp" --output "."
+//! ```
+//!
+//! で出力フォルダのテキストファイル・JSONファイル（gzip圧縮したものを含む）と`tmp/manifest.json`を一つのアーカイブファイルにまとめます。`tmp/manifest.json`は出力フォルダの`manifest.json`と区別するため、アーカイブ内でも`tmp/manifest.json`とします。`--mode both`で`p2t/`・`ocr/`に書き出した出力も、`p2t/`・`ocr/`のフォルダごと含めます。`.zip`と`.tar.gz`に対応しています。ファイルは名前順に並べられ、時刻や権限は固定されるため、同じ内容からは同じアーカイブファイルが生成されます。
+//!
+//! ## 出力の比較
+//!
+//! ```sh
+//! pdf2txt_precedent diff --old "output_old" --new "output_new"
+//! ```
+//!
+//! で、二つの出力フォルダのテキストファイル（gzip圧縮したものを含む）を比べ、新しいフォルダにのみある事件（`[Added]`）、以前のフォルダにのみある事件（`[Removed]`）、内容が変わった事件（`[Changed]`）と、事件ごとの変化した文字の割合を表示します。OCRの設定を変えた結果を評価する際に用います。
+//!
+//! ## 切り取る範囲の調整
+//!
+//! ```sh
+//! pdf2txt_precedent --input "list.json" --config "config.json" calibrate --samples 3
+//! ```
+//!
+//! で、一覧のJSONファイルからキーの順に等間隔で選んだ`--samples`件の事件の最初のページに、OCRの前に切り取る範囲を赤い枠で描いた画像を出力フォルダの`calibrate/{name}.jpg`に書き出し、新しい範囲の入力を待ちます。`{幅}x{高さ}+{左}+{上}`を入力すると描き直し、何も入力せずにEnterを押すとその範囲を`--config`の設定ファイルの`crop`に書き出して終わります（他の項目はそのまま残し、ファイルが無ければ作ります）。`q`で書き出さずに終わります。裁判所や年代によって余白の大きさが異なる場合に用います。
+//!
+//! ## 設定の確認
+//!
+//! ```sh
+//! pdf2txt_precedent --input "list.json" --mode ocr preview --case "2023_1_1_判決"
+//! ```
+//!
+//! で、一覧のJSONファイルの1つの事件の最初のページだけを、全体を処理する場合と同じ切り取り・黒塗りの処理・OCRで処理し、加工した画像（`preview/{name}.jpg`、`--image-format tiff-g4`の場合は`preview/{name}.png`）・テキスト（`preview/{name}.txt`）と、それらを並べた`preview/{name}.html`を出力フォルダに書き出します。`--mode p2t`の場合はpdftotextで取り出したテキストを並べます。全ての事件を処理する前に、設定が意図したとおりに働くかを確かめる際に用います。`--input`などのオプションはサブコマンドの前に指定します。
+//!
+//! ## オフラインでのテスト
+//!
+//! `full_pdf_link`には`file://`で始まるローカルのファイルのURLも使えます。
+//!
+//! ```sh
+//! cargo test
+//! ```
+//!
+//! で、`tests/fixtures`にあるPDFファイルを`file://`のURLとテストの中で立ち上げるHTTPサーバから配信し、courts.go.jpに接続せずにダウンロードからテキストの出力までを確かめます。
+//!
+//! ## 回帰テスト
+//!
+//! ```sh
+//! pdf2txt_precedent --mode p2t regress --corpus "tests/fixtures"
+//! ```
+//!
+//! で、`--corpus`で指定したフォルダにある`{name}.pdf`をそれぞれ処理し、プレーンテキストの出力を同じフォルダの正解のテキストファイル`{name}.txt`と比べます。一致しないものがあれば行番号付きの差分を表示して失敗します。`--mode`などのオプションはサブコマンドの前に指定します。`--update`を付けると一致しない正解のテキストファイルを現在の出力で置き換えます。
 //!
 //! ---
 //! [MIT License](https://github.com/japanese-law-analysis/pdf2txt_precedent/blob/master/LICENSE)
 //! (c) 2023 Naoki Kaneko (a.k.a. "puripuri2100")
 //!
 
+mod akn;
+mod auth;
+mod breaker;
+mod cache;
+mod calibrate;
+mod case_list;
+mod classify;
+mod cloud_vision;
+mod config;
+mod confusion;
+mod consistency;
+mod court;
+mod crop;
+mod daemon;
+mod dataset;
+mod diff;
+mod document;
+mod ensemble;
+mod errors;
+mod export;
+mod extractor;
+mod fallback;
+mod fonts;
+mod hocr;
+mod html;
+mod images;
+mod interrupt;
+mod kyujitai;
+mod labels;
+mod law_id;
+mod layout;
+mod logging;
+mod markdown;
+mod messages;
+mod metadata;
+mod noise;
+#[cfg(feature = "notify")]
+mod notify;
+mod number;
+mod output;
+mod parties;
+mod pause;
+mod piped;
+mod precedent;
+mod preview;
+mod priority;
+mod profile;
+mod progress;
+mod provenance;
+mod quarantine;
+mod raster;
+mod redaction;
+mod regress;
+mod report;
+mod resume;
+mod rewrite;
+mod robots;
+mod ruby;
+mod seal;
+mod sections;
+mod segment;
+mod sidecar;
+mod similarity;
+mod statute;
+mod suspicious;
+mod template;
+mod tesseract;
+mod throttle;
+mod tmpram;
+mod wrap;
+
 use anyhow::{anyhow, Result};
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
+use messages::Message;
 use regex::Regex;
+use serde::Serialize;
 use serde_json::{Map, Value};
-use std::path::Path;
+use std::{
+  borrow::Cow,
+  collections::HashMap,
+  sync::Arc,
+  time::{Duration, Instant},
+};
 use tokio::{
   self,
   fs::{self, *},
   io::AsyncWriteExt,
   process::Command,
+  sync::{Mutex, Semaphore},
+  task::JoinSet,
 };
 use tokio_stream::StreamExt;
 
-async fn download_pdf(path: &str, url: &str) -> Result<()> {
-  let response = reqwest::get(url).await?;
-  let bytes = response.bytes().await?;
-  let mut f = File::create(path).await?;
-  f.write_all(&bytes).await?;
+/// ダウンロードの進み具合を表示する間隔（全体の大きさが分からない場合のバイト数）
+const DOWNLOAD_PROGRESS_BYTES: u64 = 10 * 1024 * 1024;
+
+/// `--html-report`に表示するテキストの冒頭の文字数
+const SNIPPET_CHARS: usize = 300;
+
+/// `file://`で始まるURLの場合はローカルのファイルを複製する
+///
+/// 大きなPDFファイルをメモリに載せないように、受け取った分から`{path}.part`に書き込み、終わってから`path`に移す。
+/// `max_size`を超えた場合は途中で止め、書きかけのファイルを削除してエラーを返す
+///
+/// 応答のヘッダなどを返す。ファイルが見つからない（404）場合は`None`を返す
+async fn download_pdf(
+  context: &Context,
+  path: &str,
+  url: &str,
+  max_size: Option<u64>,
+) -> Result<Option<cache::Fetch>> {
+  let fetched_at = Some(cache::now_rfc3339());
+  if let Some(local_path) = url.strip_prefix("file://") {
+    if !std::path::Path::new(local_path).exists() {
+      return Ok(None);
+    }
+    let size = fs::metadata(local_path).await?.len();
+    if let Some(max_size) = max_size.filter(|max_size| size > *max_size) {
+      return Err(anyhow!(
+        "{}",
+        Message::DownloadTooLarge(Some(size), max_size, url)
+      ));
+    }
+    fs::copy(local_path, path).await?;
+    return Ok(Some(cache::Fetch {
+      source: Some(url.to_string()),
+      content_length: Some(size),
+      fetched_at,
+      ..Default::default()
+    }));
+  }
+  let mut response = context
+    .credentials
+    .apply(url, context.client.get(url))
+    .send()
+    .await?;
+  if response.status() == reqwest::StatusCode::NOT_FOUND {
+    return Ok(None);
+  }
+  let header = |name: reqwest::header::HeaderName| {
+    response
+      .headers()
+      .get(name)
+      .and_then(|v| v.to_str().ok())
+      .map(|v| v.to_string())
+  };
+  let fetch = cache::Fetch {
+    source: Some(url.to_string()),
+    status: Some(response.status().as_u16()),
+    content_length: response.content_length(),
+    content_type: header(reqwest::header::CONTENT_TYPE),
+    etag: header(reqwest::header::ETAG),
+    last_modified: header(reqwest::header::LAST_MODIFIED),
+    fetched_at,
+  };
+  let total = response.content_length();
+  let part_path = format!("{path}.part");
+  let mut f = File::create(&part_path).await?;
+  let mut downloaded: u64 = 0;
+  // 次に進み具合を表示する割合（%）かバイト数
+  let mut next_progress = match total {
+    Some(total) if total > 0 => 10,
+    _ => DOWNLOAD_PROGRESS_BYTES,
+  };
+  while let Some(chunk) = response.chunk().await? {
+    downloaded += chunk.len() as u64;
+    if let Some(max_size) = max_size.filter(|max_size| downloaded > *max_size) {
+      drop(f);
+      fs::remove_file(&part_path).await?;
+      return Err(anyhow!(
+        "{}",
+        Message::DownloadTooLarge(None, max_size, url)
+      ));
+    }
+    f.write_all(&chunk).await?;
+    if let Some(throttle) = &context.throttle {
+      throttle.consume(chunk.len()).await;
+    }
+    match total {
+      // 全体の大きさが分かる場合は10%ごとに表示する
+      Some(total) if total > 0 => {
+        let percent = downloaded * 100 / total;
+        if percent >= next_progress {
+          logging::info(
+            "Download",
+            None,
+            format!("{url}: {downloaded}/{total} bytes ({percent}%)"),
+          );
+          next_progress = percent / 10 * 10 + 10;
+        }
+      }
+      _ => {
+        if downloaded >= next_progress {
+          logging::info("Download", None, format!("{url}: {downloaded} bytes"));
+          next_progress = downloaded / DOWNLOAD_PROGRESS_BYTES * DOWNLOAD_PROGRESS_BYTES
+            + DOWNLOAD_PROGRESS_BYTES;
+        }
+      }
+    }
+  }
   f.flush().await?;
-  Ok(())
+  drop(f);
+  fs::rename(&part_path, path).await?;
+  Ok(Some(fetch))
+}
+
+/// `--mirror-base`のサーバで元のURLと同じpathにあるPDFファイルのURL
+fn mirror_url(mirror_base: &str, url: &str) -> String {
+  let path = url
+    .split_once("://")
+    .map(|(_, rest)| rest.find('/').map(|i| &rest[i..]).unwrap_or(""))
+    .unwrap_or(url);
+  format!(
+    "{}/{}",
+    mirror_base.trim_end_matches('/'),
+    path.trim_start_matches('/')
+  )
+}
+
+/// 事件のPDFファイルを取得する候補のURL
+///
+/// `full_pdf_link`、各事件の`fallback_pdf_links`、`--mirror-base`のサーバの順に並べる
+fn pdf_urls(v: &Value, args: &Args) -> Result<Vec<String>> {
+  let url = v
+    .get("full_pdf_link")
+    .and_then(|v| v.as_str())
+    .ok_or_else(|| anyhow!("{}", Message::MissingField("full_pdf_link")))?;
+  let mut urls = vec![url.to_string()];
+  if let Some(fallbacks) = v.get("fallback_pdf_links").and_then(|v| v.as_array()) {
+    urls.extend(
+      fallbacks
+        .iter()
+        .filter_map(|v| v.as_str())
+        .map(|url| url.to_string()),
+    );
+  }
+  if let Some(mirror_base) = &args.mirror_base {
+    urls.push(mirror_url(mirror_base, url));
+  }
+  Ok(urls)
+}
+
+/// `is_downloads`の場合は`urls`を順に試してPDFファイルをダウンロードし、使ったURLや応答のヘッダを返す
+///
+/// そうでない場合はキャッシュしたPDFファイルを使えるようにする
+async fn fetch_pdf(
+  name: &str,
+  urls: &[String],
+  is_downloads: bool,
+  args: &Args,
+  context: &Context,
+) -> Result<Option<cache::Fetch>> {
+  let file_path_pdf = format!("{}/{name}.pdf", args.tmp);
+  if !is_downloads {
+    logging::info("Hit PDF Cache", Some(name), &file_path_pdf);
+    cache::decompress(&file_path_pdf).await?;
+    return Ok(None);
+  }
+  let mut stream = tokio_stream::iter(urls);
+  while let Some(url) = stream.next().await {
+    if let Some(robots) = &context.robots {
+      if !robots.check(&context.client, url).await? {
+        logging::info("Disallowed", Some(name), format!("downloads: {url}"));
+        continue;
+      }
+    }
+    logging::info("START", Some(name), format!("downloads: {url}"));
+    let started = Instant::now();
+    if let Some(fetch) = download_pdf(context, &file_path_pdf, url, args.max_download_size).await? {
+      logging::emit(
+        logging::Level::Info,
+        "END",
+        Some(name),
+        Some(started.elapsed()),
+        format!("downloads: {url}"),
+      );
+      return Ok(Some(fetch));
+    }
+    logging::info("Not Found", Some(name), format!("downloads: {url}"));
+  }
+  Err(anyhow!("{}", Message::PdfNotFound(name)))
+}
+
+/// ダウンロードする前に`Content-Length`からPDFファイルの大きさを調べる
+///
+/// ダウンロードと同じく`--download-jobs`の数までに制限し、robots.txtで許されないURLは飛ばして次のURLを調べる。
+/// `file://`で始まるURLの場合はローカルのファイルの大きさを返す。
+/// ファイルが無いURLや、失敗したか成功以外の状態を返したURLも飛ばし、ダウンロードされうる次のURLを調べる。分からない場合は`None`を返す
+async fn content_length(name: &str, urls: &[String], context: &Context) -> Result<Option<u64>> {
+  let _permit = context.download_permits.acquire().await?;
+  context.pause.wait_resumed().await;
+  let mut stream = tokio_stream::iter(urls);
+  while let Some(url) = stream.next().await {
+    // `Crawl-delay`の間隔も、ダウンロードと合わせて空ける
+    if let Some(robots) = &context.robots {
+      if !robots.check(&context.client, url).await? {
+        logging::info("Disallowed", Some(name), format!("head: {url}"));
+        continue;
+      }
+    }
+    if let Some(local_path) = url.strip_prefix("file://") {
+      match fs::metadata(local_path).await {
+        Ok(metadata) => return Ok(Some(metadata.len())),
+        Err(err) => {
+          logging::info("Not Found", Some(name), format!("head: {url}: {err}"));
+          continue;
+        }
+      }
+    }
+    // HEADが失敗してもダウンロードはできる場合があるため、事件の処理を失敗にはしない
+    let response = match context
+      .credentials
+      .apply(url, context.client.head(url))
+      .send()
+      .await
+    {
+      Ok(response) if response.status().is_success() => response,
+      Ok(response) => {
+        let status = response.status();
+        logging::info("Not Found", Some(name), format!("head: {url}: {status}"));
+        continue;
+      }
+      Err(err) => {
+        logging::info("Not Found", Some(name), format!("head: {url}: {err}"));
+        continue;
+      }
+    };
+    let length = response
+      .headers()
+      .get(reqwest::header::CONTENT_LENGTH)
+      .and_then(|v| v.to_str().ok())
+      .and_then(|v| v.parse::<u64>().ok());
+    return Ok(length);
+  }
+  Ok(None)
 }
 
 async fn get_pdf_page_size(path: &str) -> Result<usize> {
-  let output = Command::new("pdfinfo").arg(path).output().await?;
+  let output = Command::new("pdfinfo")
+    .kill_on_drop(true)
+    .arg(path)
+    .output()
+    .await?;
   let text = String::from_utf8_lossy(&output.stdout);
   let re = Regex::new(r"Pages:\s*(?P<page_size>[\d]+)")?;
   let str = re
@@ -85,53 +610,161 @@ async fn get_pdf_page_size(path: &str) -> Result<usize> {
   Ok(page_size)
 }
 
-async fn convert_pdf(name: &str) -> Option<String> {
-  let output = Command::new("pdftoppm")
-    .arg("-jpeg")
-    .arg(format!("{name}.pdf"))
-    .arg(name)
-    .output()
-    .await
-    .ok();
-  output.and_then(|output| {
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    if stderr.as_ref().is_empty() {
-      None
-    } else {
-      Some(stderr.to_string())
+/// `--max-pages`で制限した処理するページの数と、制限によって省略したページがある場合に出力の末尾に付ける注記
+///
+/// 注記は空行で本文と区切るため、どの`--mode`でもそのまま末尾に加える
+fn page_limit(pdf_size: usize, args: &Args) -> (usize, Option<String>) {
+  match args.max_pages {
+    Some(max_pages) if max_pages < pdf_size => (
+      max_pages,
+      Some(format!(
+        "\n\n[truncated: {max_pages} of {pdf_size} pages]\n"
+      )),
+    ),
+    _ => (pdf_size, None),
+  }
+}
+
+/// 使えるCPUの数 分からない場合は1とする
+fn cpu_count() -> usize {
+  std::thread::available_parallelism()
+    .map(|n| n.get())
+    .unwrap_or(1)
+}
+
+/// `--dpi`の解像度と`--image-format`・`--gray`に合わせた画像の形式を指定したpdftoppm
+///
+/// `--image-format jpeg`で`--gray`の場合はグレースケールにする。`tiff-g4`の場合はグレースケールのPGMに変換し、後で2値化する
+fn pdftoppm_command(args: &Args) -> Command {
+  let mut command = Command::new("pdftoppm");
+  command
+    .kill_on_drop(true)
+    .arg("-r")
+    .arg(args.dpi.to_string());
+  match args.image_format {
+    images::Format::Jpeg => {
+      command.arg("-jpeg");
+      if args.gray {
+        command.arg("-gray");
+      }
     }
-  })
+    images::Format::TiffG4 => {
+      command.arg("-gray");
+    }
+  }
+  command
 }
 
-/// エラーがあった場合はエラーを取得する
-async fn crop_img(file_path: &str) -> Option<String> {
-  let output = Command::new("convert")
-    .arg("-crop")
-    .arg("1000x1475+150+150")
-    .arg(file_path)
-    .arg(file_path)
+/// 1ページのみを`{name}-{page_num}.{拡張子}`に変換する
+async fn convert_pdf_page(name: &str, page_num: usize, args: &Args) -> Option<String> {
+  let output = pdftoppm_command(args)
+    .arg("-f")
+    .arg(page_num.to_string())
+    .arg("-l")
+    .arg(page_num.to_string())
+    .arg("-singlefile")
+    .arg(format!("{name}.pdf"))
+    .arg(format!("{name}-{page_num}"))
     .output()
     .await
     .ok();
-  output.and_then(|output| {
+  let err_msg = output.and_then(|output| {
     let stderr = String::from_utf8_lossy(&output.stderr);
     if stderr.as_ref().is_empty() {
       None
     } else {
       Some(stderr.to_string())
     }
-  })
+  });
+  if args.image_format == images::Format::TiffG4 {
+    if let Some(tiff_err_msg) = images::to_tiff_g4(&format!("{name}-{page_num}")).await {
+      return Some(err_msg.unwrap_or_default() + &tiff_err_msg);
+    }
+  }
+  err_msg
 }
 
-async fn pdf2txt_img(name: &str) -> Option<String> {
-  let output = Command::new("tesseract")
-    .arg(format!("{name}.jpg"))
-    .arg(name)
+/// `--html-report`の際に、最初のページの縮小画像を出力フォルダの`thumbnails/{name}.jpg`に書き出す
+///
+/// 書き出せなくても事件の処理は失敗にせず、エラーのログに記録する
+async fn write_thumbnail(name: &str, pdf_path: &str, output_name: &str, errors: &errors::ErrorLog) {
+  let output = Command::new("pdftoppm")
+    .kill_on_drop(true)
+    .arg("-jpeg")
+    .arg("-f")
+    .arg("1")
     .arg("-l")
-    .arg("jpn")
+    .arg("1")
+    .arg("-scale-to")
+    .arg("240")
+    .arg("-singlefile")
+    .arg(pdf_path)
+    .arg(format!("{output_name}/{}/{name}", report::THUMBNAIL_DIR))
     .output()
-    .await
-    .ok();
+    .await;
+  let err_msg = match output {
+    Ok(output) if output.status.success() => return,
+    Ok(output) => String::from_utf8_lossy(&output.stderr).to_string(),
+    Err(err) => err.to_string(),
+  };
+  logging::warn(
+    "Thumbnail",
+    Some(name),
+    format!("thumbnail err({name}): {err_msg}"),
+  );
+  errors.record(name, "thumbnail", None, &err_msg).await.ok();
+}
+
+/// `--image-cache`を指定しない場合は`None`を返し、指定した場合はPDFファイルのSHA-256・`--dpi`・画像の形式ごとのキャッシュを開く
+async fn open_image_cache(file_name: &str, args: &Args) -> Result<Option<images::ImageCache>> {
+  if !args.image_cache {
+    return Ok(None);
+  }
+  Ok(Some(
+    images::ImageCache::open(
+      &args.tmp,
+      file_name,
+      args.dpi,
+      args.image_variant(),
+      args.image_format,
+    )
+    .await?,
+  ))
+}
+
+/// ページを画像に変換する `--image-cache`の場合は以前に変換した画像があればそれを使う
+async fn rasterize_page(
+  name: &str,
+  page_num: usize,
+  args: &Args,
+  image_cache: Option<&images::ImageCache>,
+) -> Result<Option<String>> {
+  let image_path = format!("{name}-{page_num}.{}", args.image_format.extension());
+  let Some(image_cache) = image_cache else {
+    return Ok(convert_pdf_page(name, page_num, args).await);
+  };
+  if image_cache.restore(page_num, &image_path).await? {
+    return Ok(None);
+  }
+  let err_msg = convert_pdf_page(name, page_num, args).await;
+  image_cache.store(page_num, &image_path).await?;
+  Ok(err_msg)
+}
+
+/// `image_path`の画像をOCRして`{name}.txt`（`hocr`の場合は`{name}.hocr`も）に書き出す
+async fn pdf2txt_img(
+  image_path: &str,
+  name: &str,
+  lang: &str,
+  hocr: bool,
+  threads: Option<usize>,
+) -> Option<String> {
+  let mut command = tesseract::command(threads);
+  command.arg(image_path).arg(name).arg("-l").arg(lang);
+  if hocr {
+    command.arg("txt").arg("hocr");
+  }
+  let output = command.output().await.ok();
   output.and_then(|output| {
     let stderr = String::from_utf8_lossy(&output.stderr);
     if stderr.as_ref().is_empty() {
@@ -142,12 +775,28 @@ async fn pdf2txt_img(name: &str) -> Option<String> {
   })
 }
 
+/// hOCRファイルからルビの除去や黒塗りの目印の挿入を行ったテキストを作り、tesseractが出力したテキストファイルを置き換える
+async fn postprocess_hocr(name: &str, drop_ruby: bool, redactions: &[hocr::BBox]) -> Result<()> {
+  let hocr = fs::read_to_string(format!("{name}.hocr")).await?;
+  let mut page = hocr::parse(&hocr);
+  if drop_ruby {
+    let removed = ruby::remove_ruby(&mut page);
+    logging::info("Drop Ruby", None, format!("{name}: {removed} lines"));
+  }
+  redaction::mark(&mut page, redactions);
+  let mut f = File::create(format!("{name}.txt")).await?;
+  f.write_all(page.text().as_bytes()).await?;
+  f.flush().await?;
+  Ok(())
+}
+
 async fn join_pdf2txt_text(text: &str) -> String {
   let mut s = String::new();
   let mut line_stream = tokio_stream::iter(text.lines());
   let mut is_null_line = false;
   while let Some(line) = line_stream.next().await {
-    let text = line.trim();
+    // ページの区切りは残す
+    let text = line.trim_matches(|c: char| c.is_whitespace() && c != output::PAGE_BREAK);
     if text.is_empty() {
       is_null_line = true
     } else {
@@ -161,143 +810,1672 @@ async fn join_pdf2txt_text(text: &str) -> String {
   s
 }
 
-async fn join_pdf2txt_file(file_path_lst: &[String], output_path: &str) -> Result<()> {
-  let mut s = String::new();
+/// `filters`に一致する行を取り除いてから、ページごとのテキストファイルを繋げる
+///
+/// `join`が偽の場合は行を繋げず、各行の末尾の空白のみを取り除く
+async fn join_pdf2txt_file(
+  file_path_lst: &[String],
+  join: bool,
+  filters: &[Regex],
+) -> Result<String> {
+  let mut pages = Vec::with_capacity(file_path_lst.len());
   let mut stream = tokio_stream::iter(file_path_lst);
   while let Some(file_path) = stream.next().await {
-    let file_contents = fs::read_to_string(file_path).await?;
-    s.push_str(file_contents.trim());
+    pages.push(cache::read_to_string(file_path).await?);
   }
-  let s = join_pdf2txt_text(&s).await;
-  let mut output = File::create(output_path).await?;
-  output.write_all(s.as_bytes()).await?;
-  output.flush().await?;
-  Ok(())
+  Ok(join_pdf2txt_pages(&pages, join, filters).await)
 }
 
-async fn download_and_pdftotext(
-  name: &str,
-  url: &str,
-  tmp_name: &str,
-  output_name: &str,
-  is_downloads: bool,
-) -> Result<()> {
-  let file_name = format!("{tmp_name}/{name}");
-  let file_path_pdf = format!("{file_name}.pdf");
-  let file_path_generate_txt = format!("{file_name}.txt");
-  let file_path_txt = format!("{output_name}/{name}.txt");
-  let file_path_err = format!("{file_name}_err.txt");
-  let mut txt_output = File::create(file_path_txt).await?;
-  let mut err_txt = String::new();
-  if is_downloads {
-    println!("[START] downloads: {url}");
-    download_pdf(&file_path_pdf, url).await?;
-    println!("[END] downloads: {url}");
+/// `filters`に一致する行を取り除いてからページのテキストを繋げる
+async fn join_pdf2txt_pages(pages: &[String], join: bool, filters: &[Regex]) -> String {
+  let mut s = String::new();
+  let mut stream = tokio_stream::iter(pages);
+  while let Some(file_contents) = stream.next().await {
+    let file_contents = file_contents
+      .lines()
+      .filter(|line| !noise::is_noise(filters, line))
+      .collect::<Vec<_>>()
+      .join("\n");
+    if !s.is_empty() {
+      s.push(output::PAGE_BREAK);
+    }
+    if join {
+      s.push_str(file_contents.trim());
+    } else {
+      let mut line_stream = tokio_stream::iter(file_contents.trim().lines());
+      while let Some(line) = line_stream.next().await {
+        s.push_str(line.trim_end());
+        s.push('\n');
+      }
+    }
+  }
+  if join {
+    join_pdf2txt_text(&s).await
   } else {
-    println!("[Hit PDF Cache] {file_path_pdf}");
-  };
-  let output = Command::new("pdftotext")
-    .arg(file_path_pdf)
-    .arg("-raw")
-    .output()
-    .await
-    .ok();
-  if let Some(output) = output {
-    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-    if !stderr.is_empty() {
-      err_txt.push_str(&stderr);
-      err_txt.push('\n');
-    };
+    s
   }
-  if let Ok(generate_txt) = fs::read_to_string(&file_path_generate_txt).await {
-    let mut line_stream = tokio_stream::iter(generate_txt.lines());
-    while let Some(line) = line_stream.next().await {
-      let is_page_or_line_num_re = Regex::new(r"^(\s*-?\s*\d+\s*-?\s*)|(\s+)$").unwrap();
-      if !is_page_or_line_num_re.is_match(line) {
-        txt_output.write_all(line.as_bytes()).await?;
-        txt_output.write_all(b"\n").await?;
+}
+
+/// pdftotextが出力したテキストから`filters`に一致する行を取り除く
+async fn filter_pdftotext(generate_txt: &str, filters: &[Regex]) -> String {
+  let mut txt = String::new();
+  let mut line_stream = tokio_stream::iter(generate_txt.lines());
+  while let Some(line) = line_stream.next().await {
+    // pdftotextはページの先頭に改ページを出力するため、ページ番号の行を取り除く前に取り出しておく
+    let line = match line.strip_prefix(output::PAGE_BREAK) {
+      Some(line) => {
+        txt.push(output::PAGE_BREAK);
+        line
       }
+      None => line,
+    };
+    if !noise::is_noise(filters, line) {
+      txt.push_str(line);
+      txt.push('\n');
     }
-  } else {
-    err_txt.push_str(&format!(
-      "'{}': No such file or directory\n",
-      &file_path_generate_txt
-    ));
   }
-  txt_output.flush().await?;
-  if !err_txt.is_empty() {
-    let mut err_output = File::create(file_path_err).await?;
-    err_output.write_all(err_txt.as_bytes()).await?;
-    err_output.flush().await?;
+  txt
+}
+
+/// pdftotextが出力したテキストをページごとに分ける
+///
+/// pdftotextは最後のページの後にも改ページを出力するため、その後の部分はページに含めない
+fn pdftotext_pages(txt: &str) -> Vec<&str> {
+  let mut pages = txt.split(output::PAGE_BREAK).collect::<Vec<_>>();
+  if pages.len() > 1 {
+    pages.pop();
   }
+  pages
+}
+
+/// 1ページのみをpdftotextで取り出したテキスト
+async fn pdftotext_page(file_path_pdf: &str, page_num: usize) -> Result<String> {
+  let output = Command::new("pdftotext")
+    .kill_on_drop(true)
+    .arg("-f")
+    .arg(page_num.to_string())
+    .arg("-l")
+    .arg(page_num.to_string())
+    .arg("-raw")
+    .arg(file_path_pdf)
+    .arg("-")
+    .output()
+    .await?;
+  Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// `{file_name}-{page_num}.{拡張子}`を切り抜いてOCRを行い、`{file_name}-{page_num}.txt`を生成する
+///
+/// OCRが終わった画像は削除する
+async fn ocr_page(
+  file_name: &str,
+  page_num: usize,
+  args: &Args,
+  err_output: &mut errors::CaseErrors<'_>,
+  sidecar: &mut sidecar::Sidecar,
+  timings: &mut report::Timings,
+) -> Result<()> {
+  let started = Instant::now();
+  preprocess_img(file_name, page_num, args, err_output).await?;
+  timings.conversion += started.elapsed();
+  let started = Instant::now();
+  let redactions = erase_redactions(file_name, page_num, args, err_output).await?;
+  recognize_page(file_name, page_num, redactions, args, err_output, sidecar).await?;
+  timings.ocr += started.elapsed();
   Ok(())
 }
 
-async fn download_and_ocr(
-  name: &str,
-  url: &str,
-  tmp_name: &str,
-  output_name: &str,
-  is_downloads: bool,
+/// ページの画像を`--crop`の範囲に切り取り、`--deskew`の場合は傾きを直す
+async fn preprocess_img(
+  file_name: &str,
+  page_num: usize,
+  args: &Args,
+  err_output: &mut errors::CaseErrors<'_>,
 ) -> Result<()> {
-  let file_name = format!("{tmp_name}/{name}");
-  let file_path_pdf = format!("{file_name}.pdf");
-  let file_path_txt = format!("{output_name}/{name}.txt");
-  let file_path_err = format!("{file_name}_err.txt");
-  let mut err_output = File::create(file_path_err).await?;
-  if is_downloads {
-    println!("[START] downloads: {url}");
-    download_pdf(&file_path_pdf, url).await?;
-    println!("[END] downloads: {url}");
+  let file_path = format!("{file_name}-{page_num}.{}", args.image_format.extension());
+  let rect = args
+    .crop
+    .unwrap_or_default()
+    .resolve_page(
+      &format!("{file_name}.pdf"),
+      page_num,
+      args.dpi,
+      args.scale_crop,
+    )
+    .await;
+  if let Some(err_msg) = crop::apply(&file_path, &rect).await {
+    err_output
+      .write("convert", Some(page_num), &err_msg)
+      .await?;
+  }
+  if args.deskew {
+    if let Some(err_msg) = deskew_img(&file_path).await {
+      err_output.write("deskew", Some(page_num), &err_msg).await?;
+    }
+  }
+  Ok(())
+}
+
+/// 傾けてスキャンされたページの画像の傾きを直す
+async fn deskew_img(file_path: &str) -> Option<String> {
+  let output = Command::new("convert")
+    .kill_on_drop(true)
+    .arg(file_path)
+    .arg("-deskew")
+    .arg("40%")
+    .arg("+repage")
+    .arg(file_path)
+    .output()
+    .await
+    .ok();
+  output.and_then(|output| {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.as_ref().is_empty() {
+      None
+    } else {
+      Some(stderr.to_string())
+    }
+  })
+}
+
+/// `--detect-redactions`の際に、切り取ったページの画像の黒塗りの領域を見つけて塗りつぶし、その領域を返す
+async fn erase_redactions(
+  file_name: &str,
+  page_num: usize,
+  args: &Args,
+  err_output: &mut errors::CaseErrors<'_>,
+) -> Result<Vec<hocr::BBox>> {
+  let file_path = format!("{file_name}-{page_num}.{}", args.image_format.extension());
+  let redactions = if args.detect_redactions {
+    let redactions = redaction::detect(&file_path).await?;
+    if !redactions.is_empty() {
+      logging::info(
+        "Redacted",
+        None,
+        format!("{file_path}: {} regions", redactions.len()),
+      );
+      if let Some(err_msg) = redaction::erase(&file_path, &redactions).await {
+        err_output
+          .write("redaction", Some(page_num), &err_msg)
+          .await?;
+      }
+    }
+    redactions
   } else {
-    println!("[Hit PDF Cache] {file_path_pdf}");
+    Vec::new()
   };
-  let pdf_size = get_pdf_page_size(&file_path_pdf).await?;
-  let err_msg_opt = convert_pdf(&file_name).await;
+  Ok(redactions)
+}
+
+/// 加工したページの画像をOCRして`{file_name}-{page_num}.txt`に書き出し、画像を削除する
+async fn recognize_page(
+  file_name: &str,
+  page_num: usize,
+  redactions: Vec<hocr::BBox>,
+  args: &Args,
+  err_output: &mut errors::CaseErrors<'_>,
+  sidecar: &mut sidecar::Sidecar,
+) -> Result<()> {
+  let page_name = format!("{file_name}-{page_num}");
+  let use_hocr = args.drop_ruby || args.detect_redactions || args.split_layout;
+  let write_hocr = use_hocr || args.reocr_confidence.is_some();
+  let image_path = format!("{page_name}.{}", args.image_format.extension());
+  let err_msg_opt = pdf2txt_img(
+    &image_path,
+    &page_name,
+    &args.ocr_lang,
+    write_hocr,
+    args.tesseract_thread_limit(),
+  )
+  .await;
   if let Some(err_msg) = err_msg_opt {
-    println!("convert err({name}): {err_msg}");
+    err_output
+      .write("tesseract", Some(page_num), &err_msg)
+      .await?;
   }
-  let mut stream = tokio_stream::iter(1..=pdf_size);
-  while let Some(page_num) = stream.next().await {
-    let file_path = format!("{file_name}-{page_num}.jpg");
-    let err_msg_opt = crop_img(&file_path).await;
-    if let Some(err_msg) = err_msg_opt {
-      err_output.write_all(err_msg.as_bytes()).await?;
-    }
-    let err_msg_opt = pdf2txt_img(&format!("{file_name}-{page_num}")).await;
-    if let Some(err_msg) = err_msg_opt {
-      err_output.write_all(err_msg.as_bytes()).await?;
+  if let Some(threshold) = args.reocr_confidence {
+    reocr_low_confidence(&page_name, page_num, threshold, args, err_output, sidecar).await?;
+  }
+  if use_hocr {
+    postprocess_hocr(&page_name, args.drop_ruby, &redactions).await?;
+  }
+  if args.split_layout {
+    let hocr = fs::read_to_string(format!("{page_name}.hocr")).await?;
+    let (text, err_msg) = layout::recognize(
+      &page_name,
+      &image_path,
+      &hocr,
+      args.tesseract_thread_limit(),
+    )
+    .await?;
+    err_output.write("layout", Some(page_num), &err_msg).await?;
+    if let Some(text) = text {
+      logging::info("Layout", None, format!("{page_name}: vertical regions"));
+      let mut f = File::create(format!("{page_name}.txt")).await?;
+      f.write_all(text.as_bytes()).await?;
+      f.flush().await?;
     }
   }
-  let txt_path_lst = (1..=pdf_size)
-    .map(|i| format!("{file_name}-{i}.txt"))
-    .collect::<Vec<_>>();
-  join_pdf2txt_file(&txt_path_lst, &file_path_txt).await?;
-  err_output.flush().await?;
+  sidecar
+    .redactions
+    .extend(redactions.into_iter().map(|bbox| sidecar::Redaction {
+      page: page_num,
+      bbox,
+    }));
+  // 変換に失敗して画像が無い場合もある
+  fs::remove_file(&image_path).await.ok();
   Ok(())
 }
 
-#[derive(Clone, Debug, Parser)]
-#[command(author, version, about, long_about = None)]
-struct Args {
-  /// 判例のリストがあるJSONファイルへのpath
-  #[arg(short, long)]
-  input: String,
-  /// 一時フォルダのpath
-  #[arg(short, long, default_value_t=String::from("tmp"))]
-  tmp: String,
+/// OCRし直す前に、画像をグレースケールにしてコントラストを整え、輪郭を強める
+async fn enhance_img(file_path: &str, output_path: &str) -> Option<String> {
+  let output = Command::new("convert")
+    .kill_on_drop(true)
+    .arg(file_path)
+    .arg("-colorspace")
+    .arg("Gray")
+    .arg("-normalize")
+    .arg("-sharpen")
+    .arg("0x1")
+    .arg(output_path)
+    .output()
+    .await
+    .ok();
+  output.and_then(|output| {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.as_ref().is_empty() {
+      None
+    } else {
+      Some(stderr.to_string())
+    }
+  })
+}
+
+async fn hocr_confidence(page_name: &str) -> Option<f64> {
+  let hocr = fs::read_to_string(format!("{page_name}.hocr")).await.ok()?;
+  hocr::parse(&hocr).mean_confidence()
+}
+
+/// 単語の信頼度の平均が`threshold`より低いページを、画像を加工してOCRし直し、信頼度が高い方の結果を使う
+///
+/// 加工しても位置は変わらないため、黒塗りの領域などはそのまま使える
+async fn reocr_low_confidence(
+  page_name: &str,
+  page_num: usize,
+  threshold: u32,
+  args: &Args,
+  err_output: &mut errors::CaseErrors<'_>,
+  sidecar: &mut sidecar::Sidecar,
+) -> Result<()> {
+  let Some(confidence) = hocr_confidence(page_name).await else {
+    return Ok(());
+  };
+  if confidence >= f64::from(threshold) {
+    return Ok(());
+  }
+  let retry_name = format!("{page_name}-reocr");
+  if let Some(err_msg) = enhance_img(
+    &format!("{page_name}.{}", args.image_format.extension()),
+    &format!("{retry_name}.jpg"),
+  )
+  .await
+  {
+    err_output.write("reocr", Some(page_num), &err_msg).await?;
+  }
+  if let Some(err_msg) = pdf2txt_img(
+    &format!("{retry_name}.jpg"),
+    &retry_name,
+    &args.ocr_lang,
+    true,
+    args.tesseract_thread_limit(),
+  )
+  .await
+  {
+    err_output.write("reocr", Some(page_num), &err_msg).await?;
+  }
+  let retry_confidence = hocr_confidence(&retry_name).await;
+  let adopted = retry_confidence.is_some_and(|retry| retry > confidence);
+  if adopted {
+    fs::rename(format!("{retry_name}.txt"), format!("{page_name}.txt")).await?;
+    fs::rename(format!("{retry_name}.hocr"), format!("{page_name}.hocr")).await?;
+  }
+  for extension in ["jpg", "txt", "hocr"] {
+    fs::remove_file(format!("{retry_name}.{extension}"))
+      .await
+      .ok();
+  }
+  logging::info(
+    "Re-OCR",
+    None,
+    format!(
+      "{page_name}: confidence {confidence:.1} -> {}{}",
+      retry_confidence
+        .map(|retry| format!("{retry:.1}"))
+        .unwrap_or_else(|| "-".to_string()),
+      if adopted { " (adopted)" } else { "" },
+    ),
+  );
+  sidecar.ocr_escalations.push(sidecar::OcrEscalation {
+    page: page_num,
+    confidence,
+    retry_confidence,
+    adopted,
+  });
+  Ok(())
+}
+
+/// ほとんど文字を取り出せなかったページのみOCRを行い、`txt`のそのページを置き換える
+async fn ocr_blank_pages(
+  name: &str,
+  txt: &str,
+  args: &Args,
+  filters: &noise::Filters,
+  err_output: &mut errors::CaseErrors<'_>,
+  sidecar: &mut sidecar::Sidecar,
+  timings: &mut report::Timings,
+) -> Result<String> {
+  let file_name = args.work_name(name);
+  let pdf_size = get_pdf_page_size(&format!("{file_name}.pdf")).await?;
+  let mut pages = txt
+    .split(output::PAGE_BREAK)
+    .map(|page| page.to_string())
+    .collect::<Vec<_>>();
+  let page_size = pdf_size.min(pdftotext_pages(txt).len());
+  let mut resumed = resume::Pages::load(&file_name).await?;
+  let image_cache = open_image_cache(&file_name, args).await?;
+  let mut stream = tokio_stream::iter(1..=page_size);
+  while let Some(page_num) = stream.next().await {
+    if !ensemble::is_blank_page(&pages[page_num - 1]) {
+      continue;
+    }
+    logging::info(
+      "Fallback",
+      Some(name),
+      format!("{name}: page {page_num} ocr"),
+    );
+    if resumed.resume(page_num, sidecar) {
+      logging::info("Resume", Some(name), format!("{name}: page {page_num}"));
+    } else if args.pipes_images() {
+      piped::ocr_page(&file_name, page_num, args, err_output, timings).await?;
+      resumed.done(page_num, sidecar).await?;
+    } else {
+      let started = Instant::now();
+      let err_msg_opt = rasterize_page(&file_name, page_num, args, image_cache.as_ref()).await?;
+      timings.conversion += started.elapsed();
+      if let Some(err_msg) = err_msg_opt {
+        err_output
+          .write("pdftoppm", Some(page_num), &err_msg)
+          .await?;
+      }
+      ocr_page(&file_name, page_num, args, err_output, sidecar, timings).await?;
+      resumed.done(page_num, sidecar).await?;
+    }
+    let txt_path = format!("{file_name}-{page_num}.txt");
+    match join_pdf2txt_file(std::slice::from_ref(&txt_path), !args.no_join, &filters.ocr).await {
+      Ok(page_txt) => {
+        pages[page_num - 1] = format!("{}\n", page_txt.trim_end());
+        if args.compress_cache {
+          cache::compress(&txt_path).await?;
+        }
+      }
+      // OCRに失敗した場合はpdftotextの結果をそのまま使う
+      Err(err) => {
+        err_output
+          .write("ocr", Some(page_num), &format!("{err}\n"))
+          .await?
+      }
+    }
+  }
+  resumed.finish().await?;
+  Ok(pages.join(&output::PAGE_BREAK.to_string()))
+}
+
+/// テキストをほとんど取り出せなかったページはOCRで補い、全体が文字化けしている場合はOCRで処理し直す
+///
+/// 生成したテキストを返す
+async fn pdftotext_pdf(
+  name: &str,
+  args: &Args,
+  filters: &noise::Filters,
+  errors: &errors::ErrorLog,
+  raster_permits: &Arc<Semaphore>,
+  sidecar: &mut sidecar::Sidecar,
+  timings: &mut report::Timings,
+) -> Result<String> {
+  let tmp_name = &args.tmp;
+  let file_name = format!("{tmp_name}/{name}");
+  let file_path_pdf = format!("{file_name}.pdf");
+  let file_path_generate_txt = format!("{file_name}.txt");
+  let file_path_err = format!("{}/{name}_err.txt", args.err_dir());
+  let mut txt = String::new();
+  let mut err_txt = String::new();
+  let mut command = Command::new("pdftotext");
+  command.kill_on_drop(true);
+  command.arg(&file_path_pdf).arg("-raw");
+  let mut truncation_note = None;
+  if args.max_pages.is_some() {
+    let (page_size, note) = page_limit(get_pdf_page_size(&file_path_pdf).await?, args);
+    command.arg("-l").arg(page_size.to_string());
+    truncation_note = note;
+  }
+  let started = Instant::now();
+  let output = command.output().await.ok();
+  timings.conversion += started.elapsed();
+  if let Some(output) = output {
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    if !stderr.is_empty() {
+      err_txt.push_str(&stderr);
+      err_txt.push('\n');
+    };
+  }
+  let mut has_blank_pages = false;
+  if let Ok(generate_txt) = fs::read_to_string(&file_path_generate_txt).await {
+    txt = filter_pdftotext(&generate_txt, &filters.pdftotext).await;
+    // `--mode both`ではpdftotextの結果を比べるため、文字化けしていてもそのまま書き出す
+    // `--fallback-chain`の場合は、次の方法に進むかを質の検査で決める
+    if args.mode_dir.is_none() && args.fallback_chain.is_empty() && ensemble::is_garbled(&txt) {
+      logging::warn(
+        "Text Layer Unusable",
+        Some(name),
+        Message::TextLayerUnusable(name),
+      );
+      sidecar.text_layer_unusable = true;
+      if !err_txt.is_empty() {
+        errors.record(name, "pdftotext", None, &err_txt).await?;
+      }
+      fs::remove_file(&file_path_generate_txt).await.ok();
+      return ocr_pdf(
+        name,
+        args,
+        filters,
+        errors,
+        raster_permits,
+        sidecar,
+        timings,
+      )
+      .await;
+    }
+    has_blank_pages = pdftotext_pages(&txt)
+      .into_iter()
+      .any(ensemble::is_blank_page);
+  } else {
+    err_txt.push_str(&format!(
+      "'{}': No such file or directory\n",
+      &file_path_generate_txt
+    ));
+  }
+  if !err_txt.is_empty() || has_blank_pages {
+    let mut err_output = errors::CaseErrors::create(&file_path_err, errors, name).await?;
+    err_output.write("pdftotext", None, &err_txt).await?;
+    if has_blank_pages {
+      txt = ocr_blank_pages(name, &txt, args, filters, &mut err_output, sidecar, timings).await?;
+    }
+    err_output.flush().await?;
+  }
+  if args.compress_cache {
+    cache::compress(&file_path_pdf).await?;
+    cache::compress(&file_path_generate_txt).await?;
+  }
+  if let Some(note) = truncation_note {
+    txt.push_str(&note);
+  }
+  Ok(txt)
+}
+
+/// 生成したテキストを返す
+async fn ocr_pdf(
+  name: &str,
+  args: &Args,
+  filters: &noise::Filters,
+  errors: &errors::ErrorLog,
+  raster_permits: &Arc<Semaphore>,
+  sidecar: &mut sidecar::Sidecar,
+  timings: &mut report::Timings,
+) -> Result<String> {
+  let file_name = args.work_name(name);
+  // `--compress-cache`で圧縮するのは`--tmp`のPDFファイル
+  let file_path_pdf = format!("{}/{name}.pdf", args.tmp);
+  let file_path_err = format!("{}/{name}_err.txt", args.err_dir());
+  let mut err_output = errors::CaseErrors::create(&file_path_err, errors, name).await?;
+  let page_count = get_pdf_page_size(&file_path_pdf).await?;
+  let (page_size, truncation_note) = page_limit(page_count, args);
+  let mut txt = if args.keeps_in_memory() {
+    // ページのテキストも一時フォルダに書き出さないため、途中で止まった場合は最初のページからやり直す
+    let mut pages = Vec::with_capacity(page_size);
+    let mut stream = tokio_stream::iter(1..=page_size);
+    while let Some(page_num) = stream.next().await {
+      pages.push(piped::recognize(&file_name, page_num, args, &mut err_output, timings).await?);
+    }
+    join_pdf2txt_pages(&pages, !args.no_join, &filters.ocr).await
+  } else {
+    let mut resumed = resume::Pages::load(&file_name).await?;
+    let image_cache = open_image_cache(&file_name, args).await?;
+    // 全てのページを先に画像にすると大きなPDFファイルでは一時フォルダの容量が足りなくなるため、1ページずつ変換する
+    // `--raster-jobs`の場合も、一度に変換するのは区間をその数だけ並べたページまでとする
+    let windowed = args.raster_jobs > 1
+      && image_cache.is_none()
+      && !args.pipes_images()
+      && page_size > raster::WINDOW_PAGES;
+    let mut rasterized_until = 0;
+    let mut stream = tokio_stream::iter(1..=page_size);
+    while let Some(page_num) = stream.next().await {
+      if resumed.resume(page_num, sidecar) {
+        logging::info("Resume", Some(name), format!("{name}: page {page_num}"));
+        continue;
+      }
+      if args.pipes_images() {
+        piped::ocr_page(&file_name, page_num, args, &mut err_output, timings).await?;
+        resumed.done(page_num, sidecar).await?;
+        continue;
+      }
+      let started = Instant::now();
+      let err_msgs = if !windowed {
+        rasterize_page(&file_name, page_num, args, image_cache.as_ref())
+          .await?
+          .map(|err_msg| vec![(page_num, err_msg)])
+          .unwrap_or_default()
+      } else if page_num > rasterized_until {
+        rasterized_until = (page_num + args.raster_jobs * raster::WINDOW_PAGES - 1).min(page_size);
+        let pages = (page_num..=rasterized_until)
+          .filter(|page_num| !resumed.is_done(*page_num))
+          .collect::<Vec<_>>();
+        raster::rasterize(&file_name, &pages, page_count, args, raster_permits).await?
+      } else {
+        Vec::new()
+      };
+      timings.conversion += started.elapsed();
+      for (page_num, err_msg) in err_msgs {
+        logging::warn(
+          "Convert",
+          Some(name),
+          format!("convert err({name}): {err_msg}"),
+        );
+        errors
+          .record(name, "pdftoppm", Some(page_num), &err_msg)
+          .await?;
+      }
+      ocr_page(
+        &file_name,
+        page_num,
+        args,
+        &mut err_output,
+        sidecar,
+        timings,
+      )
+      .await?;
+      resumed.done(page_num, sidecar).await?;
+    }
+    let txt_path_lst = (1..=page_size)
+      .map(|i| format!("{file_name}-{i}.txt"))
+      .collect::<Vec<_>>();
+    let txt = join_pdf2txt_file(&txt_path_lst, !args.no_join, &filters.ocr).await?;
+    resumed.finish().await?;
+    if args.compress_cache {
+      let mut stream = tokio_stream::iter(&txt_path_lst);
+      while let Some(txt_path) = stream.next().await {
+        cache::compress(txt_path).await?;
+      }
+    }
+    txt
+  };
+  err_output.flush().await?;
+  if args.compress_cache {
+    cache::compress(&file_path_pdf).await?;
+  }
+  if let Some(note) = truncation_note {
+    txt.push_str(&note);
+  }
+  Ok(txt)
+}
+
+/// ページごとにGoogle Cloud VisionでOCRを行う
+///
+/// 生成したテキストを返す
+async fn cloud_vision_pdf(
+  name: &str,
+  args: &Args,
+  context: &Context,
+  timings: &mut report::Timings,
+) -> Result<String> {
+  let api_key = cloud_vision::api_key()?;
+  let file_path_pdf = format!("{}/{name}.pdf", args.tmp);
+  let page_count = get_pdf_page_size(&file_path_pdf).await?;
+  let (page_size, truncation_note) = page_limit(page_count, args);
+  let mut pages = Vec::with_capacity(page_size);
+  let mut stream = tokio_stream::iter(1..=page_size);
+  while let Some(page_num) = stream.next().await {
+    let started = Instant::now();
+    let client = &context.client;
+    pages
+      .push(cloud_vision::recognize(client, &api_key, &file_path_pdf, page_num, args.dpi).await?);
+    timings.ocr += started.elapsed();
+  }
+  let mut txt = join_pdf2txt_pages(&pages, !args.no_join, &context.filters.ocr).await;
+  if args.compress_cache {
+    cache::compress(&file_path_pdf).await?;
+  }
+  if let Some(note) = truncation_note {
+    txt.push_str(&note);
+  }
+  Ok(txt)
+}
+
+/// ページごとに、テキストレイヤーが使えればpdftotextの結果を、使えなければOCRの結果を使う
+///
+/// 生成したテキストを返す
+async fn ensemble_pdf(
+  name: &str,
+  args: &Args,
+  filters: &noise::Filters,
+  errors: &errors::ErrorLog,
+  sidecar: &mut sidecar::Sidecar,
+  timings: &mut report::Timings,
+) -> Result<String> {
+  let file_name = args.work_name(name);
+  // `--compress-cache`で圧縮するのは`--tmp`のPDFファイル
+  let file_path_pdf = format!("{}/{name}.pdf", args.tmp);
+  let file_path_err = format!("{}/{name}_err.txt", args.err_dir());
+  let mut err_output = errors::CaseErrors::create(&file_path_err, errors, name).await?;
+  let (page_size, truncation_note) = page_limit(get_pdf_page_size(&file_path_pdf).await?, args);
+  let mut pages = Vec::new();
+  let mut ocr_txt_path_lst = Vec::new();
+  let mut resumed = resume::Pages::load(&file_name).await?;
+  let image_cache = open_image_cache(&file_name, args).await?;
+  let mut stream = tokio_stream::iter(1..=page_size);
+  while let Some(page_num) = stream.next().await {
+    let started = Instant::now();
+    let page_txt = pdftotext_page(&file_path_pdf, page_num).await?;
+    timings.conversion += started.elapsed();
+    if ensemble::is_usable_text_layer(&page_txt) {
+      let page_txt = filter_pdftotext(&page_txt, &filters.pdftotext).await;
+      pages.push(output::remove_page_breaks(&page_txt));
+      continue;
+    }
+    logging::info(
+      "Ensemble",
+      Some(name),
+      format!("{name}: page {page_num} ocr"),
+    );
+    if args.keeps_in_memory() {
+      let page_txt = piped::recognize(&file_name, page_num, args, &mut err_output, timings).await?;
+      pages.push(join_pdf2txt_pages(&[page_txt], !args.no_join, &filters.ocr).await);
+      continue;
+    }
+    if resumed.resume(page_num, sidecar) {
+      logging::info("Resume", Some(name), format!("{name}: page {page_num}"));
+    } else if args.pipes_images() {
+      piped::ocr_page(&file_name, page_num, args, &mut err_output, timings).await?;
+      resumed.done(page_num, sidecar).await?;
+    } else {
+      let started = Instant::now();
+      let err_msg_opt = rasterize_page(&file_name, page_num, args, image_cache.as_ref()).await?;
+      timings.conversion += started.elapsed();
+      if let Some(err_msg) = err_msg_opt {
+        err_output
+          .write("pdftoppm", Some(page_num), &err_msg)
+          .await?;
+      }
+      ocr_page(
+        &file_name,
+        page_num,
+        args,
+        &mut err_output,
+        sidecar,
+        timings,
+      )
+      .await?;
+      resumed.done(page_num, sidecar).await?;
+    }
+    let txt_path = format!("{file_name}-{page_num}.txt");
+    let page_txt =
+      join_pdf2txt_file(std::slice::from_ref(&txt_path), !args.no_join, &filters.ocr).await?;
+    pages.push(page_txt);
+    ocr_txt_path_lst.push(txt_path);
+  }
+  resumed.finish().await?;
+  err_output.flush().await?;
+  if args.compress_cache {
+    cache::compress(&file_path_pdf).await?;
+    let mut stream = tokio_stream::iter(&ocr_txt_path_lst);
+    while let Some(txt_path) = stream.next().await {
+      cache::compress(txt_path).await?;
+    }
+  }
+  let mut txt = pages.join(&output::PAGE_BREAK.to_string());
+  if let Some(note) = truncation_note {
+    txt.push_str(&note);
+  }
+  Ok(txt)
+}
+
+/// PEM形式のファイルに含まれる全ての証明書を読み込む
+async fn load_certificates(path: &str) -> Result<Vec<reqwest::Certificate>> {
+  const END: &str = "-----END CERTIFICATE-----";
+  let pem = fs::read_to_string(path).await?;
+  let mut certs = Vec::new();
+  let mut rest = pem.as_str();
+  while let Some(start) = rest.find("-----BEGIN CERTIFICATE-----") {
+    let end = rest[start..]
+      .find(END)
+      .ok_or_else(|| anyhow!("証明書の終わりが無い: {path}"))?
+      + start
+      + END.len();
+    certs.push(reqwest::Certificate::from_pem(
+      &rest.as_bytes()[start..end],
+    )?);
+    rest = &rest[end..];
+  }
+  if certs.is_empty() {
+    return Err(anyhow!("証明書が含まれていない: {path}"));
+  }
+  Ok(certs)
+}
+
+/// 設定ファイルと引数から作る、事件ごとの処理で共通して使うもの
+struct Context {
+  filters: noise::Filters,
+  seal_patterns: seal::Patterns,
+  profiles: Vec<profile::Profile>,
+  /// `--crop`か、指定しない場合は設定ファイルの`crop`
+  crop: Option<crop::Crop>,
+  /// `--rewrite-rules`の置き換え
+  rewrite_rules: rewrite::Rules,
+  /// `--header-template`・`--footer-template`
+  templates: template::Templates,
+  /// `--extract-statutes`で取り出した法令名の法令ID
+  law_ids: law_id::LawIds,
+  client: reqwest::Client,
+  /// ダウンロードを同時に行う数を制限する
+  download_permits: Semaphore,
+  /// テキストの抽出を同時に行う数を制限する
+  ocr_permits: Semaphore,
+  /// ダウンロードを始めてから抽出を終えるまでの事件の数を制限する
+  pending_permits: Semaphore,
+  /// `--raster-jobs`で区間に分けたページの画像への変換を同時に行う数を、全ての事件を合わせて制限する
+  raster_permits: Arc<Semaphore>,
+  /// ダウンロードの速さの制限
+  throttle: Option<throttle::Throttle>,
+  /// `--respect-robots`の場合のrobots.txtの規則
+  robots: Option<robots::Robots>,
+  /// ホストごとの認証情報
+  credentials: auth::Credentials,
+  /// `status.json`に書き出す進み具合
+  progress: progress::Progress,
+  /// Ctrl+C・SIGTERMによる中断
+  interrupt: Arc<interrupt::Interrupt>,
+  /// SIGUSR1・SIGUSR2による一時停止
+  pause: Arc<pause::Pause>,
+  /// 全ての事件のエラーを追記する`errors.jsonl`
+  errors: errors::ErrorLog,
+  /// `--abort-on-failure-rate`の場合に、失敗が多すぎるときに処理を止める
+  breaker: Option<breaker::Breaker>,
+  /// `--tmp-in-ram`の場合に途中のファイルを置くtmpfsのフォルダ
+  tmp_ram: Option<tmpram::TmpRam>,
+}
+
+impl Context {
+  async fn new(args: &Args) -> Result<Self> {
+    let config = config::Config::load(args.config.as_deref()).await?;
+    let filter_patterns = config
+      .filter_patterns
+      .iter()
+      .chain(args.filter_pattern.iter())
+      .cloned()
+      .collect::<Vec<_>>();
+    let filters = noise::Filters::new(
+      args.keep_page_numbers,
+      args.keep_line_numbers,
+      &filter_patterns,
+    )?;
+    let seal_patterns = seal::Patterns::new(&config.seal_patterns)?;
+    let rewrite_rules = rewrite::Rules::load(args.rewrite_rules.as_deref()).await?;
+    let templates = template::Templates::load(
+      args.header_template.as_deref(),
+      args.footer_template.as_deref(),
+    )
+    .await?;
+    let law_ids = law_id::LawIds::load(args.law_ids.as_deref()).await?;
+    // 応答が止まった接続で処理全体が止まらないように、接続と要求のそれぞれに時間の上限を設ける
+    let mut client_builder = reqwest::Client::builder()
+      .user_agent(format!(
+        "{}/{}",
+        robots::USER_AGENT,
+        env!("CARGO_PKG_VERSION")
+      ))
+      .connect_timeout(Duration::from_secs(args.connect_timeout))
+      .timeout(Duration::from_secs(args.request_timeout));
+    if let Some(ca_cert) = &args.ca_cert {
+      for cert in load_certificates(ca_cert).await? {
+        client_builder = client_builder.add_root_certificate(cert);
+      }
+    }
+    if args.insecure_tls {
+      logging::warn("TLS", None, Message::InsecureTls);
+      client_builder = client_builder.danger_accept_invalid_certs(true);
+    }
+    let client = client_builder.build()?;
+    let mut credentials = auth::Credentials::default();
+    for (host, credential) in &config.credentials {
+      credentials.insert(host, credential.clone());
+    }
+    let credential = match (&args.auth_token, &args.auth_basic) {
+      (Some(token), _) => Some(auth::Credential::Token(token.clone())),
+      (None, Some(user_pass)) => Some(auth::Credential::Basic(user_pass.clone())),
+      (None, None) => None,
+    };
+    if let Some(credential) = credential {
+      let mut hosts = args.auth_host.clone();
+      if hosts.is_empty() {
+        hosts.extend(args.mirror_base.as_deref().and_then(auth::host_of));
+      }
+      if hosts.is_empty() {
+        return Err(anyhow!("{}", Message::AuthHostRequired));
+      }
+      for host in &hosts {
+        credentials.insert(host, credential.clone());
+      }
+    }
+    let tmp_ram = match args.tmp_in_ram {
+      Some(size) => {
+        let tmp_ram = tmpram::TmpRam::open(size).await;
+        match &tmp_ram {
+          Some(tmp_ram) => logging::info("Tmp In RAM", None, tmp_ram.root()),
+          None => logging::warn("Tmp In RAM", None, Message::TmpfsUnavailable),
+        }
+        tmp_ram
+      }
+      None => None,
+    };
+    Ok(Context {
+      filters,
+      seal_patterns,
+      profiles: config.profiles,
+      crop: args.crop.or(config.crop),
+      rewrite_rules,
+      templates,
+      law_ids,
+      client,
+      download_permits: Semaphore::new(args.download_jobs.max(1)),
+      ocr_permits: Semaphore::new(args.ocr_jobs.max(1)),
+      pending_permits: Semaphore::new(args.pending_limit()),
+      raster_permits: Arc::new(Semaphore::new(args.raster_jobs.max(1))),
+      throttle: args.max_bandwidth.map(throttle::Throttle::new),
+      robots: args.respect_robots.then(robots::Robots::default),
+      credentials,
+      progress: progress::Progress::new(
+        args
+          .status_file
+          .clone()
+          .unwrap_or_else(|| format!("{}/status.json", args.output)),
+      ),
+      interrupt: Arc::default(),
+      pause: Arc::default(),
+      errors: errors::ErrorLog::new(format!("{}/{}", args.err_dir(), errors::FILE_NAME)),
+      breaker: args
+        .abort_on_failure_rate
+        .map(|rate| breaker::Breaker::new(rate, args.failure_window)),
+      tmp_ram,
+    })
+  }
+
+  /// 中断されたか、失敗が多すぎて処理を止めたか
+  fn is_stopped(&self) -> bool {
+    self.interrupt.is_interrupted() || self.breaker.as_ref().is_some_and(|b| b.is_tripped())
+  }
+
+  /// 中断されるか、失敗が多すぎて処理を止めるまで待つ
+  async fn stopped(&self) {
+    match &self.breaker {
+      Some(breaker) => tokio::select! {
+        () = self.interrupt.wait() => {}
+        () = breaker.wait() => {}
+      },
+      None => self.interrupt.wait().await,
+    }
+  }
+
+  /// `--crop`を指定しない場合に、設定ファイルの`crop`を使う引数
+  fn configured_args<'a>(&self, args: &'a Args) -> Cow<'a, Args> {
+    if args.crop == self.crop {
+      Cow::Borrowed(args)
+    } else {
+      Cow::Owned(Args {
+        crop: self.crop,
+        ..args.clone()
+      })
+    }
+  }
+
+  /// 設定ファイルを読み込み直す 中断と一時停止の状態は引き継ぐ
+  async fn reload(&self, args: &Args) -> Result<Self> {
+    let mut context = Context::new(args).await?;
+    context.interrupt = Arc::clone(&self.interrupt);
+    context.pause = Arc::clone(&self.pause);
+    Ok(context)
+  }
+}
+
+/// `--mode`に応じてPDFファイルからテキストを取り出す `--fallback-chain`の場合は方法を順に試す
+async fn convert_pdf(
+  name: &str,
+  args: &Args,
+  context: &Context,
+  sidecar: &mut sidecar::Sidecar,
+  timings: &mut report::Timings,
+) -> Result<String> {
+  // `--mode both`では両方の方法の結果を比べるため、指定した方法のみを使い、pdftotextの結果もそのまま書き出す
+  let uses_chain = !args.fallback_chain.is_empty() && args.mode_dir.is_none();
+  let mut modes = if uses_chain {
+    args.fallback_chain.clone()
+  } else {
+    vec![args.mode.clone()]
+  };
+  if args.inspect_fonts && args.mode_dir.is_none() && modes.iter().any(Mode::uses_text_layer) {
+    let file_path_pdf = format!("{}/{name}.pdf", args.tmp);
+    match fonts::unextractable(&file_path_pdf).await {
+      Ok(fonts) if !fonts.is_empty() => {
+        logging::info(
+          "Inspect Fonts",
+          Some(name),
+          format!("{name}: {}", fonts.join(", ")),
+        );
+        modes.retain(|mode| !mode.uses_text_layer());
+        if modes.is_empty() {
+          modes.push(Mode::OCR);
+        }
+      }
+      Ok(_) => (),
+      Err(err) => logging::warn("Inspect Fonts", Some(name), format!("{name}: {err}")),
+    }
+  }
+  if uses_chain {
+    convert_with_fallback(name, &modes, args, context, sidecar, timings).await
+  } else {
+    convert_with(name, &modes[0], args, context, sidecar, timings).await
+  }
+}
+
+/// `--fallback-chain`の方法を順に試し、質の検査を通った最初の結果を返す
+///
+/// どの結果も通らない場合は`quality`の最も高い結果を使う。試した方法と使った方法は事件ごとのJSONファイルに記録する
+async fn convert_with_fallback(
+  name: &str,
+  modes: &[Mode],
+  args: &Args,
+  context: &Context,
+  sidecar: &mut sidecar::Sidecar,
+  timings: &mut report::Timings,
+) -> Result<String> {
+  let file_path_pdf = format!("{}/{name}.pdf", args.tmp);
+  let mut attempts = Vec::new();
+  let mut chosen: Option<(String, String, sidecar::Sidecar)> = None;
+  let mut best_quality = None;
+  let mut last_err = None;
+  for mode in modes {
+    // 前の方法が`--compress-cache`で圧縮したPDFファイルを展開する
+    cache::decompress(&file_path_pdf).await?;
+    let engine = value_name(mode);
+    let mut attempt_sidecar = sidecar.clone();
+    let result = convert_with(name, mode, args, context, &mut attempt_sidecar, timings).await;
+    let (quality, rejected) = match &result {
+      Ok(txt) => (
+        suspicious::quality(txt),
+        fallback::reject_reason(txt, args.fallback_quality),
+      ),
+      Err(err) => (None, Some(format!("failed: {err}"))),
+    };
+    attempts.push(fallback::Attempt {
+      engine: engine.clone(),
+      quality,
+      rejected: rejected.clone(),
+    });
+    let Some(reason) = rejected else {
+      chosen = result.ok().map(|txt| (engine, txt, attempt_sidecar));
+      break;
+    };
+    logging::info(
+      "Fallback",
+      Some(name),
+      format!("{name}: {engine}: {reason}"),
+    );
+    match result {
+      Ok(txt) if chosen.is_none() || quality > best_quality => {
+        best_quality = quality;
+        chosen = Some((engine, txt, attempt_sidecar));
+      }
+      Ok(_) => (),
+      Err(err) => last_err = Some(err),
+    }
+  }
+  let Some((engine, txt, chosen_sidecar)) = chosen else {
+    return Err(last_err.unwrap_or_else(|| anyhow!("--fallback-chain: {name}")));
+  };
+  logging::info("Engine", Some(name), format!("{name}: {engine}"));
+  *sidecar = chosen_sidecar;
+  sidecar.engine = Some(engine);
+  sidecar.engine_attempts = attempts;
+  if args.compress_cache {
+    cache::compress(&file_path_pdf).await?;
+  }
+  Ok(txt)
+}
+
+/// `mode`の方法でPDFファイルからテキストを取り出す
+async fn convert_with(
+  name: &str,
+  mode: &Mode,
+  args: &Args,
+  context: &Context,
+  sidecar: &mut sidecar::Sidecar,
+  timings: &mut report::Timings,
+) -> Result<String> {
+  let Some(extractor) = extractor::for_mode(mode) else {
+    return Err(anyhow!("--mode bothは事件ごとにp2tとocrに分けて抽出する"));
+  };
+  let options = extractor::Options {
+    args,
+    context,
+    sidecar,
+    timings,
+  };
+  let pages = extractor.extract(&extractor::Pdf { name }, options).await?;
+  Ok(pages.into_text())
+}
+
+/// PDFファイルからテキストを抽出し、認証文などの除去と匿名化した記号の修正を行ったテキストを返す
+async fn extract_text(
+  name: &str,
+  args: &Args,
+  context: &Context,
+  sidecar: &mut sidecar::Sidecar,
+  timings: &mut report::Timings,
+) -> Result<String> {
+  if args.classify_pdf {
+    // `--compress-cache`で圧縮される前に判定する
+    match classify::classify(&format!("{}/{name}.pdf", args.tmp)).await {
+      Ok(classification) => sidecar.pdf_kind = Some(classification),
+      Err(err) => logging::warn("Classify PDF", Some(name), format!("{name}: {err}")),
+    }
+  }
+  let workspace = match &context.tmp_ram {
+    Some(tmp_ram) => {
+      let file_path_pdf = format!("{}/{name}.pdf", args.tmp);
+      // ページ数が分からない場合は見積もれないため`--tmp`で処理し、読めない理由は抽出の際に記録する
+      let workspace = match get_pdf_page_size(&file_path_pdf).await {
+        Ok(page_size) => {
+          tmp_ram
+            .enter(name, &file_path_pdf, page_size, args.dpi)
+            .await?
+        }
+        Err(_) => None,
+      };
+      if workspace.is_none() {
+        logging::info("Tmp In RAM", Some(name), format!("{name}: {}", args.tmp));
+      }
+      workspace
+    }
+    None => None,
+  };
+  // tmpfsのフォルダは、処理を終えるか中断されてworkspaceを破棄した時点で削除される
+  let mut txt = match &workspace {
+    Some(workspace) => {
+      let mut args = args.clone();
+      args.work_dir = Some(workspace.dir.clone());
+      convert_pdf(name, &args, context, sidecar, timings).await?
+    }
+    None => convert_pdf(name, args, context, sidecar, timings).await?,
+  };
+  drop(workspace);
+  let started = Instant::now();
+  if args.fix_confusions {
+    txt = confusion::fix(&txt);
+  }
+  if args.suppress_seals {
+    let (suppressed, removed) = seal::suppress(&txt, &context.seal_patterns);
+    logging::info(
+      "Suppress Seals",
+      Some(name),
+      format!("{name}: {removed} lines"),
+    );
+    txt = suppressed;
+  }
+  if args.check_labels || args.fix_labels {
+    let (issues, fixed) = labels::check(&txt, args.fix_labels);
+    sidecar.label_issues = issues;
+    if let Some(fixed) = fixed {
+      txt = fixed;
+    }
+  }
+  if args.normalize_kyujitai {
+    txt = kyujitai::normalize(&txt);
+  }
+  if args.rewrite_rules.is_some() {
+    txt = context.rewrite_rules.apply(&txt);
+  }
+  timings.postprocess += started.elapsed();
+  Ok(txt)
+}
+
+/// プレーンテキストとして出力する内容
+fn plain_text(txt: &str, args: &Args) -> String {
+  wrap::wrap(&output::remove_page_breaks(txt), args.wrap)
+}
+
+/// 主文・事実及び理由・別紙を`{name}_{部分の名前}.txt`として出力する
+///
+/// 別紙が複数ある場合は`{name}_別紙1.txt`のように番号を付ける
+///
+/// いずれかのファイルを書き出した場合は`true`を返す
+async fn write_sections(
+  name: &str,
+  section_lst: &[sections::Section],
+  args: &Args,
+) -> Result<bool> {
+  let appendix_count = section_lst
+    .iter()
+    .filter(|s| s.kind == sections::SectionKind::Appendix)
+    .count();
+  let mut appendix_num = 0;
+  let mut is_written = false;
+  let mut stream = tokio_stream::iter(section_lst);
+  while let Some(section) = stream.next().await {
+    if section.kind == sections::SectionKind::Header {
+      continue;
+    }
+    let suffix = section.kind.file_suffix();
+    let file_name = if section.kind == sections::SectionKind::Appendix && appendix_count > 1 {
+      appendix_num += 1;
+      format!("{name}_{suffix}{appendix_num}.txt")
+    } else {
+      format!("{name}_{suffix}.txt")
+    };
+    let path = output::path(&args.text_output_dir(), &file_name, args.compress_output);
+    let text = plain_text(&section.text, args);
+    is_written |= output::write(&path, text.as_bytes(), args.compress_output).await?;
+  }
+  Ok(is_written)
+}
+
+#[derive(Clone, Debug, Parser, Serialize)]
+#[command(author, version, about, long_about = None)]
+#[command(subcommand_negates_reqs = true)]
+struct Args {
+  #[command(subcommand)]
+  #[serde(skip)]
+  command: Option<SubCommand>,
+  /// 判例のリストがあるJSONファイルへのpath
+  #[arg(short, long, required = true)]
+  input: Option<String>,
+  /// 一時フォルダのpath
+  #[arg(short, long, default_value_t=String::from("tmp"))]
+  tmp: String,
   /// 生成ファイルを出力するフォルダ
   #[arg(short, long, default_value_t=String::from("."))]
   output: String,
+  /// 事件ごとの`_err.txt`と`errors.jsonl`を書き出すフォルダ（既定は一時フォルダ）
+  #[arg(long)]
+  err_dir: Option<String>,
   /// PDFのキャッシュを作成しない場合に付けるフラグ
   #[arg(long, default_value_t = false)]
   do_not_use_cache: bool,
   /// 生成後のテキストファイルがあったとしても再度実行しなおすかのフラグ
   #[arg(long, default_value_t = false)]
   force_re_run: bool,
+  /// 生成後のテキストファイルがあったとしても再度実行しなおす事件名か事件番号を1行に1件ずつ書いたファイル
+  #[arg(long)]
+  rerun_list: Option<String>,
+  /// ダウンロードも含めて処理しない事件名か事件番号を1行に1件ずつ書いたファイル
+  #[arg(long)]
+  exclude_list: Option<String>,
+  /// 指定した裁判所（「東京地裁」のような表記か`tokyo-district`のようなコード）の事件のみを処理する 複数回指定できる
+  #[arg(long)]
+  court: Vec<String>,
+  /// 一覧のJSONファイルの裁判所名を正式名称とコードに揃えてJSONファイルに出力するフラグ
+  #[arg(long, default_value_t = false)]
+  normalize_court: bool,
+  /// 一覧のJSONファイルの事件番号を元号・年・事件記号・番号に分けてJSONファイルに出力するフラグ
+  #[arg(long, default_value_t = false)]
+  parse_case_number: bool,
+  /// 一覧のJSONファイルの事件番号と判決日が抽出したテキストにあるかを調べ、無いものを`report.json`に記録するフラグ
+  #[arg(long, default_value_t = false)]
+  check_consistency: bool,
+  /// 事件ごとの結果・最初のページの縮小画像・テキストの冒頭を並べた`index.html`を出力フォルダに書き出すフラグ
+  #[arg(long, default_value_t = false)]
+  html_report: bool,
+  /// 判決日がこの日付（`YYYY-MM-DD`）以降の事件のみを処理する
+  #[arg(long)]
+  since: Option<metadata::Date>,
+  /// 一時フォルダに保存するキャッシュファイルをzstdで圧縮するフラグ
+  #[arg(long, default_value_t = false)]
+  compress_cache: bool,
+  /// 生成するテキストファイルをgzipで圧縮するフラグ
+  #[arg(long, default_value_t = false)]
+  compress_output: bool,
+  /// OCRの際にルビ（ふりがな）の行を取り除くフラグ
+  #[arg(long, default_value_t = false)]
+  drop_ruby: bool,
+  /// OCRの際に黒塗りされた領域を検出して`[redacted]`に置き換えるフラグ
+  #[arg(long, default_value_t = false)]
+  detect_redactions: bool,
+  /// OCRの際にページを縦書きと横書きの領域に分け、領域ごとに認識し直すフラグ
+  #[arg(long, default_value_t = false)]
+  split_layout: bool,
+  /// 一覧を処理し終えても終了せずに、`--daemon-interval`の間隔か一覧のファイルが更新されるたびに処理し直すフラグ
+  #[arg(long, default_value_t = false)]
+  daemon: bool,
+  /// `--daemon`の際に処理し直す間隔（秒）
+  #[arg(long, default_value_t = 3600)]
+  daemon_interval: u64,
+  /// 進み具合を書き出すファイル（既定は出力フォルダの`status.json`）
+  #[arg(long)]
+  status_file: Option<String>,
+  /// 処理に失敗した事件のファイルを移すフォルダ
+  #[arg(long, default_value_t = String::from("quarantine"))]
+  quarantine: String,
+  /// 同時にダウンロードする事件の数
+  #[arg(long, default_value_t = 1)]
+  download_jobs: usize,
+  /// 同時にテキストの抽出（OCRなど）を行う事件の数 既定値はCPUの数
+  #[arg(long, default_value_t = cpu_count())]
+  ocr_jobs: usize,
+  /// ダウンロードを終えて抽出を待つ事件を含めた、同時に処理する事件の数の上限
+  /// 指定しない場合は`--ocr-jobs`と`--download-jobs`の和
+  #[arg(long)]
+  max_pending: Option<usize>,
+  /// このプログラムと子プロセスのnice値 0（既定）から19まで
+  #[arg(long, value_parser = clap::value_parser!(i32).range(0..=19))]
+  nice: Option<i32>,
+  /// このプログラムと子プロセスのI/Oの優先度をアイドルクラスにするフラグ
+  #[arg(long, default_value_t = false)]
+  ionice: bool,
+  /// 長いPDFファイルのページを区間に分けて画像に変換する際に、全ての事件を合わせて同時に動かすpdftoppmの数
+  #[arg(long, default_value_t = 1)]
+  raster_jobs: usize,
+  /// 全てのダウンロードを合わせた1秒あたりの最大の大きさ
+  #[arg(long)]
+  max_bandwidth: Option<throttle::Bandwidth>,
+  /// PDFファイルのホストのrobots.txtに従うフラグ
+  #[arg(long, default_value_t = false)]
+  respect_robots: bool,
+  /// `--auth-host`に送るBearerトークン
+  #[arg(long, conflicts_with = "auth_basic")]
+  #[serde(skip)]
+  auth_token: Option<String>,
+  /// `--auth-host`に送るBasic認証の`user:pass`
+  #[arg(long)]
+  #[serde(skip)]
+  auth_basic: Option<String>,
+  /// `--auth-token`・`--auth-basic`を送るホスト名 指定しない場合は`--mirror-base`のホスト
+  #[arg(long)]
+  auth_host: Vec<String>,
+  /// 追加で信頼するCA証明書（PEM形式）のpath
+  #[arg(long)]
+  ca_cert: Option<String>,
+  /// TLSの証明書を検証しないフラグ
+  #[arg(long, default_value_t = false)]
+  insecure_tls: bool,
+  #[cfg(feature = "notify")]
+  #[command(flatten)]
+  #[serde(skip)]
+  notify: notify::Options,
+  /// 接続の時間の上限（秒）
+  #[arg(long, default_value_t = 30)]
+  connect_timeout: u64,
+  /// ダウンロードの要求の開始から終了までの時間の上限（秒）
+  #[arg(long, default_value_t = 600)]
+  request_timeout: u64,
+  /// PDFファイルが見つからない場合に、元のURLと同じpathを試すミラーサーバのURL
+  #[arg(long)]
+  mirror_base: Option<String>,
+  /// ダウンロードするファイルの最大の大きさ（バイト数）
+  #[arg(long)]
+  max_download_size: Option<u64>,
+  /// 日本語の文書に現れにくい語の割合（0〜1）がこれより大きいページを、OCRの結果が崩れているとみなして`report.json`に記録する
+  #[arg(long)]
+  flag_suspicious_pages: Option<f64>,
+  /// `--mode both`の際に、pdftotextとOCRのテキストの重なり（0〜1）がこれより小さい事件を`report.json`で示す
+  #[arg(long, default_value_t = 0.5)]
+  disagreement_threshold: f64,
+  /// OCRの単語の信頼度の平均（0〜100）がこれより低いページを、画像を加工してOCRし直し、信頼度が高い方の結果を使う
+  #[arg(long, value_parser = clap::value_parser!(u32).range(0..=100))]
+  reocr_confidence: Option<u32>,
+  /// pdftoppmで変換したページの画像をPDFファイルのSHA-256ごとに一時フォルダに残し、処理し直す際に使う
+  #[arg(long, default_value_t = false)]
+  image_cache: bool,
+  /// 処理するPDFファイルの最大の大きさ（バイト数）
+  #[arg(long)]
+  max_pdf_size: Option<u64>,
+  /// 処理に失敗した事件に加えて、`--max-pdf-size`より大きいため処理しなかった事件があった場合も、残りの事件を処理してからエラーとして終了する
+  #[arg(long, default_value_t = false)]
+  strict: bool,
+  /// 最近処理した事件のうち失敗した割合がこれを超えたら残りの事件の処理を止める（`20%`のように指定する）
+  #[arg(long)]
+  abort_on_failure_rate: Option<breaker::FailureRate>,
+  /// `--abort-on-failure-rate`の割合を求める、最近処理した事件の数
+  #[arg(long, default_value_t = 20)]
+  failure_window: usize,
+  /// 1つのPDFファイルで処理する最大のページ数
+  #[arg(long)]
+  max_pages: Option<usize>,
+  /// tesseractが使うスレッドの数
+  #[arg(long)]
+  tesseract_threads: Option<usize>,
+  /// `p2t`・`ensemble`の際に、pdftotextで文字を取り出せないフォントを含むPDFファイルをOCRで処理するフラグ
+  #[arg(long, default_value_t = false)]
+  inspect_fonts: bool,
+  /// PDFファイルがスキャンしたものかどうかを判定してJSONファイルに出力するフラグ
+  #[arg(long, default_value_t = false)]
+  classify_pdf: bool,
+  /// 項目番号をもとに段落を分けてJSONファイルに出力するフラグ
+  #[arg(long, default_value_t = false)]
+  segment_paragraphs: bool,
+  /// 当事者・代理人・裁判官の名前を取り出してJSONファイルに出力するフラグ
+  #[arg(long, default_value_t = false)]
+  extract_parties: bool,
+  /// 法令の条文の引用を取り出してJSONファイルに出力するフラグ
+  #[arg(long, default_value_t = false)]
+  extract_statutes: bool,
+  /// `--extract-statutes`の際に、組み込みの表に加えて法令名から法令IDへの対応に用いるJSONファイル
+  #[arg(long)]
+  law_ids: Option<String>,
+  /// 他の裁判例の引用を取り出してJSONファイルに出力するフラグ
+  #[arg(long, default_value_t = false)]
+  extract_precedents: bool,
+  /// 匿名化した記号（Ａ、Ｂ、Ｘ１など）の不自然な使われ方を検出してJSONファイルに出力するフラグ
+  #[arg(long, default_value_t = false)]
+  check_labels: bool,
+  /// 検出した記号の誤認識を確立した記号に修正するフラグ
+  #[arg(long, default_value_t = false)]
+  fix_labels: bool,
+  /// 長音「ー」と漢数字「一」などの形の似た文字の取り違えを、前後の文字から明らかな場合に直すフラグ
+  #[arg(long, default_value_t = false)]
+  fix_confusions: bool,
+  /// 旧字体（辯・國など）を新字体に直すフラグ
+  #[arg(long, default_value_t = false)]
+  normalize_kyujitai: bool,
+  /// 抽出したテキストに順に適用する、正規表現による置き換えの一覧を書いたJSONファイル
+  #[arg(long)]
+  rewrite_rules: Option<String>,
+  /// `txt`・`md`の出力ファイルの最初に加える内容のテンプレートのファイル
+  #[arg(long)]
+  header_template: Option<String>,
+  /// `txt`・`md`の出力ファイルの最後に加える内容のテンプレートのファイル
+  #[arg(long)]
+  footer_template: Option<String>,
+  /// 主文・事実及び理由・別紙をそれぞれ別のファイルにも出力するフラグ
+  #[arg(long, default_value_t = false)]
+  split_sections: bool,
+  /// 別紙・別表を出力から取り除くフラグ
+  #[arg(long, default_value_t = false)]
+  drop_appendix: bool,
+  /// 別紙・別表を出力から取り除き、別のファイルに出力するフラグ
+  #[arg(long, default_value_t = false)]
+  separate_appendix: bool,
+  /// pdftotextの結果からページ番号の行を取り除かないフラグ
+  #[arg(long, default_value_t = false)]
+  keep_page_numbers: bool,
+  /// pdftotextの結果から行番号で始まる行を取り除かないフラグ
+  #[arg(long, default_value_t = false)]
+  keep_line_numbers: bool,
+  /// 最初と最後のページの認証文・受付印・透かしの行を取り除くフラグ
+  #[arg(long, default_value_t = false)]
+  suppress_seals: bool,
+  /// 取り除く行に一致する正規表現 複数回指定できる
+  #[arg(long)]
+  filter_pattern: Vec<String>,
+  /// 設定ファイル（JSON）のpath
+  #[arg(long)]
+  config: Option<String>,
+  /// OCRの前にページの画像から切り取る範囲（`{幅}x{高さ}+{左}+{上}`） 指定しない場合は設定ファイルの`crop`を使う
+  #[arg(long)]
+  crop: Option<crop::Crop>,
+  /// `--crop`のピクセルの値を、pdfinfoで読んだページの大きさに合わせてA4のページとの比で伸び縮みさせるフラグ
+  #[arg(long, default_value_t = false)]
+  scale_crop: bool,
+  /// OCRの際にpdftoppmでページを画像に変換する解像度
+  #[arg(long, default_value_t = 150)]
+  dpi: u32,
+  /// OCRに渡すページの画像の形式
+  #[arg(long, value_enum, default_value_t=images::Format::Jpeg)]
+  #[serde(serialize_with = "serialize_value_name")]
+  image_format: images::Format,
+  /// ページの画像をファイルに書き出さず、pdftoppm・convert・tesseractの標準入出力で受け渡すフラグ
+  #[arg(long, default_value_t = false)]
+  pipe_images: bool,
+  /// 途中のファイルをtmpfsに置く場合の合計の大きさの上限 値を省略した場合は1G
+  #[arg(
+    long,
+    value_name = "SIZE",
+    num_args = 0..=1,
+    require_equals = true,
+    default_missing_value = "1G"
+  )]
+  tmp_in_ram: Option<tmpram::Size>,
+  /// `--mode both`で本文などを書き出す出力フォルダの中のフォルダ 方法ごとに設定する
+  #[arg(skip)]
+  #[serde(skip)]
+  mode_dir: Option<&'static str>,
+  /// `--tmp-in-ram`で事件の途中のファイルを置くtmpfsのフォルダ 事件ごとに設定する
+  #[arg(skip)]
+  #[serde(skip)]
+  work_dir: Option<String>,
+  /// `--pipe-images`に加えて、ページのテキストも一時フォルダに書き出さずにメモリの上で繋げるフラグ
+  #[arg(long, default_value_t = false)]
+  in_memory: bool,
+  /// OCRの際にpdftoppmでページをグレースケールの画像に変換するフラグ
+  #[arg(long, default_value_t = false)]
+  gray: bool,
+  /// OCRの前にページの画像の傾きを直すフラグ
+  #[arg(long, default_value_t = false)]
+  deskew: bool,
+  /// tesseractの`-l`に渡すOCRの言語
+  #[arg(long, default_value_t = String::from("jpn"))]
+  ocr_lang: String,
+  /// OCRの結果の行を繋げず、元の改行を残すフラグ
+  #[arg(long, default_value_t = false)]
+  no_join: bool,
   /// 生テキスト抽出をどの方法で行うかの選択
   #[arg(short, long, value_enum, default_value_t=Mode::P2T)]
+  #[serde(serialize_with = "serialize_value_name")]
   mode: Mode,
+  /// 事件ごとに順に試す方法 前の方法の結果が質の検査を通らなかった場合に次の方法を使う
+  #[arg(long, value_enum, value_delimiter = ',')]
+  #[serde(serialize_with = "serialize_value_names")]
+  fallback_chain: Vec<Mode>,
+  /// `--fallback-chain`の質の検査で、抽出したテキストの`quality`がこれより低い場合は次の方法に進む
+  #[arg(long, default_value_t = 0.9)]
+  fallback_quality: f64,
+  /// 事件を処理する順番
+  #[arg(long, value_enum, default_value_t=Order::Key)]
+  #[serde(serialize_with = "serialize_value_name")]
+  order: Order,
+  /// エラー・警告・実行の結果の概要などのメッセージの言語
+  #[arg(long, value_enum, default_value_t = messages::Lang::Ja)]
+  lang_messages: messages::Lang,
+  /// 進み具合や警告のログの形式 `json`の場合は1行に1つのJSONオブジェクトを標準エラー出力か`--log-file`に書き出す
+  #[arg(long, value_enum, default_value_t = logging::LogFormat::Text)]
+  log_format: logging::LogFormat,
+  /// ログを標準出力・標準エラー出力の代わりに追記するファイル
+  #[arg(long)]
+  log_file: Option<String>,
+  /// 生成するファイルの形式
+  #[arg(long, value_enum, default_value_t=output::Format::Txt)]
+  #[serde(serialize_with = "serialize_value_name")]
+  format: output::Format,
+  /// テキストファイルの行を折り返す幅（半角文字を1、全角文字を2と数える）か、折り返さない場合はnone
+  #[arg(long, default_value = "none")]
+  wrap: wrap::Wrap,
+}
+
+impl Args {
+  /// エラー出力を書き出すフォルダ
+  fn err_dir(&self) -> &str {
+    self.err_dir.as_deref().unwrap_or(&self.tmp)
+  }
+
+  /// 本文・JSONファイルなどを書き出すフォルダ `--mode both`の場合は出力フォルダの中の方法ごとのフォルダになる
+  fn text_output_dir(&self) -> String {
+    match self.mode_dir {
+      Some(dir) => format!("{}/{dir}", self.output),
+      None => self.output.clone(),
+    }
+  }
+
+  /// 出力の有無を調べるフォルダ
+  fn text_output_dirs(&self) -> Vec<String> {
+    match self.mode {
+      Mode::Both => BOTH_MODES
+        .iter()
+        .map(|(_, dir)| format!("{}/{dir}", self.output))
+        .collect(),
+      _ => vec![self.text_output_dir()],
+    }
+  }
+
+  /// 事件の途中のファイルのpathから拡張子などを除いた部分 `--tmp-in-ram`でtmpfsに置く場合はそのフォルダの中になる
+  fn work_name(&self, name: &str) -> String {
+    format!("{}/{name}", self.work_dir.as_deref().unwrap_or(&self.tmp))
+  }
+
+  /// `--pipe-images`で、ページの画像をファイルに書き出さずにOCRを行うかどうか
+  ///
+  /// hOCRや画像のファイルを使うオプションと併用する場合は書き出す
+  fn pipes_images(&self) -> bool {
+    (self.pipe_images || self.in_memory)
+      && !(self.drop_ruby
+        || self.detect_redactions
+        || self.split_layout
+        || self.reocr_confidence.is_some()
+        || self.image_cache)
+  }
+
+  /// `--in-memory`で、ページのテキストも一時フォルダに書き出さずにOCRを行うかどうか
+  fn keeps_in_memory(&self) -> bool {
+    self.in_memory && self.pipes_images()
+  }
+
+  /// pdftoppmで変換する画像の形式 `--image-cache`で残す画像を形式ごとに分けるために用いる
+  fn image_variant(&self) -> &'static str {
+    match self.image_format {
+      images::Format::Jpeg if self.gray => "jpeg-gray",
+      images::Format::Jpeg => "jpeg",
+      images::Format::TiffG4 => "tiff-g4",
+    }
+  }
+
+  /// tesseractに使わせるスレッドの数
+  ///
+  /// 指定されていない場合は、同時に抽出を行う事件の数でCPUの数を分ける
+  fn tesseract_thread_limit(&self) -> Option<usize> {
+    if self.tesseract_threads.is_some() || self.ocr_jobs <= 1 {
+      return self.tesseract_threads;
+    }
+    Some((cpu_count() / self.ocr_jobs).max(1))
+  }
+
+  /// 同時に処理する事件の数 ダウンロードが抽出より先に進みすぎて、一時フォルダが抽出を待つPDFファイルで埋まらないようにする
+  fn pending_limit(&self) -> usize {
+    self
+      .max_pending
+      .unwrap_or(self.ocr_jobs.max(1) + self.download_jobs.max(1))
+      .max(1)
+  }
+
+  /// 事件ごとのJSONファイルを出力するかどうか
+  fn use_sidecar(&self) -> bool {
+    self.classify_pdf
+      || self.detect_redactions
+      || self.segment_paragraphs
+      || self.extract_parties
+      || self.extract_statutes
+      || self.normalize_court
+      || self.parse_case_number
+      || self.extract_precedents
+      || self.check_labels
+      || self.fix_labels
+      || self.reocr_confidence.is_some()
+      || !self.fallback_chain.is_empty()
+  }
+}
+
+#[derive(Clone, Debug, Subcommand)]
+enum SubCommand {
+  /// 一時フォルダのキャッシュを整理する
+  Cache {
+    #[command(subcommand)]
+    action: CacheAction,
+    /// 一時フォルダのpath
+    #[arg(short, long, global = true, default_value_t=String::from("tmp"))]
+    tmp: String,
+    /// 生成ファイルを出力したフォルダ
+    #[arg(short, long, global = true, default_value_t=String::from("."))]
+    output: String,
+  },
+  /// 生成ファイルとキャッシュの一覧を一つのアーカイブファイルにまとめる
+  Export {
+    /// 出力するアーカイブファイルのpath（.zipか.tar.gz）
+    #[arg(short, long)]
+    archive: String,
+    /// 一時フォルダのpath
+    #[arg(short, long, default_value_t=String::from("tmp"))]
+    tmp: String,
+    /// 生成ファイルを出力したフォルダ
+    #[arg(short, long, default_value_t=String::from("."))]
+    output: String,
+  },
+  /// 二つの出力フォルダのテキストファイルを比べ、追加・削除・変更された事件を表示する
+  Diff {
+    /// 以前の出力フォルダ
+    #[arg(long)]
+    old: String,
+    /// 新しい出力フォルダ
+    #[arg(long)]
+    new: String,
+  },
+  /// 見本のPDFファイルを処理し、保存しておいた正解のテキストファイルと比べる
+  Regress {
+    /// 見本のPDFファイル`{name}.pdf`と正解のテキストファイル`{name}.txt`を置いたフォルダ
+    #[arg(long)]
+    corpus: String,
+    /// 一致しない場合に正解のテキストファイルを出力で置き換えるフラグ
+    #[arg(long, default_value_t = false)]
+    update: bool,
+  },
+  /// 一覧から選んだ事件の最初のページに切り取る範囲を描き、範囲を確かめながら調整して設定ファイルに書き出す
+  Calibrate {
+    /// 範囲を確かめる事件の数
+    #[arg(long, default_value_t = 3)]
+    samples: usize,
+  },
+  /// 一覧の1つの事件の最初のページを切り取り・加工・OCRし、加工した画像とテキストを並べて書き出す
+  Preview {
+    /// 一覧のJSONファイルの事件の名前（キー）
+    #[arg(long)]
+    case: String,
+  },
+}
+
+#[derive(Clone, Debug, Subcommand)]
+enum CacheAction {
+  /// キャッシュしたPDFファイルのハッシュ値を照合し、壊れているものと実体の無いものを取り除く
+  Verify,
+  /// 出力が生成済みの事件の中間ファイルを削除する
+  Gc,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Order {
+  /// 一覧のJSONファイルのキーの順
+  Key,
+  /// 一覧のJSONファイルに書かれている順
+  Input,
+  /// 判決日の古い順 同じ日のものと判決日が分からないものはキーの順
+  #[value(alias = "date")]
+  DateAsc,
+  /// 判決日の新しい順 同じ日のものと判決日が分からないものはキーの順
+  DateDesc,
+  /// キャッシュしたPDFファイルの小さい順 キャッシュの記録が無いものは最後にキーの順
+  Size,
 }
 
 #[allow(clippy::upper_case_acronyms)]
@@ -307,59 +2485,834 @@ enum Mode {
   P2T,
   /// OCRを使用する
   OCR,
+  /// ページごとにテキストレイヤーの有無と文字化けを調べ、`pdftotext`とOCRのどちらかを使用する
+  Ensemble,
+  /// `pdftotext`とOCRの両方を使用し、出力フォルダの`p2t/`と`ocr/`にそれぞれ書き出す
+  Both,
+  /// Google Cloud VisionのAPIでOCRを行う
+  CloudVision,
+}
+
+impl Mode {
+  /// pdftotextでテキストレイヤーから文字を取り出す方法かどうか
+  fn uses_text_layer(&self) -> bool {
+    matches!(self, Mode::P2T | Mode::Ensemble)
+  }
+}
+
+/// `--mode both`で事件ごとに順に使う方法と、その出力を書き出すフォルダ
+const BOTH_MODES: [(Mode, &str); 2] = [
+  (Mode::P2T, output::MODE_DIRS[0]),
+  (Mode::OCR, output::MODE_DIRS[1]),
+];
+
+/// `--download-jobs`の数までに制限してPDFファイルを取得し、ダウンロードした場合はPDFファイルであることを確かめる
+async fn fetch_checked_pdf(
+  name: &str,
+  urls: &[String],
+  is_downloads: bool,
+  args: &Args,
+  context: &Context,
+  timings: &mut report::Timings,
+) -> Result<Option<cache::Fetch>> {
+  let fetch = {
+    let _permit = context.download_permits.acquire().await?;
+    context.pause.wait_resumed().await;
+    context.progress.start(name).await?;
+    let started = Instant::now();
+    let fetch = fetch_pdf(name, urls, is_downloads, args, context).await?;
+    timings.download += started.elapsed();
+    fetch
+  };
+  if is_downloads {
+    let kind = cache::classify_file(&format!("{}/{name}.pdf", args.tmp)).await?;
+    if kind != cache::Kind::Pdf {
+      return Err(anyhow!("{}", Message::NotPdf(&kind.to_string(), name)));
+    }
+  }
+  Ok(fetch)
+}
+
+/// `--mode both`の際に、1つの事件をpdftotextとOCRのそれぞれで処理し、出力フォルダの`p2t/`・`ocr/`に書き出す
+///
+/// PDFファイルは最初の処理の際に取得し、次の処理では`--do-not-use-cache`の場合もダウンロードし直さない
+async fn process_both(
+  name: &str,
+  v: &Value,
+  mut is_downloads: bool,
+  args: &Args,
+  context: &Context,
+  manifest: &Mutex<cache::Manifest>,
+  details: &mut report::CaseDetails,
+) -> Result<report::Status> {
+  let cache_file_path = format!("{}/{name}.pdf", args.tmp);
+  let mut is_written = false;
+  let mut texts = Vec::new();
+  for (mode, dir) in BOTH_MODES {
+    let mut mode_args = args.clone();
+    mode_args.mode = mode;
+    mode_args.mode_dir = Some(dir);
+    let status = Box::pin(process_case(
+      name,
+      v,
+      is_downloads,
+      &mode_args,
+      context,
+      manifest,
+      details,
+    ))
+    .await?;
+    match status {
+      report::Status::Written => is_written = true,
+      report::Status::Unchanged => {}
+      _ => return Ok(status),
+    }
+    is_downloads = !cache::exists(&cache_file_path);
+    texts.extend(details.text.take());
+  }
+  if let [p2t_txt, ocr_txt] = texts.as_slice() {
+    let similarity = similarity::similarity(p2t_txt, ocr_txt);
+    details.similarity = Some(similarity);
+    if similarity < args.disagreement_threshold {
+      details.disagrees = true;
+      logging::warn(
+        "Disagreement",
+        Some(name),
+        format!("{name}: {similarity:.3}"),
+      );
+    }
+  }
+  if is_written {
+    Ok(report::Status::Written)
+  } else {
+    Ok(report::Status::Unchanged)
+  }
+}
+
+/// 1つの事件のPDFファイルを取得してテキストを抽出し、出力ファイルを書き出す
+///
+/// ダウンロードと抽出はそれぞれ`--download-jobs`・`--ocr-jobs`の数までしか同時に行わない。
+/// ダウンロードを始めてから抽出を終えるまでの事件も`--max-pending`の数までとする
+async fn process_case(
+  name: &str,
+  v: &Value,
+  mut is_downloads: bool,
+  args: &Args,
+  context: &Context,
+  manifest: &Mutex<cache::Manifest>,
+  details: &mut report::CaseDetails,
+) -> Result<report::Status> {
+  if matches!(args.mode, Mode::Both) {
+    return process_both(name, v, is_downloads, args, context, manifest, details).await;
+  }
+  let configured = context.configured_args(args);
+  let args = configured.as_ref();
+  let profiled;
+  let args = match v
+    .as_object()
+    .and_then(|metadata| profile::select(&context.profiles, name, metadata))
+  {
+    Some(profile) => {
+      logging::info("Profile", Some(name), format!("{name}: {}", profile.name));
+      profiled = profile.apply(args);
+      &profiled
+    }
+    None => args,
+  };
+  let timings = &mut details.timings;
+  let tmp_name = &args.tmp;
+  let output_name = &args.output;
+  let text_output_name = &args.text_output_dir();
+  let cache_file_path = format!("{tmp_name}/{name}.pdf");
+  if !is_downloads {
+    // 以前のキャッシュにはPDFファイルの代わりにエラーページが保存されていることがあるため、ダウンロードし直す
+    let kind = cache::classify_file(&cache_file_path).await?;
+    if kind != cache::Kind::Pdf {
+      logging::info(
+        "Invalid Cache",
+        Some(name),
+        format!("{cache_file_path}: {kind}"),
+      );
+      cache::remove(&cache_file_path).await?;
+      manifest.lock().await.entries.remove(name);
+      is_downloads = true;
+    }
+  }
+  let urls = pdf_urls(v, args)?;
+  if let Some(max_pdf_size) = args.max_pdf_size {
+    let pdf_size = if is_downloads {
+      content_length(name, &urls, context).await?
+    } else {
+      let recorded_size = manifest
+        .lock()
+        .await
+        .entries
+        .get(name)
+        .map(|entry| entry.size);
+      match recorded_size {
+        Some(size) => Some(size),
+        None => fs::metadata(&cache_file_path)
+          .await
+          .ok()
+          .map(|metadata| metadata.len()),
+      }
+    };
+    if let Some(pdf_size) = pdf_size.filter(|size| *size > max_pdf_size) {
+      logging::info("Too Large", Some(name), format!("{name}: {pdf_size} bytes"));
+      return Ok(report::Status::TooLarge);
+    }
+  }
+  logging::info("START", Some(name), format!("write: {name}"));
+  let started = Instant::now();
+  let extract = async {
+    let mut sidecar = sidecar::Sidecar::default();
+    let pending = context.pending_permits.acquire().await?;
+    let mut fetch = fetch_checked_pdf(name, &urls, is_downloads, args, context, timings).await?;
+    let permit = context.ocr_permits.acquire().await?;
+    let txt = match extract_text(name, args, context, &mut sidecar, timings).await {
+      Ok(txt) => txt,
+      // 以前のダウンロードが途中で切れていた場合などはキャッシュが壊れているため、ダウンロードし直して一度だけやり直す
+      Err(err) if !is_downloads => {
+        logging::info(
+          "Corrupt Cache",
+          Some(name),
+          format!("{cache_file_path}: {err}"),
+        );
+        cache::remove(&cache_file_path).await?;
+        manifest.lock().await.entries.remove(name);
+        is_downloads = true;
+        fetch = fetch_checked_pdf(name, &urls, is_downloads, args, context, timings).await?;
+        sidecar = sidecar::Sidecar::default();
+        extract_text(name, args, context, &mut sidecar, timings).await?
+      }
+      Err(err) => return Err(err),
+    };
+    Ok::<_, anyhow::Error>((txt, sidecar, fetch, is_downloads, (pending, permit)))
+  };
+  // 中断された場合は抽出を途中で止め（子プロセスも終了させる）、出力ファイルは書き出さない
+  let (mut txt, mut sidecar, fetch, is_downloads, _permits) = tokio::select! {
+    biased;
+    () = context.stopped() => {
+      fs::remove_file(format!("{cache_file_path}.part")).await.ok();
+      logging::info("Interrupted", Some(name), name);
+      return Ok(report::Status::Interrupted);
+    }
+    extracted = extract => extracted?,
+  };
+  details.pages = get_pdf_page_size(&cache_file_path).await.ok();
+  details.text_layer_unusable = sidecar.text_layer_unusable;
+  if args.html_report {
+    write_thumbnail(name, &cache_file_path, output_name, &context.errors).await;
+  }
+  let postprocess_started = Instant::now();
+  details.quality = suspicious::quality(&txt);
+  let empty = Map::new();
+  let metadata = v.as_object().unwrap_or(&empty);
+  if let Some(threshold) = args.flag_suspicious_pages {
+    details.suspicious_pages = suspicious::find(txt.split(output::PAGE_BREAK), threshold);
+    if !details.suspicious_pages.is_empty() {
+      let pages = details
+        .suspicious_pages
+        .iter()
+        .map(|page| page.page.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+      logging::info("Suspicious", Some(name), format!("{name}: {pages}"));
+    }
+  }
+  if args.check_consistency {
+    details.mismatches = consistency::check(&txt, name, metadata);
+    for mismatch in &details.mismatches {
+      logging::warn(
+        "Consistency",
+        Some(name),
+        Message::InconsistentMetadata(name, mismatch.field, &mismatch.expected),
+      );
+    }
+  }
+  let mut appendices = Vec::new();
+  if args.drop_appendix || args.separate_appendix {
+    (txt, appendices) = sections::split_off_appendices(&txt);
+  }
+  let templates = &context.templates;
+  let output_txt = match args.format {
+    output::Format::Txt => templates.apply(plain_text(&txt, args), name, metadata),
+    output::Format::Md => templates.apply(markdown::render(&txt), name, metadata),
+    output::Format::Html => html::render(&txt, name, metadata),
+    output::Format::Akn => akn::render(&txt, name, metadata)?,
+  };
+  let file_path_txt = output::path(
+    text_output_name,
+    &format!("{name}.{}", args.format.extension()),
+    args.compress_output,
+  );
+  let mut is_written =
+    output::write(&file_path_txt, output_txt.as_bytes(), args.compress_output).await?;
+  let txt = output::remove_page_breaks(&txt);
+  if args.mode_dir.is_some() {
+    details.text = Some(txt.clone());
+  }
+  if args.html_report {
+    details.snippet = Some(txt.chars().take(SNIPPET_CHARS).collect());
+  }
+  if args.split_sections {
+    is_written |= write_sections(name, &sections::split(&txt), args).await?;
+  }
+  if args.separate_appendix {
+    is_written |= write_sections(name, &appendices, args).await?;
+  }
+  if args.segment_paragraphs {
+    sidecar.paragraphs = segment::segment(&txt);
+  }
+  if args.extract_parties {
+    sidecar.parties = Some(parties::extract(&txt));
+  }
+  if args.normalize_court {
+    sidecar.court = metadata::court_name(metadata).and_then(court::normalize);
+  }
+  if args.parse_case_number {
+    let case_number = metadata
+      .get("case_number")
+      .and_then(|v| v.as_str())
+      .unwrap_or_default();
+    match precedent::parse_case_numbers(case_number) {
+      Some(case_numbers) => sidecar.case_numbers = case_numbers,
+      None => logging::warn(
+        "Case Number",
+        Some(name),
+        Message::InvalidCaseNumber(name, case_number),
+      ),
+    }
+  }
+  if args.extract_statutes {
+    sidecar.statute_citations = statute::extract(&txt);
+    context.law_ids.resolve(&mut sidecar.statute_citations);
+  }
+  if args.extract_precedents {
+    sidecar.precedent_citations = precedent::extract(&txt);
+  }
+  if args.use_sidecar() {
+    is_written |= sidecar
+      .write(text_output_name, name, args.compress_output)
+      .await?;
+  }
+  if is_downloads {
+    let mut manifest = manifest.lock().await;
+    manifest
+      .record(tmp_name, name, fetch.unwrap_or_default())
+      .await?;
+    manifest.save(tmp_name).await?;
+  }
+  details.timings.postprocess += postprocess_started.elapsed();
+  if is_written {
+    logging::emit(
+      logging::Level::Info,
+      "END",
+      Some(name),
+      Some(started.elapsed()),
+      format!("write: {name}"),
+    );
+    Ok(report::Status::Written)
+  } else {
+    logging::emit(
+      logging::Level::Info,
+      "END",
+      Some(name),
+      Some(started.elapsed()),
+      format!("unchanged: {name}"),
+    );
+    Ok(report::Status::Unchanged)
+  }
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
+fn main() -> Result<()> {
   let args = Args::parse();
+  // tokioのスレッドを作る前に下げることで、全てのスレッドと子プロセスに引き継がせる
+  let priority_errors = priority::lower(args.nice, args.ionice);
+  tokio::runtime::Builder::new_multi_thread()
+    .enable_all()
+    .build()?
+    .block_on(start(args, priority_errors))
+}
+
+async fn start(args: Args, priority_errors: Vec<(&'static str, String)>) -> Result<()> {
+  let args = Arc::new(args);
+  messages::set_lang(args.lang_messages);
+  logging::init(args.log_format, args.log_file.as_deref())?;
+  for (program, err_msg) in &priority_errors {
+    logging::warn(
+      "Priority",
+      None,
+      Message::PriorityUnavailable(program, err_msg),
+    );
+  }
+  if args
+    .fallback_chain
+    .iter()
+    .any(|mode| matches!(mode, Mode::Both))
+  {
+    return Err(anyhow!("--fallback-chainにbothは指定できない"));
+  }
+  if (args.pipe_images || args.in_memory) && !args.pipes_images() {
+    let option = if args.in_memory {
+      "--in-memory"
+    } else {
+      "--pipe-images"
+    };
+    logging::warn("Pipe Images", None, Message::PipeImagesUnavailable(option));
+  }
+  match &args.command {
+    Some(SubCommand::Cache {
+      action,
+      tmp,
+      output,
+    }) => {
+      return match action {
+        CacheAction::Verify => cache::verify(tmp).await,
+        CacheAction::Gc => cache::gc(tmp, output).await,
+      };
+    }
+    Some(SubCommand::Export {
+      archive,
+      tmp,
+      output,
+    }) => return export::export(tmp, output, archive).await,
+    Some(SubCommand::Diff { old, new }) => return diff::run(old, new).await,
+    Some(SubCommand::Regress { corpus, update }) => {
+      return regress::run(corpus, *update, &args).await;
+    }
+    Some(SubCommand::Calibrate { samples }) => return calibrate::run(*samples, &args).await,
+    Some(SubCommand::Preview { case }) => return preview::run(case, &args).await,
+    None => (),
+  }
+  let context = Context::new(&args).await?;
+  {
+    let interrupt = Arc::clone(&context.interrupt);
+    tokio::spawn(async move { interrupt.listen().await });
+    let pause = Arc::clone(&context.pause);
+    tokio::spawn(async move { pause.listen().await });
+  }
+  if args.daemon {
+    return daemon::run(args, context).await;
+  }
+  run_once(&args, Arc::new(context)).await
+}
+
+/// 一覧の事件を一通り処理し、`status.json`に結果を書き出して通知する
+///
+/// 処理に失敗した事件があった場合は全て処理してからエラーを返す
+async fn run_once(args: &Arc<Args>, context: Arc<Context>) -> Result<()> {
+  #[cfg(feature = "notify")]
+  let started = std::time::Instant::now();
+  let mut statuses = Vec::new();
+  let result = run(Arc::clone(args), Arc::clone(&context), &mut statuses).await;
+  let ended = context.progress.end(result.as_ref().err()).await;
+  #[cfg(feature = "notify")]
+  {
+    let statuses = statuses
+      .iter()
+      .map(|(_, _, status)| *status)
+      .collect::<Vec<_>>();
+    let summary = notify::Summary::new(&statuses, started.elapsed(), result.as_ref().err());
+    notify::send(&args.notify, &summary).await;
+  }
+  result.and(ended)?;
+  let failed = statuses
+    .iter()
+    .filter(|(_, _, status)| *status == report::Status::Failed)
+    .count();
+  if failed > 0 {
+    return Err(anyhow!("{}", Message::CasesFailed(failed)));
+  }
+  let too_large = statuses
+    .iter()
+    .filter(|(_, _, status)| *status == report::Status::TooLarge)
+    .count();
+  if args.strict && too_large > 0 {
+    return Err(anyhow!("{}", Message::CasesTooLarge(too_large)));
+  }
+  Ok(())
+}
+
+/// `--mode`などに指定する値の名前
+fn value_name<T: ValueEnum>(value: &T) -> String {
+  value
+    .to_possible_value()
+    .map(|value| value.get_name().to_string())
+    .unwrap_or_default()
+}
+
+/// 記録するためのコマンドライン引数 認証情報は`***`に置き換える
+fn redacted_arguments() -> Vec<String> {
+  const SECRET_OPTIONS: [&str; 2] = ["--auth-token", "--auth-basic"];
+  let mut arguments = Vec::new();
+  let mut is_secret = false;
+  for argument in std::env::args().skip(1) {
+    if is_secret {
+      arguments.push("***".to_string());
+      is_secret = false;
+    } else if let Some((option, _)) = argument
+      .split_once('=')
+      .filter(|(option, _)| SECRET_OPTIONS.contains(option))
+    {
+      arguments.push(format!("{option}=***"));
+    } else {
+      is_secret = SECRET_OPTIONS.contains(&argument.as_str());
+      arguments.push(argument);
+    }
+  }
+  arguments
+}
+
+fn serialize_value_name<T: ValueEnum, S: serde::Serializer>(
+  value: &T,
+  serializer: S,
+) -> Result<S::Ok, S::Error> {
+  serializer.serialize_str(&value_name(value))
+}
+
+fn serialize_value_names<T: ValueEnum, S: serde::Serializer>(
+  values: &[T],
+  serializer: S,
+) -> Result<S::Ok, S::Error> {
+  serializer.collect_seq(values.iter().map(value_name))
+}
+
+/// 一覧の事件を処理し、事件ごとの結果を`statuses`に加える
+///
+/// 途中で中断した場合も、それまでに得た結果は`statuses`に残る
+async fn run(
+  args: Arc<Args>,
+  context: Arc<Context>,
+  statuses: &mut Vec<(usize, String, report::Status)>,
+) -> Result<()> {
   let tmp_name = &args.tmp;
   let output_name = &args.output;
   fs::create_dir_all(tmp_name).await?;
   fs::create_dir_all(output_name).await?;
-  let input_file_path = &args.input;
+  for dir in args.text_output_dirs() {
+    fs::create_dir_all(dir).await?;
+  }
+  fs::create_dir_all(args.err_dir()).await?;
+  if args.html_report {
+    fs::create_dir_all(format!("{output_name}/{}", report::THUMBNAIL_DIR)).await?;
+  }
+  let manifest = cache::Manifest::load(tmp_name).await?;
+  let dataset = dataset::Manifest::load(output_name).await?;
+  let rerun_list = case_list::CaseList::load(args.rerun_list.as_deref()).await?;
+  let exclude_list = case_list::CaseList::load(args.exclude_list.as_deref()).await?;
+  let mut report = report::Report::default();
+  let input_file_path = args.input.as_ref().expect("inputは必須");
   let input_json = fs::read_to_string(input_file_path).await?;
   let input_json_lst: Map<String, Value> = serde_json::from_str(&input_json)?;
-  let mut json_stream = tokio_stream::iter(input_json_lst);
-  while let Some((name, v)) = json_stream.next().await {
+  let mut input_lst = input_json_lst.into_iter().collect::<Vec<_>>();
+  // 実行ごとに処理の順番とログが変わらないように並べ替える
+  let judgment_date = |name: &str, v: &Value| {
+    v.as_object()
+      .and_then(|metadata| metadata::judgment_date(name, metadata))
+  };
+  match args.order {
+    Order::Key => input_lst.sort_by(|(a, _), (b, _)| a.cmp(b)),
+    Order::Input => (),
+    Order::DateAsc => input_lst.sort_by_cached_key(|(name, v)| {
+      let date = judgment_date(name, v);
+      (date.is_none(), date, name.clone())
+    }),
+    Order::DateDesc => input_lst.sort_by_cached_key(|(name, v)| {
+      let date = judgment_date(name, v);
+      (date.is_none(), std::cmp::Reverse(date), name.clone())
+    }),
+    Order::Size => input_lst.sort_by_cached_key(|(name, _)| {
+      let size = manifest.entries.get(name).map(|entry| entry.size);
+      (size.is_none(), size, name.clone())
+    }),
+  }
+  context.progress.set_total(input_lst.len()).await?;
+  // 一時停止の状態を`status.json`に反映する 関数を抜けると`watchers`と共に止まる
+  let mut watchers = JoinSet::new();
+  {
+    let context = Arc::clone(&context);
+    watchers.spawn(async move {
+      loop {
+        let changed = context.pause.changed();
+        if let Err(err) = context.progress.set_paused(context.pause.is_paused()).await {
+          logging::warn(
+            "Status",
+            None,
+            format!("{}: {err:#}", Message::StatusWriteFailed),
+          );
+        }
+        changed.await;
+      }
+    });
+  }
+  let manifest = Arc::new(Mutex::new(manifest));
+  let dataset = Arc::new(Mutex::new(dataset));
+  let parameters = Arc::new(dataset::Parameters {
+    version: env!("CARGO_PKG_VERSION").to_string(),
+    mode: value_name(&args.mode),
+    format: value_name(&args.format),
+    arguments: redacted_arguments(),
+    options: serde_json::to_value(context.configured_args(&args).as_ref())?,
+    tools: provenance::tool_versions().await,
+  });
+  let mut tasks = JoinSet::new();
+  // 処理中の事件 パニックした事件を見つけるために使う
+  let mut in_flight = HashMap::new();
+  let mut json_stream = tokio_stream::iter(input_lst.into_iter().enumerate());
+  while let Some((index, (name, v))) = json_stream.next().await {
     let case_number = v
       .get("case_number")
       .and_then(|v| v.as_str())
-      .ok_or_else(|| anyhow!("case_numberフィールドが無い"))?;
-    println!("case_number: {case_number}");
+      .ok_or_else(|| anyhow!("{}", Message::MissingField("case_number")))?;
+    logging::info("Case", Some(&name), format!("case_number: {case_number}"));
+    if exclude_list.contains(&name, case_number) {
+      logging::info("Excluded", Some(&name), &name);
+      context
+        .progress
+        .finish(&name, report::Status::Excluded, None)
+        .await?;
+      statuses.push((index, name, report::Status::Excluded));
+      continue;
+    }
+    if !args.court.is_empty() {
+      // 裁判所が分からない事件は`--since`と同じく取りこぼさないように処理する
+      let court = v
+        .as_object()
+        .and_then(metadata::court_name)
+        .and_then(court::normalize);
+      if let Some(court) = court.filter(|court| {
+        !args
+          .court
+          .iter()
+          .any(|filter| court::matches(filter, court))
+      }) {
+        logging::info(
+          "Excluded",
+          Some(&name),
+          format!("{name}: {}", court.full_name()),
+        );
+        context
+          .progress
+          .finish(&name, report::Status::Excluded, None)
+          .await?;
+        statuses.push((index, name, report::Status::Excluded));
+        continue;
+      }
+    }
+    if let Some(since) = &args.since {
+      // 判決日が分からない事件は取りこぼさないように処理する
+      if let Some(date) = judgment_date(&name, &v).filter(|date| !since.is_on_or_before(date)) {
+        logging::info("Before Since", Some(&name), format!("{name}: {date}"));
+        context
+          .progress
+          .finish(&name, report::Status::BeforeSince, None)
+          .await?;
+        statuses.push((index, name, report::Status::BeforeSince));
+        continue;
+      }
+    }
     let cache_file_path = format!("{tmp_name}/{name}.pdf");
-    let cache_path = Path::new(&cache_file_path);
-    let txt_file_path = format!("{name}.txt");
-    let txt_path = Path::new(&txt_file_path);
     let is_downloads = if !args.do_not_use_cache {
       // キャッシュを使うので、ファイルが無かったらダウンロードする
-      !cache_path.exists()
+      !cache::exists(&cache_file_path)
     } else {
       // キャッシュを使わないので常にダウンロード
       true
     };
-    let is_run = if !args.force_re_run {
+    let is_run = if !args.force_re_run && !rerun_list.contains(&name, case_number) {
       // 生成テキストファイルがなければ実行する
-      !txt_path.exists()
+      let file_name = format!("{name}.{}", args.format.extension());
+      args
+        .text_output_dirs()
+        .iter()
+        .any(|dir| !output::exists(dir, &file_name))
     } else {
       // 常に実行
       true
     };
     if is_run {
-      let url = v
-        .get("full_pdf_link")
-        .and_then(|v| v.as_str())
-        .ok_or_else(|| anyhow!("full_pdf_linkフィールドが無い"))?;
-      println!("[START] write: {name}");
-      match &args.mode {
-        Mode::P2T => {
-          download_and_pdftotext(&name, url, tmp_name, output_name, is_downloads).await?
+      let args = Arc::clone(&args);
+      let context = Arc::clone(&context);
+      let manifest = Arc::clone(&manifest);
+      let dataset = Arc::clone(&dataset);
+      let parameters = Arc::clone(&parameters);
+      in_flight.insert(index, name.clone());
+      tasks.spawn(async move {
+        let mut details = report::CaseDetails::default();
+        let result = async {
+          let started = Instant::now();
+          let result = process_case(
+            &name,
+            &v,
+            is_downloads,
+            &args,
+            &context,
+            &manifest,
+            &mut details,
+          )
+          .await;
+          // 失敗した事件もそれまでにかかった時間を記録する
+          details.timings.total = started.elapsed();
+          let (status, err) = match result {
+            Ok(status) => {
+              if matches!(status, report::Status::Written | report::Status::Unchanged) {
+                let mut dataset = dataset.lock().await;
+                dataset
+                  .record(&args.output, &name, Some(&parameters))
+                  .await?;
+              }
+              (status, None)
+            }
+            // Ctrl+Cで子プロセスが終了したことによるエラーは失敗としない
+            Err(_) if context.is_stopped() => {
+              logging::info("Interrupted", Some(&name), &name);
+              (report::Status::Interrupted, None)
+            }
+            Err(err) => {
+              logging::error("Failed", Some(&name), format!("{name}: {err:#}"));
+              context
+                .errors
+                .record(&name, "failed", None, &format!("{err:#}"))
+                .await?;
+              quarantine::quarantine(
+                &name,
+                &err,
+                &args.tmp,
+                args.err_dir(),
+                &args.output,
+                &args.quarantine,
+              )
+              .await?;
+              manifest.lock().await.entries.remove(&name);
+              dataset.lock().await.remove_case(&name);
+              (report::Status::Failed, Some(err))
+            }
+          };
+          if let Some(breaker) = &context.breaker {
+            if status != report::Status::Interrupted {
+              breaker.record(status == report::Status::Failed);
+            }
+          }
+          context.progress.finish(&name, status, err.as_ref()).await?;
+          Ok::<_, anyhow::Error>(status)
         }
-        Mode::OCR => download_and_ocr(&name, url, tmp_name, output_name, is_downloads).await?,
-      };
-      println!("[END] write: {name}");
+        .await;
+        (index, name, result, details)
+      });
     } else {
-      println!("[Hit Text Cache] {name}({cache_file_path})");
+      logging::info(
+        "Hit Text Cache",
+        Some(&name),
+        format!("{name}({cache_file_path})"),
+      );
+      // 以前の実行で生成したファイルが記録されていなければ記録する
+      let mut dataset = dataset.lock().await;
+      if !dataset.contains_case(&name) {
+        dataset.record(output_name, &name, None).await?;
+      }
+      drop(dataset);
+      context
+        .progress
+        .finish(&name, report::Status::Skipped, None)
+        .await?;
+      statuses.push((index, name, report::Status::Skipped));
+    }
+  }
+  let mut case_details = HashMap::new();
+  // 事件の処理の後始末に失敗しても、他の事件の結果とキャッシュの一覧は保存してからエラーとする
+  let mut task_error = None;
+  while let Some(result) = tasks.join_next().await {
+    let (index, name, result, details) = match result {
+      Ok(output) => output,
+      Err(err) => {
+        task_error.get_or_insert_with(|| anyhow!(err));
+        continue;
+      }
+    };
+    in_flight.remove(&index);
+    let status = match result {
+      Ok(status) => status,
+      Err(err) => {
+        fail_task(&name, &err, &context, &manifest, &dataset).await;
+        task_error.get_or_insert(err);
+        report::Status::Failed
+      }
+    };
+    // 処理しなかった事件は記録しない
+    if !matches!(
+      status,
+      report::Status::TooLarge | report::Status::Interrupted
+    ) {
+      case_details.insert(name.clone(), details);
     }
+    statuses.push((index, name, status));
+  }
+  // パニックした事件は結果が返らないため、残った事件を失敗とする
+  for (index, name) in in_flight {
+    let err = anyhow!("{name}の処理が異常終了した");
+    fail_task(&name, &err, &context, &manifest, &dataset).await;
+    statuses.push((index, name, report::Status::Failed));
+  }
+  // 並行して処理した場合も処理を始めた順に記録する
+  statuses.sort_by_key(|(index, _, _)| *index);
+  for (_, name, status) in statuses.iter() {
+    report.record(name, *status, case_details.remove(name));
+  }
+  manifest.lock().await.save(tmp_name).await?;
+  dataset.lock().await.save(output_name).await?;
+  report.write(output_name).await?;
+  // `--mode both`の場合はOCRの出力を示す
+  let report_text_dir = args.text_output_dirs().pop().unwrap_or_default();
+  report
+    .write_csv(output_name, &value_name(&args.mode), |name| {
+      output::path(
+        &report_text_dir,
+        &format!("{name}.{}", args.format.extension()),
+        args.compress_output,
+      )
+    })
+    .await?;
+  if args.html_report {
+    report
+      .write_html(output_name, |name| {
+        output::path(
+          match args.mode {
+            Mode::Both => BOTH_MODES[1].1,
+            _ => ".",
+          },
+          &format!("{name}.{}", args.format.extension()),
+          args.compress_output,
+        )
+      })
+      .await?;
+  }
+  if let Some(err) = task_error {
+    return Err(err);
+  }
+  if let Some(breaker) = context.breaker.as_ref().filter(|b| b.is_tripped()) {
+    return Err(anyhow!("{}", breaker.message()));
+  }
+  if context.interrupt.is_interrupted() {
+    return Err(anyhow!("{}", Message::Interrupted));
   }
   Ok(())
 }
+
+/// 事件の処理の途中で失敗した後始末を行う 後始末のエラーは実行全体を止めないよう無視する
+async fn fail_task(
+  name: &str,
+  err: &anyhow::Error,
+  context: &Context,
+  manifest: &Mutex<cache::Manifest>,
+  dataset: &Mutex<dataset::Manifest>,
+) {
+  logging::error("Failed", Some(name), format!("{name}: {err:#}"));
+  manifest.lock().await.entries.remove(name);
+  dataset.lock().await.remove_case(name);
+  context
+    .progress
+    .finish(name, report::Status::Failed, Some(err))
+    .await
+    .ok();
+}
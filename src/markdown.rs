@@ -0,0 +1,66 @@
+//! 判決文をMarkdownに変換する
+
+use crate::document::{self, Block};
+
+/// ページの区切りを表す水平線
+const HORIZONTAL_RULE: &str = "---";
+
+/// 行頭にあるとMarkdownの記法と解釈される文字を`\`でエスケープする
+fn escape(text: &str) -> String {
+  let is_ordered_list = text
+    .char_indices()
+    .find(|(_, c)| !c.is_ascii_digit())
+    .map(|(i, c)| i > 0 && (c == '.' || c == ')'))
+    .unwrap_or(false);
+  if text.starts_with(['#', '>', '-', '+', '*', '|', '`', '=']) || is_ordered_list {
+    format!("\\{text}")
+  } else {
+    text.to_string()
+  }
+}
+
+/// 主文・事実及び理由・別紙の見出しを`##`の見出しに、項目番号の入れ子をリストに、改ページを水平線にする
+pub fn render(text: &str) -> String {
+  let mut s = String::new();
+  // 現在のリストの最も浅い深さ 見出しや水平線でリストは途切れるため、その後は字下げをやり直す
+  let mut list_base: Option<usize> = None;
+  for block in document::blocks(text) {
+    match block {
+      Block::PageBreak => {
+        s.push_str(&format!("{HORIZONTAL_RULE}\n\n"));
+        list_base = None;
+      }
+      Block::Heading { text, .. } => {
+        s.push_str(&format!("## {text}\n\n"));
+        list_base = None;
+      }
+      Block::Line(line) => s.push_str(&format!("{}\n\n", escape(&line))),
+      Block::Paragraph {
+        level,
+        number: Some(number),
+        text,
+      } => {
+        let depth = level.saturating_sub(1);
+        let base = list_base.map(|base| base.min(depth)).unwrap_or(depth);
+        list_base = Some(base);
+        let indent = "  ".repeat(depth - base);
+        s.push_str(&format!("{indent}- {number}　{}\n\n", escape(&text)));
+      }
+      Block::Paragraph {
+        level,
+        number: None,
+        text,
+      } => match list_base {
+        Some(base) if level > base => {
+          let indent = "  ".repeat(level - base);
+          s.push_str(&format!("{indent}{}\n\n", escape(&text)));
+        }
+        _ => {
+          s.push_str(&format!("{}\n\n", escape(&text)));
+          list_base = None;
+        }
+      },
+    }
+  }
+  format!("{}\n", s.trim_end())
+}
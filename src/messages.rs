@@ -0,0 +1,252 @@
+//! エラー・警告・実行の結果の概要などの、処理を見守る人向けのメッセージを`--lang-messages`の言語で表す
+//!
+//! 日本語を読めない人が運用する場合にも問題を切り分けられるように、英語のメッセージも用意する。
+//! 事件ごとの進み具合を示す`[START]`などの行は、言語を問わず読めるのでそのままにする
+
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{
+  fmt,
+  sync::atomic::{AtomicBool, Ordering},
+};
+
+/// メッセージの言語
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Lang {
+  /// 日本語
+  #[default]
+  Ja,
+  /// 英語
+  En,
+}
+
+/// 英語のメッセージを使うかどうか 起動時に一度だけ設定する
+static ENGLISH: AtomicBool = AtomicBool::new(false);
+
+pub fn set_lang(lang: Lang) {
+  ENGLISH.store(lang == Lang::En, Ordering::Relaxed);
+}
+
+pub fn lang() -> Lang {
+  if ENGLISH.load(Ordering::Relaxed) {
+    Lang::En
+  } else {
+    Lang::Ja
+  }
+}
+
+/// メッセージの一覧
+#[derive(Debug, Clone, Copy)]
+pub enum Message<'a> {
+  /// 一覧のJSONファイルの事件に必要なフィールドが無い
+  MissingField(&'a str),
+  /// 全てのURLでPDFファイルが見つからない
+  PdfNotFound(&'a str),
+  /// ダウンロードしたファイルがPDFファイルではない（種類、事件の名前）
+  NotPdf(&'a str, &'a str),
+  /// ダウンロードするファイルが`--max-download-size`より大きい（大きさ、上限、URL）
+  DownloadTooLarge(Option<u64>, u64, &'a str),
+  /// `--auth-token`・`--auth-basic`を送るホストが分からない
+  AuthHostRequired,
+  InsecureTls,
+  /// 処理に失敗した事件の数
+  CasesFailed(usize),
+  /// `--strict`の際に、`--max-pdf-size`より大きいため処理しなかった事件の数
+  CasesTooLarge(usize),
+  Interrupted,
+  InterruptRequested,
+  ForceExit,
+  Paused,
+  Resumed,
+  /// `--daemon`で次に処理し直すまでの秒数
+  DaemonWaiting(u64),
+  Reloading,
+  ConfigReloadFailed,
+  StatusWriteFailed,
+  #[cfg_attr(not(feature = "notify"), allow(dead_code))]
+  NotifyFailed,
+  SdNotifyFailed,
+  TmpfsUnavailable,
+  /// `--nice`・`--ionice`で優先度を下げられなかった（コマンド、エラー）
+  PriorityUnavailable(&'a str, &'a str),
+  /// `--pipe-images`か`--in-memory`
+  PipeImagesUnavailable(&'static str),
+  /// `--abort-on-failure-rate`の上限（%）と割合を求める事件の数
+  FailureRateExceeded(f64, usize),
+  /// `--parse-case-number`の際に読めなかった事件番号（事件の名前、事件番号）
+  InvalidCaseNumber(&'a str, &'a str),
+  /// `--mode cloud-vision`のAPIキーを渡す環境変数が無い（環境変数の名前）
+  CloudVisionKeyMissing(&'static str),
+  /// pdftotextの出力が文字化けしているためOCRで処理し直す（事件の名前）
+  TextLayerUnusable(&'a str),
+  /// `--check-consistency`の際に、一覧のJSONファイルの値がテキストに見つからない（事件の名前、項目、値）
+  InconsistentMetadata(&'a str, &'a str, &'a str),
+}
+
+impl fmt::Display for Message<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    use Message::*;
+    match (lang(), self) {
+      (Lang::Ja, MissingField(field)) => write!(f, "{field}フィールドが無い"),
+      (Lang::En, MissingField(field)) => write!(f, "missing field `{field}`"),
+      (Lang::Ja, PdfNotFound(name)) => write!(f, "PDFファイルが見つからない: {name}"),
+      (Lang::En, PdfNotFound(name)) => write!(f, "PDF not found at any URL: {name}"),
+      (Lang::Ja, NotPdf(kind, name)) => {
+        write!(
+          f,
+          "ダウンロードしたファイルがPDFファイルではない({kind}): {name}"
+        )
+      }
+      (Lang::En, NotPdf(kind, name)) => {
+        write!(f, "downloaded file is not a PDF ({kind}): {name}")
+      }
+      (Lang::Ja, DownloadTooLarge(Some(size), max_size, url)) => write!(
+        f,
+        "ダウンロードするファイルが大きすぎる({size} > {max_size} bytes): {url}"
+      ),
+      (Lang::Ja, DownloadTooLarge(None, max_size, url)) => write!(
+        f,
+        "ダウンロードするファイルが大きすぎる(> {max_size} bytes): {url}"
+      ),
+      (Lang::En, DownloadTooLarge(Some(size), max_size, url)) => {
+        write!(f, "download too large ({size} > {max_size} bytes): {url}")
+      }
+      (Lang::En, DownloadTooLarge(None, max_size, url)) => {
+        write!(f, "download too large (> {max_size} bytes): {url}")
+      }
+      (Lang::Ja, AuthHostRequired) => write!(
+        f,
+        "--auth-tokenと--auth-basicには--auth-hostか--mirror-baseが必要"
+      ),
+      (Lang::En, AuthHostRequired) => write!(
+        f,
+        "--auth-token and --auth-basic require --auth-host or --mirror-base"
+      ),
+      (Lang::Ja, InsecureTls) => write!(
+        f,
+        "--insecure-tls: TLSの証明書を検証しません。通信が改ざんされても検出できません"
+      ),
+      (Lang::En, InsecureTls) => write!(
+        f,
+        "--insecure-tls: TLS certificates are not verified; tampering cannot be detected"
+      ),
+      (Lang::Ja, CasesFailed(n)) => write!(f, "{n}件の事件の処理に失敗した"),
+      (Lang::En, CasesFailed(n)) => write!(f, "{n} case(s) failed"),
+      (Lang::Ja, CasesTooLarge(n)) => write!(
+        f,
+        "--strict: {n}件の事件のPDFファイルが--max-pdf-sizeより大きいため処理しなかった"
+      ),
+      (Lang::En, CasesTooLarge(n)) => write!(
+        f,
+        "--strict: {n} case(s) skipped because the PDF exceeds --max-pdf-size"
+      ),
+      (Lang::Ja, Interrupted) => write!(
+        f,
+        "中断した。もう一度実行すると中断した事件から処理を続ける"
+      ),
+      (Lang::En, Interrupted) => write!(
+        f,
+        "interrupted; run again to resume from the interrupted cases"
+      ),
+      (Lang::Ja, InterruptRequested) => write!(
+        f,
+        "処理中の事件を中断して終了します。もう一度Ctrl+Cを押すとすぐに終了します"
+      ),
+      (Lang::En, InterruptRequested) => write!(
+        f,
+        "interrupting in-flight cases before exiting; press Ctrl+C again to exit immediately"
+      ),
+      (Lang::Ja, ForceExit) => write!(f, "終了します"),
+      (Lang::En, ForceExit) => write!(f, "exiting"),
+      (Lang::Ja, Paused) => write!(
+        f,
+        "処理中の事件が終わったら、SIGUSR2を受け取るまで新しい事件の処理を始めません"
+      ),
+      (Lang::En, Paused) => write!(
+        f,
+        "paused; in-flight cases will finish, new cases wait for SIGUSR2"
+      ),
+      (Lang::Ja, Resumed) => write!(f, "処理を再開します"),
+      (Lang::En, Resumed) => write!(f, "resumed"),
+      (Lang::Ja, DaemonWaiting(secs)) => write!(
+        f,
+        "{secs}秒後か一覧のファイルが更新された時に処理し直します"
+      ),
+      (Lang::En, DaemonWaiting(secs)) => write!(
+        f,
+        "processing again in {secs}s or when the input file changes"
+      ),
+      (Lang::Ja, Reloading) => write!(f, "設定ファイルを読み込み直します"),
+      (Lang::En, Reloading) => write!(f, "reloading the config file"),
+      (Lang::Ja, ConfigReloadFailed) => write!(f, "設定ファイルを読み込めない"),
+      (Lang::En, ConfigReloadFailed) => write!(f, "cannot reload the config file"),
+      (Lang::Ja, StatusWriteFailed) => write!(f, "status.jsonを書き出せない"),
+      (Lang::En, StatusWriteFailed) => write!(f, "cannot write status.json"),
+      (Lang::Ja, NotifyFailed) => write!(f, "通知に失敗した"),
+      (Lang::En, NotifyFailed) => write!(f, "notification failed"),
+      (Lang::Ja, SdNotifyFailed) => write!(f, "systemdに通知できない"),
+      (Lang::En, SdNotifyFailed) => write!(f, "cannot notify systemd"),
+      (Lang::Ja, FailureRateExceeded(percent, window)) => write!(
+        f,
+        "最近の{window}件の事件のうち処理に失敗した割合が{percent}%を超えたため、残りの事件の処理を止めた"
+      ),
+      (Lang::En, FailureRateExceeded(percent, window)) => write!(
+        f,
+        "more than {percent}% of the last {window} cases failed; stopped processing the remaining cases"
+      ),
+      (Lang::Ja, TmpfsUnavailable) => write!(
+        f,
+        "--tmp-in-ram: メモリ上のファイルシステム（tmpfs）が見つからないため、途中のファイルを--tmpに置きます"
+      ),
+      (Lang::En, TmpfsUnavailable) => write!(
+        f,
+        "--tmp-in-ram: no memory-backed filesystem (tmpfs) found; keeping intermediate files in --tmp"
+      ),
+      (Lang::Ja, PipeImagesUnavailable(option)) => write!(
+        f,
+        "{option}: 画像やhOCRのファイルを使うオプションと併用しているため、画像をファイルに書き出して処理します"
+      ),
+      (Lang::En, PipeImagesUnavailable(option)) => write!(
+        f,
+        "{option}: combined with options that need image or hOCR files; writing page images to files instead"
+      ),
+      (Lang::Ja, PriorityUnavailable(program, err_msg)) => {
+        write!(f, "{program}: 優先度を下げられないため、そのまま処理します: {err_msg}")
+      }
+      (Lang::En, PriorityUnavailable(program, err_msg)) => {
+        write!(f, "{program}: cannot lower the priority; continuing as is: {err_msg}")
+      }
+      (Lang::Ja, InvalidCaseNumber(name, case_number)) => {
+        write!(f, "事件番号を読めない: {name}: {case_number}")
+      }
+      (Lang::En, InvalidCaseNumber(name, case_number)) => {
+        write!(f, "cannot parse case number: {name}: {case_number}")
+      }
+      (Lang::Ja, CloudVisionKeyMissing(var)) => write!(
+        f,
+        "cloud-vision: 環境変数{var}にGoogle Cloud VisionのAPIキーを設定してください"
+      ),
+      (Lang::En, CloudVisionKeyMissing(var)) => write!(
+        f,
+        "cloud-vision: set the Google Cloud Vision API key in the {var} environment variable"
+      ),
+      (Lang::Ja, TextLayerUnusable(name)) => write!(
+        f,
+        "pdftotextの出力が文字化けしているため、OCRで処理します: {name}"
+      ),
+      (Lang::En, TextLayerUnusable(name)) => write!(
+        f,
+        "pdftotext output is garbled; falling back to OCR: {name}"
+      ),
+      (Lang::Ja, InconsistentMetadata(name, field, expected)) => write!(
+        f,
+        "一覧のJSONファイルの{field}がテキストに見つからない: {name}: {expected}"
+      ),
+      (Lang::En, InconsistentMetadata(name, field, expected)) => write!(
+        f,
+        "{field} from the input JSON not found in the extracted text: {name}: {expected}"
+      ),
+    }
+  }
+}
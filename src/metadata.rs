@@ -0,0 +1,66 @@
+//! 一覧のJSONファイルにある事件の情報を扱う
+
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::str::FromStr;
+
+/// `--since`で指定する`YYYY-MM-DD`形式の日付
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Date(String);
+
+impl FromStr for Date {
+  type Err = String;
+  /// `2024-04-01`か`2024-4-1`のように指定する
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let re = Regex::new(r"^(?P<year>\d{4})-(?P<month>\d{1,2})-(?P<day>\d{1,2})$").unwrap();
+    let caps = re
+      .captures(s.trim())
+      .ok_or_else(|| format!("`2024-04-01`のように年月日を指定してください: {s}"))?;
+    let n = |key: &str| caps[key].parse::<u32>().unwrap_or_default();
+    Ok(Date(format!(
+      "{:04}-{:02}-{:02}",
+      n("year"),
+      n("month"),
+      n("day")
+    )))
+  }
+}
+
+impl Date {
+  /// `judgment_date`で求めた`YYYY-MM-DD`の日付がこの日付と同じかそれより後か
+  pub fn is_on_or_before(&self, date: &str) -> bool {
+    self.0.as_str() <= date
+  }
+}
+
+/// 判決日を`YYYY-MM-DD`で求める
+///
+/// 一覧のJSONファイルの`date`に西暦の年月日があればそれを使い、無ければ
+/// `{事件番号}_{year}_{month}_{day}_{裁判の種類}`というファイル名から取り出す
+pub fn judgment_date(name: &str, metadata: &Map<String, Value>) -> Option<String> {
+  let date = metadata.get("date");
+  let field = |key: &str| date.and_then(|d| d.get(key)).and_then(|v| v.as_u64());
+  if let (Some(year), Some(month), Some(day)) = (field("year"), field("month"), field("day")) {
+    if year > 1000 {
+      return Some(format!("{year:04}-{month:02}-{day:02}"));
+    }
+  }
+  let re = Regex::new(r"_(?P<year>\d{4})_(?P<month>\d{1,2})_(?P<day>\d{1,2})_").unwrap();
+  let caps = re.captures(name)?;
+  let n = |key: &str| caps[key].parse::<u32>().unwrap_or_default();
+  Some(format!(
+    "{:04}-{:02}-{:02}",
+    n("year"),
+    n("month"),
+    n("day")
+  ))
+}
+
+/// 裁判所名 一覧のJSONファイルの`court_name`か`court`を使う
+pub fn court_name(metadata: &Map<String, Value>) -> Option<&str> {
+  metadata
+    .get("court_name")
+    .or_else(|| metadata.get("court"))
+    .and_then(|v| v.as_str())
+}
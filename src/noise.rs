@@ -0,0 +1,50 @@
+//! 抽出したテキストからページ番号やヘッダ・フッタなどの不要な行を取り除く
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+
+/// 行末が空白の行
+const TRAILING_SPACE_PATTERN: &str = r"\s+$";
+
+/// `- 2 -`のようなページ番号
+const PAGE_NUMBER_PATTERN: &str = r"^\s*-\s*\d+\s*-?\s*";
+
+/// 行頭の行番号
+const LINE_NUMBER_PATTERN: &str = r"^\s*\d+\s*-?\s*";
+
+/// 不要な行に一致する正規表現
+#[derive(Debug, Clone)]
+pub struct Filters {
+  /// `pdftotext`の結果に使う組み込みのものと利用者が指定したもの
+  pub pdftotext: Vec<Regex>,
+  /// OCRの結果に使う利用者が指定したもの
+  pub ocr: Vec<Regex>,
+}
+
+impl Filters {
+  /// `patterns`は設定ファイルと`--filter-pattern`で指定された正規表現
+  pub fn new(
+    keep_page_numbers: bool,
+    keep_line_numbers: bool,
+    patterns: &[String],
+  ) -> Result<Self> {
+    let ocr = patterns
+      .iter()
+      .map(|p| Regex::new(p).map_err(|e| anyhow!("filter patternの正規表現が不正: {p}: {e}")))
+      .collect::<Result<Vec<_>>>()?;
+    let mut pdftotext = vec![Regex::new(TRAILING_SPACE_PATTERN).unwrap()];
+    if !keep_page_numbers {
+      pdftotext.push(Regex::new(PAGE_NUMBER_PATTERN).unwrap());
+    }
+    if !keep_line_numbers {
+      pdftotext.push(Regex::new(LINE_NUMBER_PATTERN).unwrap());
+    }
+    pdftotext.extend(ocr.iter().cloned());
+    Ok(Filters { pdftotext, ocr })
+  }
+}
+
+/// いずれかの正規表現に一致する行かどうか
+pub fn is_noise(filters: &[Regex], line: &str) -> bool {
+  filters.iter().any(|re| re.is_match(line))
+}
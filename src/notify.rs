@@ -0,0 +1,192 @@
+//! 実行が終わった、または中断した際に、結果の概要をSlackのWebhookやメールで知らせる
+//!
+//! `notify`featureを有効にした場合のみ使える
+
+use crate::{
+  logging,
+  messages::{self, Lang, Message},
+  report::Status,
+};
+use anyhow::{anyhow, Result};
+use std::time::Duration;
+use tokio::{
+  io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+  net::TcpStream,
+};
+
+/// 通知先の指定
+#[derive(Debug, Clone, clap::Args)]
+pub struct Options {
+  /// 実行の結果を送るSlackのIncoming WebhookのURL
+  #[arg(long)]
+  notify_slack_webhook: Option<String>,
+  /// 実行の結果をメールで送る宛先
+  #[arg(long)]
+  notify_email: Option<String>,
+  /// メールの送信元のアドレス
+  #[arg(long, default_value_t = format!("{}@localhost", env!("CARGO_PKG_NAME")))]
+  notify_email_from: String,
+  /// メールを送るSMTPサーバ（`host:port`）
+  #[arg(long, default_value_t = String::from("localhost:25"))]
+  smtp_server: String,
+}
+
+/// 通知する実行の結果
+pub struct Summary {
+  /// 処理した事件の数
+  pub processed: usize,
+  /// 処理に失敗した事件の数
+  pub failed: usize,
+  /// 処理しなかった事件の数
+  pub not_processed: usize,
+  pub duration: Duration,
+  /// 中断した場合はその原因
+  pub error: Option<String>,
+}
+
+impl Summary {
+  pub fn new(statuses: &[Status], duration: Duration, error: Option<&anyhow::Error>) -> Self {
+    let count = |f: fn(&Status) -> bool| statuses.iter().filter(|status| f(status)).count();
+    let processed =
+      count(|status| matches!(status, Status::Written | Status::Unchanged | Status::Failed));
+    let failed = count(|status| *status == Status::Failed);
+    Summary {
+      processed,
+      failed,
+      not_processed: statuses.len() - processed,
+      duration,
+      error: error.map(|err| format!("{err:#}")),
+    }
+  }
+
+  fn subject(&self) -> String {
+    let state = if self.error.is_some() {
+      "aborted"
+    } else {
+      "finished"
+    };
+    format!("[{}] {state}", env!("CARGO_PKG_NAME"))
+  }
+
+  fn text(&self) -> String {
+    let secs = self.duration.as_secs();
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    let mut text = match messages::lang() {
+      Lang::Ja => format!(
+        "{}\n処理した事件：{}件（失敗：{}件）\n処理しなかった事件：{}件\n所要時間：{h}時間{m}分{s}秒\n",
+        self.subject(),
+        self.processed,
+        self.failed,
+        self.not_processed,
+      ),
+      Lang::En => format!(
+        "{}\nprocessed: {} (failed: {})\nnot processed: {}\nduration: {h}h {m}m {s}s\n",
+        self.subject(),
+        self.processed,
+        self.failed,
+        self.not_processed,
+      ),
+    };
+    if let Some(error) = &self.error {
+      match messages::lang() {
+        Lang::Ja => text.push_str(&format!("中断した原因：{error}\n")),
+        Lang::En => text.push_str(&format!("aborted by: {error}\n")),
+      }
+    }
+    text
+  }
+}
+
+async fn send_slack(webhook: &str, summary: &Summary) -> Result<()> {
+  let body = serde_json::json!({ "text": summary.text() });
+  let response = reqwest::Client::new()
+    .post(webhook)
+    .header(reqwest::header::CONTENT_TYPE, "application/json")
+    .body(body.to_string())
+    .send()
+    .await?;
+  if !response.status().is_success() {
+    return Err(anyhow!("Slackへの通知に失敗した({})", response.status()));
+  }
+  Ok(())
+}
+
+/// SMTPサーバの応答を読み、`expected`で始まる応答コードでなければエラーを返す
+async fn read_reply(reader: &mut BufReader<TcpStream>, expected: char) -> Result<()> {
+  loop {
+    let mut line = String::new();
+    if reader.read_line(&mut line).await? == 0 {
+      return Err(anyhow!("SMTPサーバとの接続が切れた"));
+    }
+    // `250-`のように4文字目が`-`の行は応答が続く
+    if line.as_bytes().get(3) == Some(&b'-') {
+      continue;
+    }
+    if !line.starts_with(expected) {
+      return Err(anyhow!("SMTPサーバがエラーを返した: {}", line.trim_end()));
+    }
+    return Ok(());
+  }
+}
+
+async fn command(reader: &mut BufReader<TcpStream>, line: &str, expected: char) -> Result<()> {
+  reader
+    .get_mut()
+    .write_all(format!("{line}\r\n").as_bytes())
+    .await?;
+  read_reply(reader, expected).await
+}
+
+/// TLSや認証を使わずに、ローカルのMTAなどのSMTPサーバにメールを渡す
+async fn send_email(options: &Options, to: &str, summary: &Summary) -> Result<()> {
+  let from = &options.notify_email_from;
+  let mut reader = BufReader::new(TcpStream::connect(&options.smtp_server).await?);
+  read_reply(&mut reader, '2').await?;
+  command(&mut reader, "EHLO localhost", '2').await?;
+  command(&mut reader, &format!("MAIL FROM:<{from}>"), '2').await?;
+  command(&mut reader, &format!("RCPT TO:<{to}>"), '2').await?;
+  command(&mut reader, "DATA", '3').await?;
+  let body = summary
+    .text()
+    .lines()
+    // 行頭の`.`はメールの終わりと区別するために重ねる
+    .map(|line| match line.strip_prefix('.') {
+      Some(_) => format!(".{line}"),
+      None => line.to_string(),
+    })
+    .collect::<Vec<_>>()
+    .join("\r\n");
+  let message = format!(
+    "From: <{from}>\r\nTo: <{to}>\r\nSubject: {}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=UTF-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n{body}\r\n.",
+    summary.subject()
+  );
+  command(&mut reader, &message, '2').await?;
+  command(&mut reader, "QUIT", '2').await?;
+  Ok(())
+}
+
+/// 指定された通知先に結果を送る
+///
+/// 通知に失敗しても実行の結果は変えず、警告を表示するだけにする
+pub async fn send(options: &Options, summary: &Summary) {
+  if let Some(webhook) = &options.notify_slack_webhook {
+    match send_slack(webhook, summary).await {
+      Ok(()) => logging::info("Notify", None, "slack"),
+      Err(err) => logging::warn(
+        "Notify",
+        None,
+        format!("{}: {err:#}", Message::NotifyFailed),
+      ),
+    }
+  }
+  if let Some(to) = &options.notify_email {
+    match send_email(options, to, summary).await {
+      Ok(()) => logging::info("Notify", None, to),
+      Err(err) => logging::warn(
+        "Notify",
+        None,
+        format!("{}: {err:#}", Message::NotifyFailed),
+      ),
+    }
+  }
+}
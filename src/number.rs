@@ -0,0 +1,74 @@
+//! 判決文中の算用数字・全角数字・漢数字を数値にする
+
+/// 算用数字（全角を含む）と漢数字にマッチする正規表現
+pub const NUMBER: &str = r"[0-9０-９]+|[〇一二三四五六七八九十百千]+";
+
+fn kanji_digit(c: char) -> Option<u32> {
+  "〇一二三四五六七八九"
+    .chars()
+    .position(|d| d == c)
+    .map(|d| d as u32)
+}
+
+/// 算用数字・全角数字・漢数字（「七〇九」「七百九」の両方の書き方）を数値にする
+pub fn parse_number(s: &str) -> Option<u32> {
+  if s.is_empty() {
+    return None;
+  }
+  if s
+    .chars()
+    .all(|c| c.is_ascii_digit() || ('０'..='９').contains(&c))
+  {
+    let digits = s
+      .chars()
+      .map(|c| match c {
+        '０'..='９' => char::from_u32(c as u32 - '０' as u32 + '0' as u32).unwrap(),
+        _ => c,
+      })
+      .collect::<String>();
+    return digits.parse::<u32>().ok();
+  }
+  if s.contains(['十', '百', '千']) {
+    let mut total = 0;
+    let mut digit = None;
+    for c in s.chars() {
+      let unit = match c {
+        '十' => 10,
+        '百' => 100,
+        '千' => 1000,
+        _ => {
+          digit = Some(kanji_digit(c)?);
+          continue;
+        }
+      };
+      total += digit.take().unwrap_or(1) * unit;
+    }
+    Some(total + digit.unwrap_or(0))
+  } else {
+    s.chars()
+      .try_fold(0, |acc, c| Some(acc * 10 + kanji_digit(c)?))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_arabic_numbers() {
+    assert_eq!(parse_number("709"), Some(709));
+    assert_eq!(parse_number("４２３"), Some(423));
+    assert_eq!(parse_number("1２"), Some(12));
+    assert_eq!(parse_number(""), None);
+  }
+
+  #[test]
+  fn parse_kanji_numbers() {
+    assert_eq!(parse_number("七〇九"), Some(709));
+    assert_eq!(parse_number("七百九"), Some(709));
+    assert_eq!(parse_number("十"), Some(10));
+    assert_eq!(parse_number("二十一"), Some(21));
+    assert_eq!(parse_number("千二百三十四"), Some(1234));
+    assert_eq!(parse_number("一"), Some(1));
+  }
+}
@@ -0,0 +1,141 @@
+//! 生成したファイルを出力フォルダに書き出す
+
+use anyhow::Result;
+use clap::ValueEnum;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use regex::Regex;
+use std::{
+  io::{Read, Write},
+  path::Path,
+};
+use tokio::{
+  fs::{self, File},
+  io::AsyncWriteExt,
+};
+
+/// gzip圧縮したファイルに付ける拡張子
+const GZIP_EXTENSION: &str = "gz";
+
+/// テキスト中でページの区切りを表す文字（改ページ）
+pub const PAGE_BREAK: char = '\x0c';
+
+/// `--mode both`でpdftotextとOCRの出力を書き出す、出力フォルダの中のフォルダ
+pub const MODE_DIRS: [&str; 2] = ["p2t", "ocr"];
+
+/// 出力するファイルの形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+  /// プレーンテキスト
+  Txt,
+  /// 部分の見出しと項目番号の入れ子を反映したMarkdown
+  Md,
+  /// 部分・段落・ページのアンカーと事件の情報を含む単独で閲覧できるHTML
+  Html,
+  /// Akoma Ntosoの`<judgment>`文書
+  Akn,
+}
+
+impl Format {
+  pub fn extension(&self) -> &'static str {
+    match self {
+      Format::Txt => "txt",
+      Format::Md => "md",
+      Format::Html => "html",
+      Format::Akn => "xml",
+    }
+  }
+}
+
+/// ページの区切りを取り除いたテキスト
+pub fn remove_page_breaks(text: &str) -> String {
+  text.replace(PAGE_BREAK, "")
+}
+
+/// 出力するファイルのpath
+///
+/// 圧縮する場合は末尾に`.gz`を付ける
+pub fn path(output_name: &str, file_name: &str, compress: bool) -> String {
+  if compress {
+    format!("{output_name}/{file_name}.{GZIP_EXTENSION}")
+  } else {
+    format!("{output_name}/{file_name}")
+  }
+}
+
+/// 形式と圧縮の有無を問わず`{name}`の出力ファイルが存在するかどうか
+pub fn exists_any_format(output_name: &str, name: &str) -> bool {
+  Format::value_variants()
+    .iter()
+    .any(|format| exists(output_name, &format!("{name}.{}", format.extension())))
+}
+
+/// 圧縮の有無を問わず出力ファイルが存在するかどうか
+pub fn exists(output_name: &str, file_name: &str) -> bool {
+  Path::new(&path(output_name, file_name, false)).exists()
+    || Path::new(&path(output_name, file_name, true)).exists()
+}
+
+/// すでにあるファイルの内容が`bytes`と同じかどうか 圧縮したファイルは展開してから比べる
+async fn is_unchanged(path: &str, bytes: &[u8], compress: bool) -> bool {
+  let Ok(existing) = fs::read(path).await else {
+    return false;
+  };
+  if compress {
+    let mut decompressed = Vec::new();
+    GzDecoder::new(existing.as_slice())
+      .read_to_end(&mut decompressed)
+      .is_ok()
+      && decompressed == bytes
+  } else {
+    existing == bytes
+  }
+}
+
+/// ファイルを書き出す 圧縮する場合はgzip圧縮してから書き出す
+///
+/// 後段の更新日時を使うキャッシュを無効にしないように、すでにあるファイルと内容が同じ場合は書き出さない
+/// 書き出した場合は`true`を返す
+pub async fn write(path: &str, bytes: &[u8], compress: bool) -> Result<bool> {
+  if is_unchanged(path, bytes, compress).await {
+    return Ok(false);
+  }
+  let mut f = File::create(path).await?;
+  if compress {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes)?;
+    f.write_all(&encoder.finish()?).await?;
+  } else {
+    f.write_all(bytes).await?;
+  }
+  f.flush().await?;
+  Ok(true)
+}
+
+/// 出力フォルダにある事件の出力ファイルの名前
+///
+/// 本文・JSONファイルと、`--split-sections`などで分けたファイルを、圧縮の有無を問わず返す。
+/// `--mode both`の`p2t/`・`ocr/`の中のファイルは`p2t/{name}.txt`のようにフォルダの名前を付けて返す
+pub async fn case_files(output_name: &str, name: &str) -> Result<Vec<String>> {
+  let re = Regex::new(&format!(
+    r"^{}(\.|_(主文|事実及び理由|別紙\d*)\.)",
+    regex::escape(name)
+  ))?;
+  let mut files = Vec::new();
+  for dir in std::iter::once("").chain(MODE_DIRS) {
+    let Ok(mut entries) = fs::read_dir(Path::new(output_name).join(dir)).await else {
+      continue;
+    };
+    while let Some(entry) = entries.next_entry().await? {
+      let file_name = entry.file_name().to_string_lossy().to_string();
+      if entry.file_type().await?.is_file() && re.is_match(&file_name) {
+        files.push(if dir.is_empty() {
+          file_name
+        } else {
+          format!("{dir}/{file_name}")
+        });
+      }
+    }
+  }
+  files.sort();
+  Ok(files)
+}
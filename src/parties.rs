@@ -0,0 +1,135 @@
+//! 判決文から当事者・代理人・裁判官の名前を取り出す
+
+use crate::sections;
+use regex::Regex;
+use serde::Serialize;
+
+/// 当事者の呼称
+const PARTY_ROLES: [&str; 17] = [
+  "原告兼反訴被告",
+  "被告兼反訴原告",
+  "附帯控訴人",
+  "附帯被控訴人",
+  "再審原告",
+  "再審被告",
+  "被控訴人",
+  "控訴人",
+  "被上告人",
+  "上告人",
+  "相手方",
+  "申立人",
+  "抗告人",
+  "被告人",
+  "原告",
+  "被告",
+  "参加人",
+];
+
+/// 名前とみなす文字列の最大文字数
+const NAME_MAX_CHARS: usize = 40;
+
+/// 裁判官の名前とみなす文字列の最大文字数
+const JUDGE_NAME_MAX_CHARS: usize = 10;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Party {
+  /// 原告・被告などの呼称
+  pub role: String,
+  pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Attorney {
+  /// 代理する当事者の呼称
+  pub party_role: Option<String>,
+  pub name: String,
+}
+
+/// 判決文から取り出した当事者などの情報
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Parties {
+  pub parties: Vec<Party>,
+  pub attorneys: Vec<Attorney>,
+  /// 裁判長
+  pub presiding_judge: Option<String>,
+  /// 裁判長を含まない裁判官
+  pub judges: Vec<String>,
+}
+
+/// 空白を取り除き、名前として妥当な長さであれば返す
+fn normalize_name(s: &str, max_chars: usize) -> Option<String> {
+  let name = s.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+  let is_name = !name.is_empty()
+    && name.chars().count() <= max_chars
+    && !name.contains(['。', '，', '、', ',']);
+  is_name.then_some(name)
+}
+
+/// 当事者は主文より前の部分から、裁判官は全体から取り出す
+pub fn extract(text: &str) -> Parties {
+  let party_re = Regex::new(&format!(
+    r"^[\s　]*(?P<role>{})(?:ら)?[\s　]+(?P<name>\S.*)$",
+    PARTY_ROLES.join("|")
+  ))
+  .unwrap();
+  let attorney_re =
+    Regex::new(r"^[\s　]*(?:同|上記\S*)?(?:訴訟)?(?:代理人弁護士|弁護人)[\s　]+(?P<name>\S.*)$")
+      .unwrap();
+  let same_re = Regex::new(r"^[\s　]*同[\s　]+(?P<name>\S.*)$").unwrap();
+  let presiding_re = Regex::new(r"^[\s　]*裁判長裁判官[\s　]+(?P<name>\S.*)$").unwrap();
+  let judge_re = Regex::new(r"^[\s　]*裁判官[\s　]+(?P<name>\S.*)$").unwrap();
+  let mut parties = Parties::default();
+  let mut in_header = true;
+  // 直前の行が代理人の行であったかどうか
+  let mut in_attorneys = false;
+  for line in text.lines() {
+    if sections::is_main_text_heading(line) {
+      in_header = false;
+    }
+    if let Some(name) = presiding_re
+      .captures(line)
+      .and_then(|caps| normalize_name(&caps["name"], JUDGE_NAME_MAX_CHARS))
+    {
+      parties.presiding_judge = Some(name);
+      continue;
+    }
+    if let Some(name) = judge_re
+      .captures(line)
+      .and_then(|caps| normalize_name(&caps["name"], JUDGE_NAME_MAX_CHARS))
+    {
+      parties.judges.push(name);
+      continue;
+    }
+    if !in_header {
+      continue;
+    }
+    let party_role = parties.parties.last().map(|party| party.role.clone());
+    if let Some(name) = attorney_re
+      .captures(line)
+      .and_then(|caps| normalize_name(&caps["name"], NAME_MAX_CHARS))
+    {
+      parties.attorneys.push(Attorney { party_role, name });
+      in_attorneys = true;
+      continue;
+    }
+    if in_attorneys {
+      if let Some(name) = same_re
+        .captures(line)
+        .and_then(|caps| normalize_name(&caps["name"], NAME_MAX_CHARS))
+      {
+        parties.attorneys.push(Attorney { party_role, name });
+        continue;
+      }
+    }
+    in_attorneys = false;
+    if let Some(caps) = party_re.captures(line) {
+      if let Some(name) = normalize_name(&caps["name"], NAME_MAX_CHARS) {
+        parties.parties.push(Party {
+          role: caps["role"].to_string(),
+          name,
+        });
+      }
+    }
+  }
+  parties
+}
@@ -0,0 +1,67 @@
+//! SIGUSR1で新しい事件の処理を始めるのを止め、SIGUSR2で再開する
+//!
+//! 止めている間も処理中の事件はそのまま終わらせる。Unix以外では使えない
+
+use crate::{logging, messages::Message};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::{futures::Notified, Notify};
+
+#[derive(Default)]
+pub struct Pause {
+  paused: AtomicBool,
+  notify: Notify,
+}
+
+impl Pause {
+  fn set(&self, paused: bool) {
+    self.paused.store(paused, Ordering::SeqCst);
+    self.notify.notify_waiters();
+  }
+
+  pub fn is_paused(&self) -> bool {
+    self.paused.load(Ordering::SeqCst)
+  }
+
+  /// 止めたか再開した時に完了する
+  pub fn changed(&self) -> Notified<'_> {
+    self.notify.notified()
+  }
+
+  /// 止められていれば再開されるまで待つ
+  pub async fn wait_resumed(&self) {
+    loop {
+      let notified = self.notify.notified();
+      if !self.is_paused() {
+        return;
+      }
+      notified.await;
+    }
+  }
+
+  /// SIGUSR1・SIGUSR2を待ち受ける
+  #[cfg(unix)]
+  pub async fn listen(&self) {
+    use tokio::signal::unix::{signal, SignalKind};
+    let (Ok(mut pause), Ok(mut resume)) = (
+      signal(SignalKind::user_defined1()),
+      signal(SignalKind::user_defined2()),
+    ) else {
+      return;
+    };
+    loop {
+      let paused = tokio::select! {
+        _ = pause.recv() => true,
+        _ = resume.recv() => false,
+      };
+      if paused {
+        logging::info("Pause", None, Message::Paused);
+      } else {
+        logging::info("Resume", None, Message::Resumed);
+      }
+      self.set(paused);
+    }
+  }
+
+  #[cfg(not(unix))]
+  pub async fn listen(&self) {}
+}
@@ -0,0 +1,129 @@
+//! `--pipe-images`の際に、ページの画像をファイルに書き出さず、pdftoppm・convert・tesseractの標準入出力で受け渡してOCRを行う
+//!
+//! 一時フォルダに書き出すのはOCRの結果のテキストファイルのみになるため、大量の事件を処理する際のファイルの作成と削除を減らせる。
+//! `--in-memory`の場合はテキストファイルも書き出さない。
+//! hOCRや画像のファイルを使う`--drop-ruby`・`--detect-redactions`・`--split-layout`・`--reocr-confidence`・`--image-cache`とは併用できない
+
+use crate::{errors, images, report, tesseract, Args};
+use anyhow::Result;
+use std::{process::Stdio, time::Instant};
+use tokio::{fs, io::AsyncWriteExt, process::Command};
+
+/// `input`を標準入力に書き込みながらコマンドを実行し、標準出力と標準エラー出力を返す
+async fn run(command: &mut Command, input: &[u8]) -> Result<(Vec<u8>, String)> {
+  let mut child = command
+    .kill_on_drop(true)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()?;
+  let mut stdin = child.stdin.take().expect("stdinはpiped");
+  let input = input.to_vec();
+  // 大きな画像では出力を読まずに書き込むと詰まるため、書き込みは別のタスクで行う
+  let writer = tokio::spawn(async move {
+    // tesseractが途中で終了した場合などは書き込めなくなるが、エラーは標準エラー出力で分かる
+    stdin.write_all(&input).await.ok();
+  });
+  let output = child.wait_with_output().await?;
+  writer.await?;
+  Ok((
+    output.stdout,
+    String::from_utf8_lossy(&output.stderr).to_string(),
+  ))
+}
+
+/// `{file_name}.pdf`の`page_num`ページ目をOCRして`{file_name}-{page_num}.txt`を生成する
+pub async fn ocr_page(
+  file_name: &str,
+  page_num: usize,
+  args: &Args,
+  err_output: &mut errors::CaseErrors<'_>,
+  timings: &mut report::Timings,
+) -> Result<()> {
+  let text = recognize(file_name, page_num, args, err_output, timings).await?;
+  fs::write(format!("{file_name}-{page_num}.txt"), text).await?;
+  Ok(())
+}
+
+/// `{file_name}.pdf`の`page_num`ページ目をOCRしたテキストを返す
+pub async fn recognize(
+  file_name: &str,
+  page_num: usize,
+  args: &Args,
+  err_output: &mut errors::CaseErrors<'_>,
+  timings: &mut report::Timings,
+) -> Result<String> {
+  let started = Instant::now();
+  let file_path_pdf = format!("{file_name}.pdf");
+  let mut pdftoppm = Command::new("pdftoppm");
+  pdftoppm.arg("-r").arg(args.dpi.to_string());
+  let input_format = match args.image_format {
+    images::Format::Jpeg if !args.gray => {
+      pdftoppm.arg("-jpeg");
+      "jpg"
+    }
+    images::Format::Jpeg => {
+      pdftoppm.arg("-jpeg").arg("-gray");
+      "jpg"
+    }
+    images::Format::TiffG4 => {
+      pdftoppm.arg("-gray");
+      "pgm"
+    }
+  };
+  // 出力先を指定しない場合は標準出力に書き出す
+  pdftoppm
+    .arg("-f")
+    .arg(page_num.to_string())
+    .arg("-l")
+    .arg(page_num.to_string())
+    .arg("-singlefile")
+    .arg(&file_path_pdf);
+  let (image, err_msg) = run(&mut pdftoppm, &[]).await?;
+  err_output
+    .write("pdftoppm", Some(page_num), &err_msg)
+    .await?;
+  let rect = args
+    .crop
+    .unwrap_or_default()
+    .resolve_page(&file_path_pdf, page_num, args.dpi, args.scale_crop)
+    .await;
+  let mut convert = Command::new("convert");
+  convert
+    .arg(format!("{input_format}:-"))
+    .arg("-crop")
+    .arg(rect.to_string())
+    .arg("+repage");
+  if args.deskew {
+    convert.arg("-deskew").arg("40%").arg("+repage");
+  }
+  match args.image_format {
+    images::Format::Jpeg => convert.arg("jpg:-"),
+    images::Format::TiffG4 => convert
+      .arg("-threshold")
+      .arg("50%")
+      .arg("-type")
+      .arg("bilevel")
+      .arg("-compress")
+      .arg("Group4")
+      .arg("tif:-"),
+  };
+  let (image, err_msg) = run(&mut convert, &image).await?;
+  err_output
+    .write("convert", Some(page_num), &err_msg)
+    .await?;
+  timings.conversion += started.elapsed();
+  let started = Instant::now();
+  let mut tesseract = tesseract::command(args.tesseract_thread_limit());
+  tesseract
+    .arg("stdin")
+    .arg("stdout")
+    .arg("-l")
+    .arg(&args.ocr_lang);
+  let (text, err_msg) = run(&mut tesseract, &image).await?;
+  err_output
+    .write("tesseract", Some(page_num), &err_msg)
+    .await?;
+  timings.ocr += started.elapsed();
+  Ok(String::from_utf8_lossy(&text).to_string())
+}
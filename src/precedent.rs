@@ -0,0 +1,265 @@
+//! 判決文から他の裁判例の引用（最判平成10年3月24日、平成9年(オ)第123号など）を取り出す
+
+use crate::{
+  court::{self, Court},
+  number::{parse_number, NUMBER},
+  sections,
+};
+use regex::{Captures, Regex};
+use serde::Serialize;
+use std::fmt;
+
+/// 元号とその元年の前年の西暦
+const ERAS: [(&str, u32); 5] = [
+  ("明治", 1867),
+  ("大正", 1911),
+  ("昭和", 1925),
+  ("平成", 1988),
+  ("令和", 2018),
+];
+
+/// 「最判」「東京地判」のような略称の裁判所
+const SHORT_COURT: &str =
+  r"最大?[判決]|大[判決]|[\p{Han}]{1,4}(?:高|地|家|簡)裁?(?:[\p{Han}]{1,3}支)?[判決審]";
+
+/// 「最高裁判所」「東京高等裁判所」のような正式名称の裁判所
+const LONG_COURT: &str =
+  r"最高裁判所|最高裁|大審院|[\p{Han}]{1,6}(?:高等|地方|家庭|簡易)裁判所(?:[\p{Han}]{1,4}支部)?";
+
+/// 判例集の略称
+const REPORTERS: &str = r"民集|刑集|集民|集刑|高民集|高刑集|下民集|行集|判時|判タ|労判|金判";
+
+/// 事件番号
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CaseNumber {
+  pub era: String,
+  pub year: u32,
+  /// 事件記号（「オ」「受」「ワ」など）
+  pub symbol: String,
+  pub serial: u32,
+}
+
+/// 引用された裁判例
+#[derive(Debug, Clone, Serialize)]
+pub struct PrecedentCitation {
+  pub court: Option<String>,
+  /// 裁判所の表記を揃えたもの
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub normalized_court: Option<Court>,
+  pub case_number: Option<CaseNumber>,
+  /// `YYYY-MM-DD`形式の裁判の日付
+  pub date: Option<String>,
+  /// 「第三小法廷」などの法廷
+  pub bench: Option<String>,
+  /// 「判決」「決定」などの裁判の種類
+  pub kind: Option<String>,
+  /// 「民集52巻2号303頁」のような掲載誌
+  pub reporter: Option<String>,
+  /// 判決文中での表記
+  pub text: String,
+}
+
+impl fmt::Display for CaseNumber {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{}{}年({})第{}号",
+      self.era, self.year, self.symbol, self.serial
+    )
+  }
+}
+
+/// 元号のいずれかに一致する正規表現
+fn era_pattern() -> String {
+  ERAS
+    .iter()
+    .map(|(name, _)| *name)
+    .collect::<Vec<_>>()
+    .join("|")
+}
+
+fn parse_year(s: &str) -> Option<u32> {
+  if s == "元" {
+    Some(1)
+  } else {
+    parse_number(s)
+  }
+}
+
+fn era_offset(era: &str) -> Option<u32> {
+  ERAS
+    .iter()
+    .find(|(name, _)| *name == era)
+    .map(|(_, offset)| *offset)
+}
+
+fn parse_case_number(caps: &Captures) -> Option<CaseNumber> {
+  Some(CaseNumber {
+    era: caps.name("case_era")?.as_str().to_string(),
+    year: parse_year(caps.name("case_year")?.as_str())?,
+    symbol: caps.name("symbol")?.as_str().to_string(),
+    serial: parse_number(caps.name("serial")?.as_str())?,
+  })
+}
+
+/// 和暦の日付を`YYYY-MM-DD`形式にする
+///
+/// 「同50年」のように元号が省略されている場合は事件番号の元号を使う
+fn parse_date(caps: &Captures, case_number: Option<&CaseNumber>) -> Option<String> {
+  let era = caps
+    .name("era")
+    .map(|m| m.as_str())
+    .or_else(|| case_number.map(|c| c.era.as_str()))?;
+  let year = era_offset(era)? + parse_year(caps.name("year")?.as_str())?;
+  let month = parse_number(caps.name("month")?.as_str())?;
+  let day = parse_number(caps.name("day")?.as_str())?;
+  Some(format!("{year:04}-{month:02}-{day:02}"))
+}
+
+/// 一覧のJSONファイルの`case_number`のような事件番号を読む
+///
+/// 「令和3年(ワ)第1234号」のほか、「令和3(ワ)1234」のように「年」「第」「号」を省いたものも読む。
+/// 「令和3(ワ)1234、令和3(ワ)2345」のように併合された事件の番号が続く場合は全てを返す。読めない部分がある場合は`None`を返す
+pub fn parse_case_numbers(s: &str) -> Option<Vec<CaseNumber>> {
+  let eras = era_pattern();
+  let sp = r"[\s　]*";
+  let re = Regex::new(&format!(
+    r"^{sp}(?P<case_era>{eras}){sp}(?P<case_year>元|{NUMBER}){sp}年?{sp}[(（](?P<symbol>[^)）\s]{{1,4}})[)）]{sp}第?{sp}(?P<serial>{NUMBER}){sp}号?(?:[、，,・\s　]|及び|並びに)*"
+  ))
+  .unwrap();
+  let mut case_numbers = Vec::new();
+  let mut rest = s.trim();
+  while !rest.is_empty() {
+    let caps = re.captures(rest)?;
+    case_numbers.push(parse_case_number(&caps)?);
+    rest = &rest[caps.get(0)?.end()..];
+  }
+  (!case_numbers.is_empty()).then_some(case_numbers)
+}
+
+/// テキスト中の「令和3年(ワ)第1234号」のような事件番号を出現順に取り出す
+pub fn find_case_numbers(text: &str) -> Vec<CaseNumber> {
+  let eras = era_pattern();
+  let sp = r"[\s　]*";
+  let re = Regex::new(&format!(
+    r"(?P<case_era>{eras}){sp}(?P<case_year>元|{NUMBER}){sp}年{sp}[(（](?P<symbol>[^)）\s]{{1,4}})[)）]{sp}第?{sp}(?P<serial>{NUMBER}){sp}号"
+  ))
+  .unwrap();
+  re.captures_iter(text)
+    .filter_map(|caps| parse_case_number(&caps))
+    .collect()
+}
+
+/// テキスト中の元号を含む和暦の日付を`YYYY-MM-DD`形式で出現順に取り出す
+pub fn find_dates(text: &str) -> Vec<String> {
+  let eras = era_pattern();
+  let sp = r"[\s　]*";
+  let re = Regex::new(&format!(
+    r"(?P<era>{eras}){sp}(?P<year>元|{NUMBER}){sp}年{sp}(?P<month>{NUMBER}){sp}月{sp}(?P<day>{NUMBER}){sp}日"
+  ))
+  .unwrap();
+  re.captures_iter(text)
+    .filter_map(|caps| parse_date(&caps, None))
+    .collect()
+}
+
+/// テキスト中の裁判例の引用を出現順に取り出す
+///
+/// 裁判所と日付の組か事件番号を伴うものを引用とする
+/// 主文より前にある事件番号は当該事件のものなので、裁判所や日付を伴わない事件番号は主文以降のものに限る
+pub fn extract(text: &str) -> Vec<PrecedentCitation> {
+  let eras = era_pattern();
+  let sp = r"[\s　]*";
+  let case = format!(
+    r"(?P<case_era>{eras})(?P<case_year>元|{NUMBER})年{sp}[(（](?P<symbol>[^)）\s]{{1,4}})[)）]{sp}第?(?P<serial>{NUMBER})号"
+  );
+  let date =
+    format!(r"同?(?P<era>{eras})?(?P<year>元|{NUMBER})年(?P<month>{NUMBER})月(?P<day>{NUMBER})日");
+  let reporter = format!(
+    r"(?P<reporter>(?:{REPORTERS}){sp}(?:(?:{NUMBER})巻)?{sp}(?:(?:{NUMBER})号)?{sp}(?:(?:{NUMBER})頁)?)"
+  );
+  let re = Regex::new(&format!(
+    r"(?:(?P<court>{SHORT_COURT}|{LONG_COURT}){sp})?(?:{case}{sp})?(?:{date})?(?:{sp}(?P<bench>大法廷|第[一二三1-3１-３]小法廷))?(?:{sp}(?P<kind>判決|決定|命令))?(?:[・，,\s　]*{reporter})?"
+  ))
+  .unwrap();
+  let main_text_start = text
+    .lines()
+    .scan(0, |pos, line| {
+      let start = *pos;
+      *pos += line.len() + 1;
+      Some((start, line))
+    })
+    .find(|(_, line)| sections::is_main_text_heading(line))
+    .map(|(start, _)| start)
+    .unwrap_or(0);
+  re.captures_iter(text)
+    .filter_map(|caps| {
+      let m = caps.get(0)?;
+      let court = caps.name("court").map(|m| m.as_str().to_string());
+      let case_number = parse_case_number(&caps);
+      let has_date = caps.name("year").is_some();
+      // 裁判所と日付の組か事件番号のどちらかが無いものは引用とみなさない
+      let is_citation = match (&court, &case_number) {
+        (Some(_), _) => has_date || case_number.is_some(),
+        (None, Some(_)) => has_date || m.start() >= main_text_start,
+        (None, None) => false,
+      };
+      if !is_citation {
+        return None;
+      }
+      let date = parse_date(&caps, case_number.as_ref());
+      Some(PrecedentCitation {
+        normalized_court: court.as_deref().and_then(court::normalize),
+        court,
+        case_number,
+        date,
+        bench: caps.name("bench").map(|m| m.as_str().to_string()),
+        kind: caps.name("kind").map(|m| m.as_str().to_string()),
+        reporter: caps
+          .name("reporter")
+          .map(|m| m.as_str().chars().filter(|c| !c.is_whitespace()).collect()),
+        text: m.as_str().trim().to_string(),
+      })
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn case_number(era: &str, year: u32, symbol: &str, serial: u32) -> CaseNumber {
+    CaseNumber {
+      era: era.to_string(),
+      year,
+      symbol: symbol.to_string(),
+      serial,
+    }
+  }
+
+  #[test]
+  fn parse_case_number_formats() {
+    assert_eq!(
+      parse_case_numbers("令和3年(ワ)第1234号"),
+      Some(vec![case_number("令和", 3, "ワ", 1234)])
+    );
+    assert_eq!(
+      parse_case_numbers("平成元（行ウ）５"),
+      Some(vec![case_number("平成", 1, "行ウ", 5)])
+    );
+    assert_eq!(
+      parse_case_numbers("令和3(ワ)1234、令和3(ワ)2345"),
+      Some(vec![
+        case_number("令和", 3, "ワ", 1234),
+        case_number("令和", 3, "ワ", 2345),
+      ])
+    );
+  }
+
+  #[test]
+  fn reject_invalid_case_number() {
+    assert_eq!(parse_case_numbers(""), None);
+    assert_eq!(parse_case_numbers("令和3(ワ)"), None);
+    assert_eq!(parse_case_numbers("令和3(ワ)1234ほか"), None);
+  }
+}
@@ -0,0 +1,134 @@
+//! 一覧の1つの事件の最初のページを、本番と同じ切り取り・加工・OCRで処理し、加工した画像とテキストを並べて書き出す
+//!
+//! 全ての事件を処理する前に、設定が意図したとおりに働くかを確かめるために用いる
+
+use crate::{
+  cloud_vision, convert_pdf_page, erase_redactions, errors, fetch_pdf, html::escape, images,
+  logging, pdf_urls, pdftotext_page, preprocess_img, profile, recognize_page, sidecar::Sidecar,
+  Args, Context, Mode,
+};
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+use tokio::{fs, process::Command};
+
+/// 出力フォルダの中の、プレビューを書き出すフォルダ
+const PREVIEW_DIR: &str = "preview";
+
+const PREVIEW_STYLE: &str = "body{font-family:sans-serif;margin:1em;display:flex;gap:1em}\
+img{max-width:50vw;border:1px solid #888}\
+pre{white-space:pre-wrap;flex:1}";
+
+/// 事件`case`の最初のページの加工した画像とテキストを、出力フォルダの`preview/{case}.jpg`・`preview/{case}.txt`と、
+/// それらを並べた`preview/{case}.html`に書き出す
+///
+/// `--mode p2t`の場合はpdftotextで抽出したテキストを並べる。`--image-format tiff-g4`の場合は画像をPNGで書き出す
+pub async fn run(case: &str, args: &Args) -> Result<()> {
+  let tmp_name = &args.tmp;
+  let preview_dir = format!("{}/{PREVIEW_DIR}", args.output);
+  fs::create_dir_all(tmp_name).await?;
+  fs::create_dir_all(args.err_dir()).await?;
+  fs::create_dir_all(&preview_dir).await?;
+  let context = Context::new(args).await?;
+  let input_file_path = args
+    .input
+    .as_ref()
+    .ok_or_else(|| anyhow!("previewには--inputで一覧のJSONファイルを指定する"))?;
+  let input_json = fs::read_to_string(input_file_path).await?;
+  let input_json_lst: Map<String, Value> = serde_json::from_str(&input_json)?;
+  let v = input_json_lst
+    .get(case)
+    .ok_or_else(|| anyhow!("一覧のJSONファイルに事件が無い: {case}"))?;
+  let configured = context.configured_args(args);
+  let args = configured.as_ref();
+  let profiled;
+  let args = match v
+    .as_object()
+    .and_then(|metadata| profile::select(&context.profiles, case, metadata))
+  {
+    Some(profile) => {
+      logging::info("Profile", Some(case), format!("{case}: {}", profile.name));
+      profiled = profile.apply(args);
+      &profiled
+    }
+    None => args,
+  };
+  let file_name = format!("{tmp_name}/{case}");
+  let is_downloads = fs::metadata(format!("{file_name}.pdf")).await.is_err();
+  fetch_pdf(case, &pdf_urls(v, args)?, is_downloads, args, &context).await?;
+  let file_path_err = format!("{}/{case}_err.txt", args.err_dir());
+  let mut err_output = errors::CaseErrors::create(&file_path_err, &context.errors, case).await?;
+  let image_path = format!("{file_name}-1.{}", args.image_format.extension());
+  if let Some(err_msg) = convert_pdf_page(&file_name, 1, args).await {
+    err_output.write("pdftoppm", Some(1), &err_msg).await?;
+  }
+  preprocess_img(&file_name, 1, args, &mut err_output).await?;
+  let redactions = erase_redactions(&file_name, 1, args, &mut err_output).await?;
+  let preview_image_name = match args.image_format {
+    images::Format::Jpeg => {
+      let preview_image_name = format!("{case}.jpg");
+      fs::copy(&image_path, format!("{preview_dir}/{preview_image_name}"))
+        .await
+        .map_err(|err| anyhow!("{image_path}: {err}"))?;
+      preview_image_name
+    }
+    // TIFFはブラウザで表示できないため、PNGに変換する
+    images::Format::TiffG4 => {
+      let preview_image_name = format!("{case}.png");
+      let output = Command::new("convert")
+        .kill_on_drop(true)
+        .arg(&image_path)
+        .arg(format!("{preview_dir}/{preview_image_name}"))
+        .output()
+        .await
+        .map_err(|err| anyhow!("convert: {err}"))?;
+      if !output.status.success() {
+        return Err(anyhow!(
+          "{image_path}: {}",
+          String::from_utf8_lossy(&output.stderr)
+        ));
+      }
+      preview_image_name
+    }
+  };
+  let text = match args.mode {
+    Mode::P2T => {
+      fs::remove_file(&image_path).await.ok();
+      pdftotext_page(&format!("{file_name}.pdf"), 1).await?
+    }
+    // APIには加工する前のページの画像を送る
+    Mode::CloudVision => {
+      let api_key = cloud_vision::api_key()?;
+      let file_path_pdf = format!("{file_name}.pdf");
+      cloud_vision::recognize(&context.client, &api_key, &file_path_pdf, 1, args.dpi).await?
+    }
+    Mode::OCR | Mode::Ensemble | Mode::Both => {
+      let mut sidecar = Sidecar::default();
+      recognize_page(
+        &file_name,
+        1,
+        redactions,
+        args,
+        &mut err_output,
+        &mut sidecar,
+      )
+      .await?;
+      let txt_path = format!("{file_name}-1.txt");
+      let text = fs::read_to_string(&txt_path).await.unwrap_or_default();
+      fs::remove_file(&txt_path).await.ok();
+      fs::remove_file(format!("{file_name}-1.hocr")).await.ok();
+      text
+    }
+  };
+  err_output.flush().await?;
+  fs::write(format!("{preview_dir}/{case}.txt"), &text).await?;
+  let html = format!(
+    "<!DOCTYPE html>\n<html lang=\"ja\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{PREVIEW_STYLE}</style>\n</head>\n<body>\n<img src=\"{}\" alt=\"\">\n<pre>{}</pre>\n</body>\n</html>\n",
+    escape(case),
+    escape(&preview_image_name),
+    escape(&text)
+  );
+  let html_path = format!("{preview_dir}/{case}.html");
+  fs::write(&html_path, html).await?;
+  logging::info("Preview", Some(case), &html_path);
+  Ok(())
+}
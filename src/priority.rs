@@ -0,0 +1,37 @@
+//! `--nice`・`--ionice`の際に、このプログラムのCPUとI/Oの優先度を下げる
+//!
+//! Linuxの優先度はスレッドごとで、新しく作るスレッドと子プロセスは作った側の優先度を引き継ぐ。
+//! tokioのスレッドを作る前に優先度を下げることで、起動するtesseract・convert・popplerなどの子プロセスも全て低い優先度で動く
+
+use std::process::Command;
+
+/// 優先度を下げられなかったコマンドとその理由を返す
+pub fn lower(nice: Option<i32>, ionice: bool) -> Vec<(&'static str, String)> {
+  let pid = std::process::id().to_string();
+  let mut commands = Vec::new();
+  if let Some(nice) = nice {
+    commands.push(("renice", vec!["-n".to_string(), nice.to_string()]));
+  }
+  if ionice {
+    // アイドルクラスでは、他のプロセスがディスクを使っていない間のみ読み書きする
+    commands.push(("ionice", vec!["-c".to_string(), "3".to_string()]));
+  }
+  commands
+    .into_iter()
+    .filter_map(|(program, args)| {
+      let output = Command::new(program)
+        .args(args)
+        .arg("-p")
+        .arg(&pid)
+        .output();
+      match output {
+        Ok(output) if output.status.success() => None,
+        Ok(output) => Some((
+          program,
+          String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        )),
+        Err(err) => Some((program, err.to_string())),
+      }
+    })
+    .collect()
+}
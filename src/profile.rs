@@ -0,0 +1,109 @@
+//! 設定ファイルの`profiles`に書いた、裁判所や判決の年ごとの切り取る範囲・解像度・傾きの補正・OCRの言語
+//!
+//! 裁判所や年代によってスキャンした文書の余白や画質が異なるため、一覧のJSONファイルの裁判所名と判決日に当てはまる
+//! 最初のプロファイルの項目で、コマンドラインの設定を事件ごとに置き換える
+
+use crate::{court, crop::Crop, metadata, Args};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Profile {
+  /// ログに表示する名前
+  pub name: String,
+  /// 当てはまる裁判所（`--court`と同じく表記かコード） 書かない場合は全ての裁判所に当てはまる
+  pub court: Vec<String>,
+  /// 当てはまる判決の年（西暦）の範囲 両端を含む
+  pub from_year: Option<u32>,
+  pub to_year: Option<u32>,
+  pub crop: Option<Crop>,
+  pub dpi: Option<u32>,
+  pub deskew: Option<bool>,
+  /// tesseractの`-l`に渡す言語（`jpn+eng`など）
+  pub language: Option<String>,
+}
+
+impl Profile {
+  /// 裁判所や判決日が分からない事件は、それを条件にするプロファイルには当てはまらないものとする
+  fn matches(&self, name: &str, metadata: &Map<String, Value>) -> bool {
+    if !self.court.is_empty() {
+      let Some(court) = metadata::court_name(metadata).and_then(court::normalize) else {
+        return false;
+      };
+      if !self
+        .court
+        .iter()
+        .any(|filter| court::matches(filter, &court))
+      {
+        return false;
+      }
+    }
+    if self.from_year.is_some() || self.to_year.is_some() {
+      let Some(year) = metadata::judgment_date(name, metadata)
+        .and_then(|date| date.get(..4).and_then(|year| year.parse::<u32>().ok()))
+      else {
+        return false;
+      };
+      if self.from_year.is_some_and(|from| year < from) || self.to_year.is_some_and(|to| year > to)
+      {
+        return false;
+      }
+    }
+    true
+  }
+
+  /// プロファイルに書いた項目でコマンドラインの設定を置き換える
+  pub fn apply(&self, args: &Args) -> Args {
+    let mut args = args.clone();
+    if let Some(crop) = self.crop {
+      args.crop = Some(crop);
+    }
+    if let Some(dpi) = self.dpi {
+      args.dpi = dpi;
+    }
+    if let Some(deskew) = self.deskew {
+      args.deskew = deskew;
+    }
+    if let Some(language) = &self.language {
+      args.ocr_lang.clone_from(language);
+    }
+    args
+  }
+}
+
+/// 事件に当てはまる最初のプロファイル
+pub fn select<'a>(
+  profiles: &'a [Profile],
+  name: &str,
+  metadata: &Map<String, Value>,
+) -> Option<&'a Profile> {
+  profiles
+    .iter()
+    .find(|profile| profile.matches(name, metadata))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn select_by_court_and_year() {
+    let profiles: Vec<Profile> = serde_json::from_value(json!([
+      {"name": "old", "to_year": 1988, "dpi": 300},
+      {"name": "tokyo", "court": ["東京地裁"], "crop": "900x1400+200+150"},
+    ]))
+    .unwrap();
+    let metadata = |v: Value| v.as_object().unwrap().clone();
+    let old = metadata(json!({"date": {"year": 1980, "month": 1, "day": 1}}));
+    assert_eq!(select(&profiles, "x", &old).unwrap().name, "old");
+    let tokyo = metadata(json!({
+      "court_name": "東京地方裁判所立川支部",
+      "date": {"year": 2020, "month": 1, "day": 1},
+    }));
+    assert_eq!(select(&profiles, "x", &tokyo).unwrap().name, "tokyo");
+    let osaka = metadata(json!({"court_name": "大阪地方裁判所"}));
+    assert!(select(&profiles, "x", &osaka).is_none());
+  }
+}
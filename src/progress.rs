@@ -0,0 +1,149 @@
+//! 実行中の進み具合を`status.json`に書き出し、外部の監視から読めるようにする
+//!
+//! 処理を始めた・終えた事件があるたびに書き直す。読む側が書きかけのファイルを読まないように、別のファイルに書いてから置き換える
+
+use crate::{cache, report::Status};
+use anyhow::Result;
+use serde::Serialize;
+use std::{
+  collections::{BTreeMap, BTreeSet, VecDeque},
+  time::{Instant, SystemTime},
+};
+use tokio::{fs, sync::Mutex};
+
+/// `recent_errors`に残すエラーの数
+const RECENT_ERRORS: usize = 10;
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum State {
+  #[default]
+  Running,
+  /// SIGUSR1で新しい事件の処理を止めている
+  Paused,
+  Finished,
+  Aborted,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CaseError {
+  name: String,
+  error: String,
+  at: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+struct Snapshot {
+  state: State,
+  started_at: String,
+  updated_at: String,
+  /// 一覧の事件の数
+  total: usize,
+  /// 結果が決まった事件の数
+  done: usize,
+  counts: BTreeMap<Status, usize>,
+  /// 処理している事件
+  current: BTreeSet<String>,
+  /// 残りの事件が終わるまでの見込みの時間（秒）と日時
+  #[serde(skip_serializing_if = "Option::is_none")]
+  eta_seconds: Option<u64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  eta: Option<String>,
+  recent_errors: VecDeque<CaseError>,
+  /// 中断した場合はその原因
+  #[serde(skip_serializing_if = "Option::is_none")]
+  error: Option<String>,
+}
+
+/// 実行中の進み具合
+pub struct Progress {
+  path: String,
+  started: Instant,
+  snapshot: Mutex<Snapshot>,
+}
+
+impl Progress {
+  pub fn new(path: String) -> Self {
+    Progress {
+      path,
+      started: Instant::now(),
+      snapshot: Mutex::new(Snapshot {
+        started_at: cache::now_rfc3339(),
+        ..Snapshot::default()
+      }),
+    }
+  }
+
+  async fn write(&self, snapshot: &mut Snapshot) -> Result<()> {
+    snapshot.updated_at = cache::now_rfc3339();
+    // 終えた事件の平均の時間から見積もる
+    let remaining = snapshot.total.saturating_sub(snapshot.done);
+    let eta = (snapshot.done > 0 && remaining > 0)
+      .then(|| self.started.elapsed() / snapshot.done as u32 * remaining as u32);
+    snapshot.eta_seconds = eta.map(|eta| eta.as_secs());
+    snapshot.eta = eta.map(|eta| cache::rfc3339(SystemTime::now() + eta));
+    let part_path = format!("{}.part", self.path);
+    fs::write(&part_path, serde_json::to_string_pretty(snapshot)?).await?;
+    fs::rename(&part_path, &self.path).await?;
+    Ok(())
+  }
+
+  pub async fn set_total(&self, total: usize) -> Result<()> {
+    let mut snapshot = self.snapshot.lock().await;
+    snapshot.total = total;
+    self.write(&mut snapshot).await
+  }
+
+  pub async fn set_paused(&self, paused: bool) -> Result<()> {
+    let mut snapshot = self.snapshot.lock().await;
+    snapshot.state = if paused {
+      State::Paused
+    } else {
+      State::Running
+    };
+    self.write(&mut snapshot).await
+  }
+
+  /// 事件の処理を始めた
+  pub async fn start(&self, name: &str) -> Result<()> {
+    let mut snapshot = self.snapshot.lock().await;
+    snapshot.current.insert(name.to_string());
+    self.write(&mut snapshot).await
+  }
+
+  /// 事件の結果が決まった 失敗した場合はそのエラーも記録する
+  pub async fn finish(
+    &self,
+    name: &str,
+    status: Status,
+    err: Option<&anyhow::Error>,
+  ) -> Result<()> {
+    let mut snapshot = self.snapshot.lock().await;
+    snapshot.current.remove(name);
+    snapshot.done += 1;
+    *snapshot.counts.entry(status).or_default() += 1;
+    if let Some(err) = err {
+      if snapshot.recent_errors.len() == RECENT_ERRORS {
+        snapshot.recent_errors.pop_front();
+      }
+      snapshot.recent_errors.push_back(CaseError {
+        name: name.to_string(),
+        error: format!("{err:#}"),
+        at: cache::now_rfc3339(),
+      });
+    }
+    self.write(&mut snapshot).await
+  }
+
+  /// 実行が終わった `err`は中断した原因
+  pub async fn end(&self, err: Option<&anyhow::Error>) -> Result<()> {
+    let mut snapshot = self.snapshot.lock().await;
+    snapshot.current.clear();
+    snapshot.state = match err {
+      Some(_) => State::Aborted,
+      None => State::Finished,
+    };
+    snapshot.error = err.map(|err| format!("{err:#}"));
+    self.write(&mut snapshot).await
+  }
+}
@@ -0,0 +1,35 @@
+//! 出力ファイルを後から再現・説明できるように、使った外部のツールのバージョンを調べる
+
+use std::collections::BTreeMap;
+use tokio::process::Command;
+
+/// バージョンを調べるツールと、バージョンを表示させる引数
+const TOOLS: [(&str, &str); 4] = [
+  ("tesseract", "--version"),
+  ("pdftotext", "-v"),
+  ("pdftoppm", "-v"),
+  ("convert", "-version"),
+];
+
+/// ツールの名前ごとの、バージョンを表示させた際の最初の行
+///
+/// 標準出力に表示するツールと標準エラー出力に表示するツールがあるため、空でない方を使う。見つからないツールは含めない
+pub async fn tool_versions() -> BTreeMap<String, String> {
+  let mut versions = BTreeMap::new();
+  for (tool, arg) in TOOLS {
+    let Ok(output) = Command::new(tool).arg(arg).output().await else {
+      continue;
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let text = if stdout.trim().is_empty() {
+      stderr
+    } else {
+      stdout
+    };
+    if let Some(line) = text.lines().map(str::trim).find(|line| !line.is_empty()) {
+      versions.insert(tool.to_string(), line.to_string());
+    }
+  }
+  versions
+}
@@ -0,0 +1,56 @@
+//! 処理に失敗した事件のPDFファイルと途中までの出力を隔離フォルダに移し、失敗した理由を残す
+
+use crate::{cache, output};
+use anyhow::Result;
+use std::path::Path;
+use tokio::fs;
+
+/// 失敗した理由を書き出すファイルの名前
+const REASON_FILE_NAME: &str = "reason.txt";
+
+/// 別のファイルシステムに移す場合は複製してから削除する
+async fn move_file(from: &Path, to: &Path) -> Result<()> {
+  crate::logging::info(
+    "Quarantine",
+    None,
+    format!("{} -> {}", from.display(), to.display()),
+  );
+  if let Some(parent) = to.parent() {
+    fs::create_dir_all(parent).await?;
+  }
+  if fs::rename(from, to).await.is_err() {
+    fs::copy(from, to).await?;
+    fs::remove_file(from).await?;
+  }
+  Ok(())
+}
+
+/// `{quarantine_name}/{name}/`に事件のPDFファイル・エラー出力・出力ファイルを移し、`reason.txt`に失敗した理由を書き出す
+pub async fn quarantine(
+  name: &str,
+  reason: &anyhow::Error,
+  tmp_name: &str,
+  err_dir: &str,
+  output_name: &str,
+  quarantine_name: &str,
+) -> Result<()> {
+  let dir = Path::new(quarantine_name).join(name);
+  fs::create_dir_all(&dir).await?;
+  let pdf_path = format!("{tmp_name}/{name}.pdf");
+  let err_path = format!("{err_dir}/{name}_err.txt");
+  for path in [cache::compressed_path(&pdf_path), pdf_path, err_path] {
+    let path = Path::new(&path);
+    if let Some(file_name) = path.file_name().filter(|_| path.exists()) {
+      move_file(path, &dir.join(file_name)).await?;
+    }
+  }
+  for file_name in output::case_files(output_name, name).await? {
+    move_file(
+      &Path::new(output_name).join(&file_name),
+      &dir.join(&file_name),
+    )
+    .await?;
+  }
+  fs::write(dir.join(REASON_FILE_NAME), format!("{reason:?}\n")).await?;
+  Ok(())
+}
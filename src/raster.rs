@@ -0,0 +1,114 @@
+//! `--raster-jobs`の際に、長いPDFファイルのページを`pdftoppm -f A -l B`の区間に分けて並行して画像に変換する
+//!
+//! 1ページずつ変換すると300ページの判決でも1つのCPUしか使わないため、OCRを行う前にいくつかの区間をまとめて変換する。
+//! 同時に動かすpdftoppmの数は、全ての事件を合わせて`--raster-jobs`以下に抑える
+
+use crate::{images, pdftoppm_command, Args};
+use anyhow::Result;
+use std::sync::Arc;
+use tokio::{fs, sync::Semaphore, task::JoinSet};
+
+/// 1回のpdftoppmで変換するページ数
+pub const WINDOW_PAGES: usize = 8;
+
+/// 変換するページ番号を、連続した`WINDOW_PAGES`ページ以下の区間（両端を含む）に分ける
+fn windows(pages: &[usize]) -> Vec<(usize, usize)> {
+  let mut windows: Vec<(usize, usize)> = Vec::new();
+  for &page_num in pages {
+    match windows.last_mut() {
+      Some((first, last)) if *last + 1 == page_num && page_num - *first < WINDOW_PAGES => {
+        *last = page_num
+      }
+      _ => windows.push((page_num, page_num)),
+    }
+  }
+  windows
+}
+
+/// pdftoppmが`-singlefile`を付けずに書き出す画像のpath ページ番号は全体のページ数の桁数に0で揃えられる
+fn output_path(name: &str, page_num: usize, page_count: usize, extension: &str) -> String {
+  let digits = page_count.to_string().len();
+  format!("{name}-{page_num:0digits$}.{extension}")
+}
+
+/// `first`ページ目から`last`ページ目までを`{name}-{page_num}.{拡張子}`に変換する
+async fn convert_window(
+  name: &str,
+  first: usize,
+  last: usize,
+  page_count: usize,
+  args: &Args,
+) -> Option<String> {
+  let output = pdftoppm_command(args)
+    .arg("-f")
+    .arg(first.to_string())
+    .arg("-l")
+    .arg(last.to_string())
+    .arg(format!("{name}.pdf"))
+    .arg(name)
+    .output()
+    .await;
+  let mut err_msg = match output {
+    Ok(output) => String::from_utf8_lossy(&output.stderr).to_string(),
+    Err(err) => err.to_string(),
+  };
+  let extension = match args.image_format {
+    images::Format::Jpeg => "jpg",
+    images::Format::TiffG4 => "pgm",
+  };
+  for page_num in first..=last {
+    let path = output_path(name, page_num, page_count, extension);
+    let page_name = format!("{name}-{page_num}");
+    fs::rename(&path, format!("{page_name}.{extension}"))
+      .await
+      .ok();
+    if args.image_format == images::Format::TiffG4 {
+      if let Some(tiff_err_msg) = images::to_tiff_g4(&page_name).await {
+        err_msg.push_str(&tiff_err_msg);
+      }
+    }
+  }
+  (!err_msg.is_empty()).then_some(err_msg)
+}
+
+/// `pages`を区間に分けて並行して画像に変換し、pdftoppmのエラーを区間の最初のページ番号と共に返す
+///
+/// `page_count`はPDFファイルの全体のページ数
+pub async fn rasterize(
+  name: &str,
+  pages: &[usize],
+  page_count: usize,
+  args: &Args,
+  permits: &Arc<Semaphore>,
+) -> Result<Vec<(usize, String)>> {
+  let mut tasks = JoinSet::new();
+  for (first, last) in windows(pages) {
+    let (name, args, permits) = (name.to_string(), args.clone(), Arc::clone(permits));
+    tasks.spawn(async move {
+      let _permit = permits.acquire_owned().await?;
+      let err_msg = convert_window(&name, first, last, page_count, &args).await;
+      Ok::<_, anyhow::Error>(err_msg.map(|err_msg| (first, err_msg)))
+    });
+  }
+  let mut err_msgs = Vec::new();
+  while let Some(result) = tasks.join_next().await {
+    err_msgs.extend(result??);
+  }
+  err_msgs.sort();
+  Ok(err_msgs)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn split_windows() {
+    let pages = (1..=20)
+      .filter(|page_num| *page_num != 4)
+      .collect::<Vec<_>>();
+    assert_eq!(windows(&pages), vec![(1, 3), (5, 12), (13, 20)]);
+    assert_eq!(output_path("t/a", 7, 300, "jpg"), "t/a-007.jpg");
+    assert_eq!(output_path("t/a", 7, 9, "jpg"), "t/a-7.jpg");
+  }
+}
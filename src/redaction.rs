@@ -0,0 +1,317 @@
+//! ページ画像から黒塗りされた領域を検出し、OCRの結果に目印を入れる
+//!
+//! 検出するのは黒く塗りつぶされた領域のみで、枠線で囲んで中を白く抜いた領域（白塗り）は、空欄の表の枠と区別できないため検出しない
+
+use crate::hocr::{BBox, Line, Page, Paragraph, Word};
+use anyhow::{anyhow, Result};
+use tokio::process::Command;
+
+/// 黒塗りされた領域の代わりにテキストへ入れる目印
+pub const MARKER: &str = "[redacted]";
+
+/// 画像を区切る正方形の一辺の画素数
+const CELL_SIZE: usize = 8;
+
+/// この値以下の明るさの画素を黒とみなす
+const DARK_THRESHOLD: u8 = 80;
+
+/// 黒い画素がこの割合以上ある区画を塗りつぶされた区画とする
+const SOLID_CELL_RATIO: f64 = 0.9;
+
+/// 黒塗りとみなす領域の最小の幅と高さ（区画数）
+const MIN_WIDTH_CELLS: usize = 4;
+const MIN_HEIGHT_CELLS: usize = 2;
+
+/// 外接矩形のうち塗りつぶされた区画がこの割合以上ある領域を黒塗りとする
+const MIN_FILL_RATIO: f64 = 0.85;
+
+struct GrayImage {
+  width: usize,
+  height: usize,
+  pixels: Vec<u8>,
+}
+
+/// ImageMagickでグレースケールのPGM形式に変換して読み込む
+async fn load_gray_image(file_path: &str) -> Result<GrayImage> {
+  let output = Command::new("convert")
+    .kill_on_drop(true)
+    .arg(file_path)
+    .arg("-colorspace")
+    .arg("Gray")
+    .arg("-depth")
+    .arg("8")
+    .arg("pgm:-")
+    .output()
+    .await?;
+  parse_pgm(&output.stdout).ok_or_else(|| anyhow!("PGMの読み込み失敗: {file_path}"))
+}
+
+fn parse_pgm(bytes: &[u8]) -> Option<GrayImage> {
+  // ヘッダは"P5"・幅・高さ・最大値の4つの値が空白区切りで並ぶ
+  let mut values = Vec::new();
+  let mut pos = 0;
+  while values.len() < 4 {
+    while bytes.get(pos)?.is_ascii_whitespace() {
+      pos += 1;
+    }
+    if *bytes.get(pos)? == b'#' {
+      while *bytes.get(pos)? != b'\n' {
+        pos += 1;
+      }
+      continue;
+    }
+    let start = pos;
+    while !bytes.get(pos)?.is_ascii_whitespace() {
+      pos += 1;
+    }
+    values.push(String::from_utf8_lossy(&bytes[start..pos]).to_string());
+  }
+  if values[0] != "P5" {
+    return None;
+  }
+  let width = values[1].parse::<usize>().ok()?;
+  let height = values[2].parse::<usize>().ok()?;
+  let pixels = bytes.get(pos + 1..pos + 1 + width * height)?.to_vec();
+  Some(GrayImage {
+    width,
+    height,
+    pixels,
+  })
+}
+
+fn solid_cells(image: &GrayImage) -> (usize, usize, Vec<bool>) {
+  let cols = image.width / CELL_SIZE;
+  let rows = image.height / CELL_SIZE;
+  let mut cells = vec![false; cols * rows];
+  for row in 0..rows {
+    for col in 0..cols {
+      let mut dark = 0;
+      for y in row * CELL_SIZE..(row + 1) * CELL_SIZE {
+        let line = &image.pixels[y * image.width..(y + 1) * image.width];
+        dark += line[col * CELL_SIZE..(col + 1) * CELL_SIZE]
+          .iter()
+          .filter(|p| **p <= DARK_THRESHOLD)
+          .count();
+      }
+      cells[row * cols + col] = dark as f64 >= (CELL_SIZE * CELL_SIZE) as f64 * SOLID_CELL_RATIO;
+    }
+  }
+  (cols, rows, cells)
+}
+
+/// 塗りつぶされた区画の連結成分のうち、十分に大きく矩形に近いものを黒塗りの領域とする
+fn find_regions(image: &GrayImage) -> Vec<BBox> {
+  let (cols, rows, cells) = solid_cells(image);
+  let mut visited = vec![false; cells.len()];
+  let mut regions = Vec::new();
+  for start in 0..cells.len() {
+    if !cells[start] || visited[start] {
+      continue;
+    }
+    visited[start] = true;
+    let mut stack = vec![start];
+    let mut count = 0;
+    let (mut min_col, mut min_row, mut max_col, mut max_row) = (cols, rows, 0, 0);
+    while let Some(i) = stack.pop() {
+      count += 1;
+      let (row, col) = (i / cols, i % cols);
+      min_col = min_col.min(col);
+      max_col = max_col.max(col);
+      min_row = min_row.min(row);
+      max_row = max_row.max(row);
+      let mut neighbors = Vec::with_capacity(4);
+      if col > 0 {
+        neighbors.push(i - 1);
+      }
+      if col + 1 < cols {
+        neighbors.push(i + 1);
+      }
+      if row > 0 {
+        neighbors.push(i - cols);
+      }
+      if row + 1 < rows {
+        neighbors.push(i + cols);
+      }
+      for n in neighbors {
+        if cells[n] && !visited[n] {
+          visited[n] = true;
+          stack.push(n);
+        }
+      }
+    }
+    let width_cells = max_col - min_col + 1;
+    let height_cells = max_row - min_row + 1;
+    let fill_ratio = count as f64 / (width_cells * height_cells) as f64;
+    if width_cells >= MIN_WIDTH_CELLS
+      && height_cells >= MIN_HEIGHT_CELLS
+      && fill_ratio >= MIN_FILL_RATIO
+    {
+      regions.push(BBox {
+        x0: (min_col * CELL_SIZE) as u32,
+        y0: (min_row * CELL_SIZE) as u32,
+        x1: ((max_col + 1) * CELL_SIZE) as u32,
+        y1: ((max_row + 1) * CELL_SIZE) as u32,
+      });
+    }
+  }
+  regions
+}
+
+/// ページ画像から黒塗りされた領域を検出する
+pub async fn detect(file_path: &str) -> Result<Vec<BBox>> {
+  let image = load_gray_image(file_path).await?;
+  Ok(find_regions(&image))
+}
+
+/// 黒塗りされた領域を白く塗りつぶし、OCRが記号の羅列を出力しないようにする
+///
+/// エラーがあった場合はエラーを取得する
+pub async fn erase(file_path: &str, regions: &[BBox]) -> Option<String> {
+  let mut command = Command::new("convert");
+  command.kill_on_drop(true);
+  command.arg(file_path).arg("-fill").arg("white");
+  for r in regions {
+    command
+      .arg("-draw")
+      .arg(format!("rectangle {},{} {},{}", r.x0, r.y0, r.x1, r.y1));
+  }
+  let output = command.arg(file_path).output().await.ok();
+  output.and_then(|output| {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.as_ref().is_empty() {
+      None
+    } else {
+      Some(stderr.to_string())
+    }
+  })
+}
+
+/// 黒塗りされた領域の位置に目印を入れる
+///
+/// 領域と同じ高さにある行にはその横位置に、そうでなければ新しい段落として縦位置の順に挿入する
+pub fn mark(page: &mut Page, regions: &[BBox]) {
+  for region in regions {
+    let center_y = (region.y0 + region.y1) / 2;
+    let word = Word {
+      bbox: *region,
+      text: MARKER.to_string(),
+      confidence: None,
+    };
+    let line = page
+      .paragraphs
+      .iter_mut()
+      .flat_map(|par| par.lines.iter_mut())
+      .find(|line| line.bbox.y0 <= center_y && center_y <= line.bbox.y1);
+    if let Some(line) = line {
+      let pos = line
+        .words
+        .iter()
+        .position(|w| w.bbox.x0 >= region.x0)
+        .unwrap_or(line.words.len());
+      line.words.insert(pos, word);
+    } else {
+      let pos = page
+        .paragraphs
+        .iter()
+        .position(|par| {
+          par
+            .lines
+            .first()
+            .map(|line| line.bbox.y0 > region.y0)
+            .unwrap_or(false)
+        })
+        .unwrap_or(page.paragraphs.len());
+      let line = Line {
+        bbox: *region,
+        words: vec![word],
+      };
+      page.paragraphs.insert(pos, Paragraph { lines: vec![line] });
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::hocr;
+
+  /// 白地に`dark`の矩形（x0, y0, x1, y1）を黒く塗ったPGM形式の画像
+  fn pgm(width: usize, height: usize, dark: &[(usize, usize, usize, usize)]) -> Vec<u8> {
+    let mut bytes = format!("P5\n# synthetic\n{width} {height}\n255\n").into_bytes();
+    for y in 0..height {
+      for x in 0..width {
+        let is_dark = dark
+          .iter()
+          .any(|&(x0, y0, x1, y1)| x0 <= x && x < x1 && y0 <= y && y < y1);
+        bytes.push(if is_dark { 0 } else { 255 });
+      }
+    }
+    bytes
+  }
+
+  #[test]
+  fn parse_pgm_header() {
+    let image = parse_pgm(&pgm(16, 8, &[(0, 0, 8, 8)])).unwrap();
+    assert_eq!((image.width, image.height), (16, 8));
+    assert_eq!(image.pixels.len(), 16 * 8);
+    assert_eq!((image.pixels[0], image.pixels[15]), (0, 255));
+    assert!(parse_pgm(b"P6\n16 8\n255\n").is_none());
+    assert!(parse_pgm(b"P5\n16 8\n255\n\0\0").is_none());
+  }
+
+  #[test]
+  fn find_filled_regions() {
+    let image = parse_pgm(&pgm(
+      96,
+      64,
+      &[
+        // 5区画×2区画の黒塗り
+        (8, 8, 48, 24),
+        // 小さすぎる黒い点
+        (64, 8, 72, 16),
+        // 枠線だけの白塗り
+        (8, 40, 48, 41),
+        (8, 55, 48, 56),
+        (8, 40, 9, 56),
+        (47, 40, 48, 56),
+      ],
+    ))
+    .unwrap();
+    assert_eq!(
+      find_regions(&image),
+      vec![BBox {
+        x0: 8,
+        y0: 8,
+        x1: 48,
+        y1: 24
+      }]
+    );
+  }
+
+  #[test]
+  fn mark_regions() {
+    let mut page = hocr::parse(
+      "<p class='ocr_par'>\
+<span class='ocr_line' title='bbox 0 0 200 30'>\
+<span class='ocrx_word' title='bbox 0 0 40 30'>被告</span>\
+<span class='ocrx_word' title='bbox 120 0 200 30'>は</span></span>\
+<p class='ocr_par'>\
+<span class='ocr_line' title='bbox 0 200 200 230'>\
+<span class='ocrx_word' title='bbox 0 200 200 230'>主文</span></span>",
+    );
+    let inline = BBox {
+      x0: 50,
+      y0: 5,
+      x1: 110,
+      y1: 25,
+    };
+    let between = BBox {
+      x0: 0,
+      y0: 100,
+      x1: 200,
+      y1: 140,
+    };
+    mark(&mut page, &[inline, between]);
+    assert_eq!(page.text(), "被告[redacted]は\n\n[redacted]\n\n主文");
+  }
+}
@@ -0,0 +1,86 @@
+//! 見本のPDFファイルを処理した結果を、保存しておいた正解のテキストファイルと比べる
+
+use crate::{
+  diff::{self, Edit},
+  extract_text, fetch_pdf, plain_text,
+  report::Timings,
+  sections,
+  sidecar::Sidecar,
+  Args, Context,
+};
+use anyhow::{anyhow, Result};
+use std::path::Path;
+use tokio::fs;
+
+fn print_diff(golden_path: &str, expected: &str, actual: &str) {
+  let expected = expected.lines().collect::<Vec<_>>();
+  let actual = actual.lines().collect::<Vec<_>>();
+  println!("--- {golden_path}");
+  println!("+++ (出力)");
+  for edit in diff::edits(&expected, &actual) {
+    match edit {
+      Edit::Removed(i) => println!("-{:>5}: {}", i + 1, expected[i]),
+      Edit::Added(j) => println!("+{:>5}: {}", j + 1, actual[j]),
+    }
+  }
+}
+
+/// `corpus`の`{name}.pdf`を処理し、プレーンテキストの出力を`{name}.txt`と比べる
+///
+/// 一致しないものがあれば差分を表示してエラーを返す `update`の場合は正解のテキストファイルを出力で置き換える
+pub async fn run(corpus: &str, update: bool, args: &Args) -> Result<()> {
+  fs::create_dir_all(&args.tmp).await?;
+  fs::create_dir_all(args.err_dir()).await?;
+  let context = Context::new(args).await?;
+  let mut names = Vec::new();
+  let mut dir = fs::read_dir(corpus).await?;
+  while let Some(entry) = dir.next_entry().await? {
+    let path = entry.path();
+    if path.extension().map(|ext| ext == "pdf").unwrap_or(false) {
+      if let Some(stem) = path.file_stem() {
+        names.push(stem.to_string_lossy().to_string());
+      }
+    }
+  }
+  names.sort();
+  let corpus_path = Path::new(corpus).canonicalize()?;
+  let mut failed = Vec::new();
+  for name in &names {
+    let url = format!("file://{}/{name}.pdf", corpus_path.display());
+    let mut sidecar = Sidecar::default();
+    fetch_pdf(name, &[url], true, args, &context).await?;
+    let mut txt = extract_text(name, args, &context, &mut sidecar, &mut Timings::default()).await?;
+    if args.drop_appendix || args.separate_appendix {
+      (txt, _) = sections::split_off_appendices(&txt);
+    }
+    let actual = plain_text(&txt, args);
+    let golden_path = format!("{corpus}/{name}.txt");
+    let expected = fs::read_to_string(&golden_path).await.ok();
+    if expected.as_deref() == Some(actual.as_str()) {
+      println!("[OK] regress: {name}");
+      continue;
+    }
+    if update {
+      fs::write(&golden_path, &actual).await?;
+      println!("[Update] regress: {golden_path}");
+      continue;
+    }
+    println!("[NG] regress: {name}");
+    match &expected {
+      Some(expected) => print_diff(&golden_path, expected, &actual),
+      None => println!("正解のテキストファイルが無い: {golden_path}"),
+    }
+    failed.push(name.clone());
+  }
+  if failed.is_empty() {
+    println!("[END] regress: {}件", names.len());
+    Ok(())
+  } else {
+    Err(anyhow!(
+      "regress: {}件中{}件の出力が正解と一致しない: {}",
+      names.len(),
+      failed.len(),
+      failed.join(", ")
+    ))
+  }
+}
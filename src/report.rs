@@ -0,0 +1,264 @@
+//! 実行した結果を事件ごとに記録し、出力フォルダに`report.json`として書き出す
+
+use crate::{consistency::Mismatch, html::escape, suspicious::SuspiciousPage};
+use anyhow::Result;
+use serde::{Serialize, Serializer};
+use std::time::Duration;
+use tokio::fs;
+
+const REPORT_FILE_NAME: &str = "report.json";
+
+const SUMMARY_FILE_NAME: &str = "summary.csv";
+
+const HTML_REPORT_FILE_NAME: &str = "index.html";
+
+/// `--html-report`の際に最初のページの縮小画像を置く、出力フォルダの中のフォルダ
+pub const THUMBNAIL_DIR: &str = "thumbnails";
+
+const HTML_REPORT_STYLE: &str = "body{font-family:sans-serif;margin:1em}\
+table{border-collapse:collapse}\
+td,th{border:1px solid #ccc;padding:.3em;vertical-align:top;text-align:left}\
+img{max-width:160px;border:1px solid #888}\
+.snippet{max-width:40em;white-space:pre-wrap;font-size:small}\
+.failed,.interrupted{background:#fdd}\
+.disagrees{background:#ffd}\
+.too_large,.excluded,.before_since{background:#eee}";
+
+/// 事件ごとの処理の結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+  /// 出力ファイルを書き出した
+  Written,
+  /// 処理したが出力ファイルの内容が変わらなかったため書き出さなかった
+  Unchanged,
+  /// 生成済みの出力ファイルがあるため処理しなかった
+  Skipped,
+  /// `--exclude-list`に含まれるか、`--court`の裁判所ではないため処理しなかった
+  Excluded,
+  /// 判決日が`--since`より前のため処理しなかった
+  BeforeSince,
+  /// PDFファイルが`--max-pdf-size`より大きいため処理しなかった
+  TooLarge,
+  /// 処理に失敗したため、ファイルを`--quarantine`のフォルダに移した
+  Failed,
+  /// Ctrl+Cで中断したため出力ファイルを書き出さなかった
+  Interrupted,
+}
+
+/// 事件ごとの段階ごとにかかった時間（ミリ秒）
+///
+/// `total`は同時に処理する数の制限で待った時間も含む
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Timings {
+  /// PDFファイルのダウンロード
+  #[serde(rename = "download_ms", serialize_with = "serialize_millis")]
+  pub download: Duration,
+  /// pdftotext・pdftoppmなどによるPDFファイルからテキストや画像への変換と画像の加工
+  #[serde(rename = "conversion_ms", serialize_with = "serialize_millis")]
+  pub conversion: Duration,
+  /// tesseractによるOCR
+  #[serde(rename = "ocr_ms", serialize_with = "serialize_millis")]
+  pub ocr: Duration,
+  /// 抽出したテキストの加工と出力ファイルの書き出し
+  #[serde(rename = "postprocess_ms", serialize_with = "serialize_millis")]
+  pub postprocess: Duration,
+  #[serde(rename = "total_ms", serialize_with = "serialize_millis")]
+  pub total: Duration,
+}
+
+fn serialize_millis<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+  serializer.serialize_u128(duration.as_millis())
+}
+
+/// 処理した事件の詳細
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CaseDetails {
+  pub timings: Timings,
+  /// PDFファイルのページ数
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub pages: Option<usize>,
+  /// 抽出したテキストの語のうち、日本語の文書に現れにくい語ではないものの割合（0〜1）
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub quality: Option<f64>,
+  /// `--flag-suspicious-pages`の際に、OCRの結果が崩れているとみられるページ
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub suspicious_pages: Vec<SuspiciousPage>,
+  /// `--check-consistency`の際に、一覧のJSONファイルの情報がテキストに見つからなかった項目
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub mismatches: Vec<Mismatch>,
+  /// `p2t`の際に、pdftotextの出力が文字化けしていたためOCRで処理した
+  #[serde(skip_serializing_if = "std::ops::Not::not")]
+  pub text_layer_unusable: bool,
+  /// `--mode both`の際の、pdftotextとOCRのテキストの文字の2-gramの重なり（0〜1）
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub similarity: Option<f64>,
+  /// `similarity`が`--disagreement-threshold`より小さい
+  #[serde(skip_serializing_if = "std::ops::Not::not")]
+  pub disagrees: bool,
+  /// `--html-report`の際に表示する、出力したテキストの冒頭
+  #[serde(skip)]
+  pub snippet: Option<String>,
+  /// `--mode both`の際に、方法ごとに抽出したテキストを比べるまで持っておく
+  #[serde(skip)]
+  pub text: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CaseReport {
+  pub name: String,
+  pub status: Status,
+  /// 処理しなかった事件には無い
+  #[serde(flatten)]
+  pub details: Option<CaseDetails>,
+}
+
+/// 処理した順に並べた事件ごとの結果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Report {
+  pub cases: Vec<CaseReport>,
+}
+
+impl Report {
+  pub fn record(&mut self, name: &str, status: Status, details: Option<CaseDetails>) {
+    self.cases.push(CaseReport {
+      name: name.to_string(),
+      status,
+      details,
+    });
+  }
+
+  pub async fn write(&self, output_name: &str) -> Result<()> {
+    let s = serde_json::to_string_pretty(self)?;
+    fs::write(format!("{output_name}/{REPORT_FILE_NAME}"), s).await?;
+    Ok(())
+  }
+
+  /// 表計算ソフトで確かめられるように、事件ごとに1行の`summary.csv`を書き出す
+  ///
+  /// `output_path`は事件の名前から出力ファイルのpathを求める。出力ファイルの無い事件と処理しなかった項目は空にする
+  pub async fn write_csv(
+    &self,
+    output_name: &str,
+    mode: &str,
+    output_path: impl Fn(&str) -> String,
+  ) -> Result<()> {
+    let mut s = String::from(
+      "name,status,mode,pages,download_ms,conversion_ms,ocr_ms,postprocess_ms,total_ms,quality,output\n",
+    );
+    for case in &self.cases {
+      let status = serde_json::to_value(case.status)?;
+      let details = case.details.as_ref();
+      let millis = |duration: fn(&Timings) -> Duration| {
+        details
+          .map(|details| duration(&details.timings).as_millis().to_string())
+          .unwrap_or_default()
+      };
+      let has_output = matches!(
+        case.status,
+        Status::Written | Status::Unchanged | Status::Skipped
+      );
+      let row = [
+        case.name.clone(),
+        status.as_str().unwrap_or_default().to_string(),
+        mode.to_string(),
+        details
+          .and_then(|details| details.pages)
+          .map(|pages| pages.to_string())
+          .unwrap_or_default(),
+        millis(|timings| timings.download),
+        millis(|timings| timings.conversion),
+        millis(|timings| timings.ocr),
+        millis(|timings| timings.postprocess),
+        millis(|timings| timings.total),
+        details
+          .and_then(|details| details.quality)
+          .map(|quality| format!("{quality:.3}"))
+          .unwrap_or_default(),
+        if has_output {
+          output_path(&case.name)
+        } else {
+          String::new()
+        },
+      ];
+      let row = row.iter().map(|field| csv_field(field)).collect::<Vec<_>>();
+      s.push_str(&row.join(","));
+      s.push('\n');
+    }
+    fs::write(format!("{output_name}/{SUMMARY_FILE_NAME}"), s).await?;
+    Ok(())
+  }
+}
+
+impl Report {
+  /// 事件ごとの結果・最初のページの縮小画像・テキストの冒頭を並べた`index.html`を書き出す
+  ///
+  /// `output_file_name`は事件の名前から出力フォルダの中の出力ファイルの名前を求める
+  pub async fn write_html(
+    &self,
+    output_name: &str,
+    output_file_name: impl Fn(&str) -> String,
+  ) -> Result<()> {
+    let mut s = format!(
+      "<!DOCTYPE html>\n<html lang=\"ja\">\n<head>\n<meta charset=\"utf-8\">\n<title>report</title>\n<style>{HTML_REPORT_STYLE}</style>\n</head>\n<body>\n<table>\n<tr><th></th><th>name</th><th>status</th><th>pages</th><th>quality</th><th>text</th></tr>\n"
+    );
+    for case in &self.cases {
+      let status = serde_json::to_value(case.status)?;
+      let status = status.as_str().unwrap_or_default();
+      let details = case.details.as_ref();
+      let thumbnail = format!("{THUMBNAIL_DIR}/{}.jpg", case.name);
+      let thumbnail = if fs::metadata(format!("{output_name}/{thumbnail}"))
+        .await
+        .is_ok()
+      {
+        format!(
+          "<img src=\"{}\" alt=\"\" loading=\"lazy\">",
+          escape(&thumbnail)
+        )
+      } else {
+        String::new()
+      };
+      let name = if matches!(
+        case.status,
+        Status::Written | Status::Unchanged | Status::Skipped
+      ) {
+        format!(
+          "<a href=\"{}\">{}</a>",
+          escape(&output_file_name(&case.name)),
+          escape(&case.name)
+        )
+      } else {
+        escape(&case.name)
+      };
+      let disagrees = if details.is_some_and(|details| details.disagrees) {
+        " disagrees"
+      } else {
+        ""
+      };
+      s.push_str(&format!(
+        "<tr class=\"{status}{disagrees}\"><td>{thumbnail}</td><td>{name}</td><td>{status}</td><td>{}</td><td>{}</td><td class=\"snippet\">{}</td></tr>\n",
+        details
+          .and_then(|details| details.pages)
+          .map(|pages| pages.to_string())
+          .unwrap_or_default(),
+        details
+          .and_then(|details| details.quality)
+          .map(|quality| format!("{quality:.3}"))
+          .unwrap_or_default(),
+        escape(details.and_then(|details| details.snippet.as_deref()).unwrap_or_default()),
+      ));
+    }
+    s.push_str("</table>\n</body>\n</html>\n");
+    fs::write(format!("{output_name}/{HTML_REPORT_FILE_NAME}"), s).await?;
+    Ok(())
+  }
+}
+
+/// カンマ・二重引用符・改行を含む項目は二重引用符で囲む
+fn csv_field(field: &str) -> String {
+  if field.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", field.replace('"', "\"\""))
+  } else {
+    field.to_string()
+  }
+}
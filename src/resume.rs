@@ -0,0 +1,95 @@
+//! 途中で止まった事件のOCRを、以前の実行でOCRを終えたページから続ける
+//!
+//! ページごとにOCRを終えるたびに、PDFファイルのSHA-256と終えたページを`{tmp}/{事件の名前}.ocr.json`に記録する。
+//! PDFファイルが変わっていなければ、記録されていてテキストファイルが残っているページはOCRし直さない。
+//! 事件のOCRを全て終えたら記録を削除するため、次に処理し直す場合は全てのページをOCRし直す
+
+use crate::{cache, hocr::BBox, sidecar};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tokio::fs;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Record {
+  pdf_sha256: String,
+  /// OCRを終えたページとそのページで見つけた黒塗りの領域
+  pages: BTreeMap<usize, Vec<BBox>>,
+}
+
+/// 1つの事件のOCRを終えたページ
+pub struct Pages {
+  /// `{tmp}/{事件の名前}`
+  file_name: String,
+  record: Record,
+}
+
+impl Pages {
+  fn path(file_name: &str) -> String {
+    format!("{file_name}.ocr.json")
+  }
+
+  /// `file_name`は`{tmp}/{事件の名前}` PDFファイルが記録と異なる場合は何も終えていないものとする
+  pub async fn load(file_name: &str) -> Result<Self> {
+    let pdf_sha256 = cache::sha256_hex(&cache::read(&format!("{file_name}.pdf")).await?);
+    let record = fs::read_to_string(Self::path(file_name))
+      .await
+      .ok()
+      .and_then(|s| serde_json::from_str::<Record>(&s).ok())
+      .filter(|record| record.pdf_sha256 == pdf_sha256)
+      .unwrap_or(Record {
+        pdf_sha256,
+        pages: BTreeMap::new(),
+      });
+    Ok(Pages {
+      file_name: file_name.to_string(),
+      record,
+    })
+  }
+
+  /// 以前の実行でそのページのOCRを終えていて、テキストファイルも残っているか
+  pub fn is_done(&self, page_num: usize) -> bool {
+    self.record.pages.contains_key(&page_num)
+      && cache::exists(&format!("{}-{page_num}.txt", self.file_name))
+  }
+
+  /// 以前の実行でOCRを終えていれば、そのページの黒塗りの領域を`sidecar`に加えて`true`を返す
+  pub fn resume(&self, page_num: usize, sidecar: &mut sidecar::Sidecar) -> bool {
+    if !self.is_done(page_num) {
+      return false;
+    }
+    let redactions = &self.record.pages[&page_num];
+    sidecar
+      .redactions
+      .extend(redactions.iter().map(|bbox| sidecar::Redaction {
+        page: page_num,
+        bbox: *bbox,
+      }));
+    true
+  }
+
+  /// ページのOCRを終えたことを記録する
+  pub async fn done(&mut self, page_num: usize, sidecar: &sidecar::Sidecar) -> Result<()> {
+    let redactions = sidecar
+      .redactions
+      .iter()
+      .filter(|redaction| redaction.page == page_num)
+      .map(|redaction| redaction.bbox)
+      .collect();
+    self.record.pages.insert(page_num, redactions);
+    let path = Self::path(&self.file_name);
+    let part_path = format!("{path}.part");
+    fs::write(&part_path, serde_json::to_string(&self.record)?).await?;
+    fs::rename(&part_path, &path).await?;
+    Ok(())
+  }
+
+  /// 事件のOCRを全て終えたので記録を削除する
+  pub async fn finish(self) -> Result<()> {
+    let path = Self::path(&self.file_name);
+    if fs::metadata(&path).await.is_ok() {
+      fs::remove_file(&path).await?;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,51 @@
+//! `--rewrite-rules`で指定するJSONファイルの、正規表現による置き換えを順に抽出したテキストに適用する
+//!
+//! サイトごとの細かな整形を、クレートを書き換えずに利用者が加えられるようにする
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::Deserialize;
+use tokio::fs;
+
+#[derive(Debug, Deserialize)]
+struct RuleDef {
+  /// 置き換える部分に一致する正規表現
+  find: String,
+  /// 置き換えた後の文字列 `$1`や`${name}`で捕獲したグループを使える
+  replace: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Rules {
+  rules: Vec<(Regex, String)>,
+}
+
+impl Rules {
+  /// `[{"find": "...", "replace": "..."}]`の形式のファイルを読み込む pathが無い場合は何も置き換えない
+  pub async fn load(path: Option<&str>) -> Result<Self> {
+    let Some(path) = path else {
+      return Ok(Rules::default());
+    };
+    let s = fs::read_to_string(path).await?;
+    let defs = serde_json::from_str::<Vec<RuleDef>>(&s)?;
+    let rules = defs
+      .into_iter()
+      .map(|def| {
+        let find = Regex::new(&def.find)
+          .map_err(|e| anyhow!("rewrite ruleの正規表現が不正: {}: {e}", def.find))?;
+        Ok((find, def.replace))
+      })
+      .collect::<Result<_>>()?;
+    Ok(Rules { rules })
+  }
+
+  /// 規則を書かれた順に適用する
+  pub fn apply(&self, text: &str) -> String {
+    self
+      .rules
+      .iter()
+      .fold(text.to_string(), |text, (find, replace)| {
+        find.replace_all(&text, replace.as_str()).into_owned()
+      })
+  }
+}
@@ -0,0 +1,202 @@
+//! `--respect-robots`の際に、PDFファイルのホストのrobots.txtに従ってダウンロードの可否と間隔を決める
+
+use anyhow::Result;
+use regex::Regex;
+use reqwest::{StatusCode, Url};
+use std::{collections::HashMap, time::Duration};
+use tokio::{
+  sync::Mutex,
+  time::{sleep_until, Instant},
+};
+
+/// robots.txtの`User-agent`と照合する名前
+pub const USER_AGENT: &str = env!("CARGO_PKG_NAME");
+
+/// 1つのホストのrobots.txtのうち、このソフトウェアに適用される規則
+#[derive(Debug, Clone, Default)]
+pub struct Rules {
+  /// `Allow`ならば`true`とした、pathの規則
+  rules: Vec<(String, bool)>,
+  crawl_delay: Option<Duration>,
+}
+
+/// `*`を任意の文字列に、末尾の`$`をpathの終わりにした正規表現にする
+fn pattern_regex(pattern: &str) -> Option<Regex> {
+  let (pattern, anchored) = match pattern.strip_suffix('$') {
+    Some(pattern) => (pattern, true),
+    None => (pattern, false),
+  };
+  let body = pattern
+    .split('*')
+    .map(regex::escape)
+    .collect::<Vec<_>>()
+    .join(".*");
+  let end = if anchored { "$" } else { "" };
+  Regex::new(&format!("^{body}{end}")).ok()
+}
+
+impl Rules {
+  /// 全てのpathを禁止する規則
+  fn disallow_all() -> Self {
+    Rules {
+      rules: vec![("/".to_string(), false)],
+      crawl_delay: None,
+    }
+  }
+
+  /// robots.txtを読み込み、`USER_AGENT`に一致するグループがあればその規則を、無ければ`*`のグループの規則を返す
+  pub fn parse(text: &str) -> Self {
+    // グループごとの`User-agent`の一覧と規則
+    let mut groups: Vec<(Vec<String>, Rules)> = Vec::new();
+    let mut is_agent_line = false;
+    for line in text.lines() {
+      let line = line.split('#').next().unwrap_or_default().trim();
+      let Some((key, value)) = line.split_once(':') else {
+        continue;
+      };
+      let key = key.trim().to_ascii_lowercase();
+      let value = value.trim();
+      if key == "user-agent" {
+        if !is_agent_line || groups.is_empty() {
+          groups.push((Vec::new(), Rules::default()));
+        }
+        if let Some((agents, _)) = groups.last_mut() {
+          agents.push(value.to_ascii_lowercase());
+        }
+        is_agent_line = true;
+        continue;
+      }
+      is_agent_line = false;
+      let Some((_, rules)) = groups.last_mut() else {
+        continue;
+      };
+      match key.as_str() {
+        "allow" if !value.is_empty() => rules.rules.push((value.to_string(), true)),
+        "disallow" if !value.is_empty() => rules.rules.push((value.to_string(), false)),
+        "crawl-delay" => {
+          rules.crawl_delay = value.parse::<f64>().ok().map(Duration::from_secs_f64);
+        }
+        _ => (),
+      }
+    }
+    let agent = USER_AGENT.to_ascii_lowercase();
+    let find = |f: &dyn Fn(&str) -> bool| {
+      groups
+        .iter()
+        .find(|(agents, _)| agents.iter().any(|a| f(a)))
+        .map(|(_, rules)| rules.clone())
+    };
+    find(&|a| a != "*" && agent.contains(a))
+      .or_else(|| find(&|a| a == "*"))
+      .unwrap_or_default()
+  }
+
+  /// 最も長く一致する規則に従う。同じ長さの場合は`Allow`を優先する
+  pub fn is_allowed(&self, path: &str) -> bool {
+    self
+      .rules
+      .iter()
+      .filter(|(pattern, _)| {
+        pattern_regex(pattern)
+          .map(|re| re.is_match(path))
+          .unwrap_or(false)
+      })
+      .max_by_key(|(pattern, allow)| (pattern.len(), *allow))
+      .map(|(_, allow)| *allow)
+      .unwrap_or(true)
+  }
+}
+
+struct Host {
+  rules: Rules,
+  /// 次にリクエストしてよい時刻
+  next: Instant,
+}
+
+/// ホストごとに取得したrobots.txtの規則と、`Crawl-delay`による次のリクエストの時刻
+#[derive(Default)]
+pub struct Robots {
+  hosts: Mutex<HashMap<String, Host>>,
+}
+
+impl Robots {
+  async fn fetch(client: &reqwest::Client, url: &Url) -> Result<Rules> {
+    let robots_url = url.join("/robots.txt")?;
+    crate::logging::info("Robots", None, &robots_url);
+    let response = client.get(robots_url).send().await?;
+    let status = response.status();
+    if status.is_success() {
+      Ok(Rules::parse(&response.text().await?))
+    } else if status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS {
+      // robots.txtが無い場合は制限が無いものとする
+      Ok(Rules::default())
+    } else {
+      // 取得できない場合は全てを禁止されたものとする
+      Ok(Rules::disallow_all())
+    }
+  }
+
+  /// `url`をダウンロードしてよいかを調べ、よい場合は`Crawl-delay`の間隔を空けるまで待つ
+  ///
+  /// `http`・`https`以外のURLは常にダウンロードしてよいとする
+  pub async fn check(&self, client: &reqwest::Client, url: &str) -> Result<bool> {
+    let Ok(url) = Url::parse(url) else {
+      return Ok(true);
+    };
+    if url.scheme() != "http" && url.scheme() != "https" {
+      return Ok(true);
+    }
+    let origin = url.origin().ascii_serialization();
+    let until = {
+      let mut hosts = self.hosts.lock().await;
+      if !hosts.contains_key(&origin) {
+        let rules = Self::fetch(client, &url).await?;
+        hosts.insert(
+          origin.clone(),
+          Host {
+            rules,
+            next: Instant::now(),
+          },
+        );
+      }
+      let host = hosts.get_mut(&origin).expect("追加済み");
+      let path = match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_string(),
+      };
+      if !host.rules.is_allowed(&path) {
+        return Ok(false);
+      }
+      let now = Instant::now();
+      let until = host.next.max(now);
+      host.next = until + host.rules.crawl_delay.unwrap_or_default();
+      until
+    };
+    sleep_until(until).await;
+    Ok(true)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn use_matching_group() {
+    let rules = Rules::parse(
+      "User-agent: *\nDisallow: /\n\nUser-agent: other\nUser-agent: pdf2txt_precedent\nDisallow: /app/private/\nCrawl-delay: 2\n",
+    );
+    assert!(rules.is_allowed("/app/files/hanrei_jp/1.pdf"));
+    assert!(!rules.is_allowed("/app/private/1.pdf"));
+    assert_eq!(rules.crawl_delay, Some(Duration::from_secs(2)));
+  }
+
+  #[test]
+  fn longest_match_wins() {
+    let rules = Rules::parse("User-agent: *\nDisallow: /app/\nAllow: /app/files/*.pdf$\n");
+    assert!(rules.is_allowed("/app/files/hanrei_jp/1.pdf"));
+    assert!(!rules.is_allowed("/app/files/hanrei_jp/1.pdf?x=1"));
+    assert!(!rules.is_allowed("/app/hanrei_jp/search"));
+    assert!(rules.is_allowed("/index.html"));
+  }
+}
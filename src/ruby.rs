@@ -0,0 +1,97 @@
+//! OCRの結果からルビ（ふりがな）の行を取り除く
+
+use crate::hocr::{BBox, Line, Page};
+
+/// 本文の行の高さに対してこの割合より低い行をルビの候補とする
+const RUBY_HEIGHT_RATIO: f64 = 0.6;
+
+fn is_kana(c: char) -> bool {
+  ('\u{3041}'..='\u{309F}').contains(&c) || ('\u{30A0}'..='\u{30FF}').contains(&c)
+}
+
+fn median_height(page: &Page) -> Option<u32> {
+  let mut heights = page
+    .lines()
+    .map(|line| line.bbox.height())
+    .collect::<Vec<_>>();
+  if heights.is_empty() {
+    return None;
+  }
+  heights.sort_unstable();
+  Some(heights[heights.len() / 2])
+}
+
+/// すぐ下に横方向で重なる行があるかどうか
+fn has_body_line_below(bbox: &BBox, body_height: u32, lines: &[BBox]) -> bool {
+  lines.iter().any(|other| {
+    other != bbox
+      && other.y0 >= bbox.y1.saturating_sub(bbox.height() / 2)
+      && other.y0 <= bbox.y1 + body_height
+      && other.x0 < bbox.x1
+      && bbox.x0 < other.x1
+  })
+}
+
+fn is_ruby_line(line: &Line, body_height: u32, lines: &[BBox]) -> bool {
+  let text = line.text();
+  let mut chars = text.chars().filter(|c| !c.is_whitespace()).peekable();
+  chars.peek().is_some()
+    && chars.all(is_kana)
+    && (line.bbox.height() as f64) < body_height as f64 * RUBY_HEIGHT_RATIO
+    && has_body_line_below(&line.bbox, body_height, lines)
+}
+
+/// 本文の行より十分に低く、かなのみで構成され、本文の行の直上にある行をルビとして取り除く
+///
+/// 取り除いた行の数を返す
+pub fn remove_ruby(page: &mut Page) -> usize {
+  let Some(body_height) = median_height(page) else {
+    return 0;
+  };
+  let lines = page.lines().map(|line| line.bbox).collect::<Vec<_>>();
+  let mut removed = 0;
+  for par in page.paragraphs.iter_mut() {
+    let len = par.lines.len();
+    par
+      .lines
+      .retain(|line| !is_ruby_line(line, body_height, &lines));
+    removed += len - par.lines.len();
+  }
+  removed
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::hocr;
+
+  fn line((x0, y0, x1, y1): (u32, u32, u32, u32), text: &str) -> String {
+    format!(
+      "<span class='ocr_line' title='bbox {x0} {y0} {x1} {y1}'>\
+<span class='ocrx_word' title='bbox {x0} {y0} {x1} {y1}; x_wconf 90'>{text}</span></span>\n"
+    )
+  }
+
+  #[test]
+  fn remove_ruby_above_body() {
+    let hocr = [
+      "<p class='ocr_par'>\n".to_string(),
+      // 本文の直上のふりがな
+      line((100, 80, 400, 96), "ほんけんこうそ"),
+      line((100, 100, 900, 140), "本件控訴を棄却する。"),
+      // 本文の直上でも、かなのみではない小さな文字
+      line((100, 144, 300, 156), "注記あり"),
+      line((100, 160, 900, 200), "控訴費用は控訴人の負担とする。"),
+      // 直下に本文の無い、かなのみの小さな行
+      line((100, 300, 300, 316), "かな"),
+      line((100, 400, 900, 440), "事実及び理由"),
+    ]
+    .concat();
+    let mut page = hocr::parse(&hocr);
+    assert_eq!(remove_ruby(&mut page), 1);
+    assert_eq!(
+      page.text(),
+      "本件控訴を棄却する。\n注記あり\n控訴費用は控訴人の負担とする。\nかな\n事実及び理由"
+    );
+  }
+}
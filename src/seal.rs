@@ -0,0 +1,107 @@
+//! 判決文の最初と最後のページにある認証文・受付印・透かしの文字を取り除く
+
+use crate::output::PAGE_BREAK;
+use anyhow::{anyhow, Result};
+use regex::Regex;
+
+/// 「これは正本である。」のような認証文の始まりの行
+const CERTIFICATION_PATTERNS: [&str; 2] = [r"[正謄抄]本である", r"原本と相違ない"];
+
+/// 認証文の始まりの行に続く日付・裁判所名・書記官名の行の最大数
+const CERTIFICATION_MAX_LINES: usize = 6;
+
+/// 認証文に続く行とみなす最大文字数
+const CERTIFICATION_LINE_MAX_CHARS: usize = 30;
+
+/// 受付印・受領印の行
+const STAMP_PATTERNS: [&str; 1] = [
+  r"^[\s　]*(?:(?:明治|大正|昭和|平成|令和)?[\s　]*[0-9０-９元]+[\s　]*[年.．][\s　]*[0-9０-９]+[\s　]*[月.．][\s　]*[0-9０-９]+[\s　]*日?[\s　]*)?(?:受付|受領|受理)印?[\s　]*$",
+];
+
+/// 透かしの文字の行
+const WATERMARK_PATTERNS: [&str; 1] =
+  [r"^[\s　]*(?:複製禁止|無断転載禁止|部外秘|COPY|ＣＯＰＹ|写)[\s　]*$"];
+
+/// 取り除く行に一致する正規表現
+#[derive(Debug, Clone)]
+pub struct Patterns {
+  certification: Vec<Regex>,
+  /// 受付印・透かしと利用者が指定したもの
+  stamp: Vec<Regex>,
+}
+
+impl Patterns {
+  /// `extra`は設定ファイルで指定された、最初と最後のページから取り除く行の正規表現
+  pub fn new(extra: &[String]) -> Result<Self> {
+    let compile =
+      |p: &str| Regex::new(p).map_err(|e| anyhow!("seal patternの正規表現が不正: {p}: {e}"));
+    Ok(Patterns {
+      certification: CERTIFICATION_PATTERNS
+        .iter()
+        .map(|p| compile(p))
+        .collect::<Result<_>>()?,
+      stamp: STAMP_PATTERNS
+        .iter()
+        .chain(WATERMARK_PATTERNS.iter())
+        .copied()
+        .chain(extra.iter().map(|s| s.as_str()))
+        .map(compile)
+        .collect::<Result<_>>()?,
+    })
+  }
+}
+
+/// 認証文の始まりの行から、続く短い行までを取り除く
+fn remove_certification(lines: &mut Vec<&str>, patterns: &Patterns) -> usize {
+  let Some(start) = lines
+    .iter()
+    .position(|line| patterns.certification.iter().any(|re| re.is_match(line)))
+  else {
+    return 0;
+  };
+  let mut end = start + 1;
+  while end < lines.len()
+    && end - start <= CERTIFICATION_MAX_LINES
+    && lines[end].trim().chars().count() <= CERTIFICATION_LINE_MAX_CHARS
+    && !lines[end].trim_end().ends_with('。')
+  {
+    end += 1;
+  }
+  lines.drain(start..end);
+  end - start
+}
+
+fn remove_stamps(lines: &mut Vec<&str>, patterns: &Patterns) -> usize {
+  let len = lines.len();
+  lines.retain(|line| !patterns.stamp.iter().any(|re| re.is_match(line)));
+  len - lines.len()
+}
+
+/// 最初と最後のページから受付印・透かしの行を、最後のページから認証文を取り除く
+///
+/// 取り除いたテキストと行の数を返す
+pub fn suppress(text: &str, patterns: &Patterns) -> (String, usize) {
+  let pages = text.split(PAGE_BREAK).collect::<Vec<_>>();
+  // pdftotextは末尾にも改ページを出力するため、空のページは数えない
+  let last = pages
+    .iter()
+    .rposition(|page| !page.trim().is_empty())
+    .unwrap_or_default();
+  let mut removed = 0;
+  let pages = pages
+    .into_iter()
+    .enumerate()
+    .map(|(i, page)| {
+      if i != 0 && i != last {
+        return page.to_string();
+      }
+      let mut lines = page.split('\n').collect::<Vec<_>>();
+      if i == last {
+        removed += remove_certification(&mut lines, patterns);
+      }
+      removed += remove_stamps(&mut lines, patterns);
+      lines.join("\n")
+    })
+    .collect::<Vec<_>>();
+  (pages.join(&PAGE_BREAK.to_string()), removed)
+}
@@ -0,0 +1,114 @@
+//! 判決文を主文・事実及び理由・別紙などの部分に分ける
+
+/// 判決文の部分の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionKind {
+  /// 主文より前の事件番号・当事者などが書かれた部分
+  Header,
+  /// 主文
+  MainText,
+  /// 事実及び理由（古い判決では「事実」と「理由」）
+  FactsAndReasons,
+  /// 別紙・別表
+  Appendix,
+}
+
+impl SectionKind {
+  /// 部分ごとにファイルを分ける際のファイル名に付ける名前
+  pub fn file_suffix(&self) -> &'static str {
+    match self {
+      SectionKind::Header => "冒頭",
+      SectionKind::MainText => "主文",
+      SectionKind::FactsAndReasons => "事実及び理由",
+      SectionKind::Appendix => "別紙",
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Section {
+  pub kind: SectionKind,
+  pub text: String,
+}
+
+fn remove_whitespace(line: &str) -> String {
+  line.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// 主文の見出しの行かどうか
+pub fn is_main_text_heading(line: &str) -> bool {
+  remove_whitespace(line) == "主文"
+}
+
+fn is_facts_and_reasons_heading(line: &str) -> bool {
+  matches!(
+    remove_whitespace(line).as_str(),
+    "事実及び理由" | "事実" | "理由"
+  )
+}
+
+/// 別紙・別表の見出しの行かどうか
+///
+/// 「別紙」「（別紙１）」「別紙物件目録」「別表２」のように別紙か別表で始まる短い行とする
+fn is_appendix_heading(line: &str) -> bool {
+  let s = remove_whitespace(line);
+  let s = s.trim_start_matches(['(', '（']);
+  (s.starts_with("別紙") || s.starts_with("別表")) && s.chars().count() <= 12 && !s.contains('。')
+}
+
+/// 見出しの行であればその部分の種類を返す
+///
+/// `current`は直前の行が属する部分の種類で、主文より前の「事実」などは見出しとみなさない
+pub fn heading_kind(line: &str, current: SectionKind) -> Option<SectionKind> {
+  if is_appendix_heading(line) {
+    Some(SectionKind::Appendix)
+  } else if current == SectionKind::Appendix {
+    None
+  } else if is_main_text_heading(line) && current == SectionKind::Header {
+    Some(SectionKind::MainText)
+  } else if is_facts_and_reasons_heading(line) && current != SectionKind::Header {
+    Some(SectionKind::FactsAndReasons)
+  } else {
+    None
+  }
+}
+
+/// 見出しの行で判決文を分ける
+///
+/// 別紙が始まった後は、次の別紙・別表の見出しまでを一つの別紙とする
+pub fn split(text: &str) -> Vec<Section> {
+  let mut sections = vec![Section {
+    kind: SectionKind::Header,
+    text: String::new(),
+  }];
+  for line in text.lines() {
+    let current = sections
+      .last()
+      .map(|s| s.kind)
+      .unwrap_or(SectionKind::Header);
+    let next = heading_kind(line, current);
+    let is_new_section = next
+      .map(|kind| kind != current || kind == SectionKind::Appendix)
+      .unwrap_or(false);
+    if let Some(kind) = next.filter(|_| is_new_section) {
+      sections.push(Section {
+        kind,
+        text: String::new(),
+      });
+    }
+    let section = sections.last_mut().unwrap();
+    section.text.push_str(line);
+    section.text.push('\n');
+  }
+  sections.retain(|s| !s.text.trim().is_empty());
+  sections
+}
+
+/// 別紙・別表を取り除いたテキストと、取り除いた別紙・別表を返す
+pub fn split_off_appendices(text: &str) -> (String, Vec<Section>) {
+  let (body, appendices): (Vec<_>, Vec<_>) = split(text)
+    .into_iter()
+    .partition(|s| s.kind != SectionKind::Appendix);
+  let body = body.iter().map(|s| s.text.as_str()).collect::<String>();
+  (body, appendices)
+}
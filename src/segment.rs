@@ -0,0 +1,102 @@
+//! 判決文の項目番号（第1、1、(1)、ア、(ア)…）をもとにテキストを段落に分ける
+
+use regex::Regex;
+use serde::Serialize;
+
+/// 判決文で使われる項目番号の書式を上位のものから並べたもの
+///
+/// 括弧で囲まれていない番号は後ろに空白があるものに限る
+const NUMBERING_PATTERNS: [&str; 7] = [
+  r"第[0-9０-９一二三四五六七八九十百]+[\s　]+",
+  r"[0-9０-９]+[\s　]+",
+  r"[(（][0-9０-９]+[)）][\s　]*",
+  r"[ア-ン][\s　]+",
+  r"[(（][ア-ン][)）][\s　]*",
+  r"[a-zａ-ｚ][\s　]+",
+  r"[(（][a-zａ-ｚ][)）][\s　]*",
+];
+
+/// 見出しとみなす行の最大文字数
+const HEADING_MAX_CHARS: usize = 30;
+
+/// 項目番号で区切られた段落
+#[derive(Debug, Clone, Serialize)]
+pub struct Paragraph {
+  /// 入れ子の深さ 項目番号の無い冒頭部分は0
+  pub level: usize,
+  /// 段落の先頭にある項目番号
+  pub number: Option<String>,
+  pub text: String,
+}
+
+struct Numbering {
+  /// 書式の順位
+  rank: usize,
+  number: String,
+  /// 項目番号を除いた行の残り
+  rest: String,
+}
+
+fn parse_numbering(res: &[Regex], line: &str) -> Option<Numbering> {
+  res.iter().enumerate().find_map(|(rank, re)| {
+    let caps = re.captures(line)?;
+    Some(Numbering {
+      rank,
+      number: caps.name("number")?.as_str().trim().to_string(),
+      rest: caps.name("rest")?.as_str().trim().to_string(),
+    })
+  })
+}
+
+fn is_heading(paragraph: &Paragraph) -> bool {
+  paragraph.number.is_some()
+    && paragraph.text.chars().count() <= HEADING_MAX_CHARS
+    && !paragraph.text.contains(['。', '，', '、', ','])
+}
+
+fn is_continuation(prev: &Paragraph, line: &str) -> bool {
+  !line.starts_with('　') && !prev.text.ends_with('。') && !is_heading(prev)
+}
+
+/// テキストを段落に分け、項目番号から入れ子の深さを求める
+///
+/// 項目番号か字下げで始まる行、または句点で終わる行の次の行を新しい段落の始まりとする
+pub fn segment(text: &str) -> Vec<Paragraph> {
+  let res = NUMBERING_PATTERNS
+    .iter()
+    .map(|p| Regex::new(&format!(r"^[\s　]*(?P<number>{p})(?P<rest>.*)$")).unwrap())
+    .collect::<Vec<_>>();
+  let mut paragraphs: Vec<Paragraph> = Vec::new();
+  // 現在開いている項目番号の書式の順位
+  let mut rank_stack: Vec<usize> = Vec::new();
+  for line in text.lines() {
+    if line.trim().is_empty() {
+      continue;
+    }
+    if let Some(numbering) = parse_numbering(&res, line) {
+      while rank_stack
+        .last()
+        .map(|rank| *rank >= numbering.rank)
+        .unwrap_or(false)
+      {
+        rank_stack.pop();
+      }
+      rank_stack.push(numbering.rank);
+      paragraphs.push(Paragraph {
+        level: rank_stack.len(),
+        number: Some(numbering.number),
+        text: numbering.rest,
+      });
+      continue;
+    }
+    match paragraphs.last_mut() {
+      Some(prev) if is_continuation(prev, line) => prev.text.push_str(line.trim()),
+      _ => paragraphs.push(Paragraph {
+        level: rank_stack.len(),
+        number: None,
+        text: line.trim().to_string(),
+      }),
+    }
+  }
+  paragraphs
+}
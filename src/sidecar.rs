@@ -0,0 +1,71 @@
+//! 事件ごとの付加情報を記録するJSONファイル
+
+use crate::{
+  classify::Classification,
+  court::Court,
+  fallback::Attempt,
+  hocr::BBox,
+  labels::LabelIssue,
+  output,
+  parties::Parties,
+  precedent::{CaseNumber, PrecedentCitation},
+  segment::Paragraph,
+  statute::StatuteCitation,
+};
+use anyhow::Result;
+use serde::Serialize;
+
+/// 黒塗りされた領域
+#[derive(Debug, Clone, Serialize)]
+pub struct Redaction {
+  /// 1から始まるページ番号
+  pub page: usize,
+  /// 切り抜き後のページ画像での位置
+  pub bbox: BBox,
+}
+
+/// `--reocr-confidence`で画像を加工してOCRし直したページ
+#[derive(Debug, Clone, Serialize)]
+pub struct OcrEscalation {
+  /// 1から始まるページ番号
+  pub page: usize,
+  /// 最初のOCRの単語の信頼度の平均
+  pub confidence: f64,
+  /// OCRし直した結果の単語の信頼度の平均
+  pub retry_confidence: Option<f64>,
+  /// OCRし直した結果を使ったかどうか
+  pub adopted: bool,
+}
+
+/// 生成したテキストファイルと同じ名前で出力するJSONファイルの内容
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Sidecar {
+  /// `--classify-pdf`の際に、PDFファイルがスキャンしたものかどうかを判定したもの
+  pub pdf_kind: Option<Classification>,
+  /// `p2t`の際に、pdftotextの出力が文字化けしていたためOCRで処理した
+  pub text_layer_unusable: bool,
+  /// `--fallback-chain`の際に使った方法
+  pub engine: Option<String>,
+  /// `--fallback-chain`の際に試した方法と、質の検査の結果
+  pub engine_attempts: Vec<Attempt>,
+  /// `--normalize-court`の際に、一覧のJSONファイルの裁判所名を揃えたもの
+  pub court: Option<Court>,
+  /// `--parse-case-number`の際に、一覧のJSONファイルの事件番号を読んだもの
+  pub case_numbers: Vec<CaseNumber>,
+  pub redactions: Vec<Redaction>,
+  pub paragraphs: Vec<Paragraph>,
+  pub parties: Option<Parties>,
+  pub statute_citations: Vec<StatuteCitation>,
+  pub precedent_citations: Vec<PrecedentCitation>,
+  pub label_issues: Vec<LabelIssue>,
+  pub ocr_escalations: Vec<OcrEscalation>,
+}
+
+impl Sidecar {
+  /// `{name}.json`として出力フォルダに書き出す 書き出した場合は`true`を返す
+  pub async fn write(&self, output_name: &str, name: &str, compress: bool) -> Result<bool> {
+    let path = output::path(output_name, &format!("{name}.json"), compress);
+    let s = serde_json::to_string_pretty(self)?;
+    output::write(&path, s.as_bytes(), compress).await
+  }
+}
@@ -0,0 +1,49 @@
+//! `--mode both`の際に、pdftotextとOCRで抽出したテキストがどれだけ一致するかを求める
+//!
+//! 長い判決では編集距離を求めるのに時間がかかりすぎるため、空白を除いた文字の2-gramの重なり（Dice係数）を用いる。
+//! 片方にしか無い部分や、文字化け・誤認識した部分が多いほど小さくなる
+
+use std::collections::HashMap;
+
+fn bigrams(text: &str) -> HashMap<(char, char), usize> {
+  let chars = text
+    .chars()
+    .filter(|c| !c.is_whitespace())
+    .collect::<Vec<_>>();
+  let mut bigrams = HashMap::new();
+  for pair in chars.windows(2) {
+    *bigrams.entry((pair[0], pair[1])).or_insert(0) += 1;
+  }
+  bigrams
+}
+
+/// 2つのテキストの文字の2-gramの重なり（0〜1） どちらも空の場合は1とする
+pub fn similarity(a: &str, b: &str) -> f64 {
+  let (a, b) = (bigrams(a), bigrams(b));
+  let total = a.values().sum::<usize>() + b.values().sum::<usize>();
+  if total == 0 {
+    return 1.0;
+  }
+  let common = a
+    .iter()
+    .map(|(bigram, count)| (*count).min(b.get(bigram).copied().unwrap_or(0)))
+    .sum::<usize>();
+  2.0 * common as f64 / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn compare_texts() {
+    assert_eq!(
+      similarity("主文\n本件控訴を棄却する。", "主 文 本件控訴を棄却する。"),
+      1.0
+    );
+    assert_eq!(similarity("", ""), 1.0);
+    assert_eq!(similarity("本件控訴を棄却する。", ""), 0.0);
+    let garbled = similarity("本件控訴を棄却する。", "本件控訴を乗却する。");
+    assert!(0.5 < garbled && garbled < 1.0);
+  }
+}
@@ -0,0 +1,225 @@
+//! 判決文から法令の条文の引用（民法709条、会社法第423条第1項など）を取り出す
+
+use crate::number::{parse_number, NUMBER};
+use regex::Regex;
+use serde::Serialize;
+
+/// 引用された条文
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StatuteCitation {
+  /// 法令名
+  pub law: String,
+  /// 条
+  pub article: u32,
+  /// 枝番（第27条の2の3であれば`[2, 3]`）
+  pub article_branches: Vec<u32>,
+  /// 項
+  pub paragraph: Option<u32>,
+  /// 号
+  pub item: Option<u32>,
+  /// 判決文中での表記
+  pub text: String,
+  /// e-Gov法令検索の法令ID 対応付けられなかった場合は無い
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub law_id: Option<String>,
+  /// e-Gov法令検索で法令を表示するURL
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub url: Option<String>,
+}
+
+/// 法令名の末尾
+const LAW_SUFFIX: &str = r"法律|法|令|規則|条例";
+
+/// 条文の引用の間に入る接続の表現
+const CONNECTIVE: &str =
+  r"^[、，,・\s　]*(?:及び|並びに|又は|若しくは|ないし|から|まで)?[、，,・\s　]*$";
+
+fn parse_branches(s: &str, number_re: &Regex) -> Vec<u32> {
+  number_re
+    .find_iter(s)
+    .filter_map(|m| parse_number(m.as_str()))
+    .collect()
+}
+
+/// テキスト中の条文の引用を出現順に取り出す
+///
+/// 「同法」「同条」は直前の引用の法令・条として扱い、
+/// 「民法709条及び710条」のように接続の表現だけを挟んで続く条文は直前の引用と同じ法令として扱う
+pub fn extract(text: &str) -> Vec<StatuteCitation> {
+  let tail = format!(
+    r"(?P<branches>(?:の(?:{NUMBER}))*)(?:第?(?P<paragraph>{NUMBER})項)?(?:第?(?P<item>{NUMBER})号)?"
+  );
+  let re = Regex::new(&format!(
+    r"(?:(?P<law>同法|[\p{{Han}}\p{{Katakana}}ー]+?(?:{LAW_SUFFIX}))第?(?P<article>{NUMBER})条|(?P<same>同条)|第?(?P<bare>{NUMBER})条){tail}"
+  ))
+  .unwrap();
+  let number_re = Regex::new(NUMBER).unwrap();
+  let connective_re = Regex::new(CONNECTIVE).unwrap();
+  let mut citations: Vec<StatuteCitation> = Vec::new();
+  let mut last_end = 0;
+  for caps in re.captures_iter(text) {
+    let m = caps.get(0).unwrap();
+    let law = caps.name("law").map(|m| m.as_str());
+    let article = caps
+      .name("article")
+      .or_else(|| caps.name("bare"))
+      .and_then(|m| parse_number(m.as_str()));
+    let follows_previous =
+      !citations.is_empty() && connective_re.is_match(&text[last_end..m.start()]);
+    let prev = citations.last();
+    let (law, article, article_branches) = match (law, caps.name("same"), article) {
+      // 同法第N条
+      (Some("同法"), _, Some(article)) => match prev {
+        Some(prev) => (prev.law.clone(), article, Vec::new()),
+        None => continue,
+      },
+      // 法令名第N条
+      (Some(law), _, Some(article)) => (law.to_string(), article, Vec::new()),
+      // 同条第N項
+      (None, Some(_), None) => match prev {
+        Some(prev) if caps.name("paragraph").is_some() || caps.name("item").is_some() => (
+          prev.law.clone(),
+          prev.article,
+          prev.article_branches.clone(),
+        ),
+        _ => continue,
+      },
+      // 接続の表現に続く第N条
+      (None, None, Some(article)) if follows_previous => {
+        (prev.unwrap().law.clone(), article, Vec::new())
+      }
+      _ => continue,
+    };
+    let article_branches = match caps.name("branches") {
+      Some(branches) if !branches.as_str().is_empty() => {
+        parse_branches(branches.as_str(), &number_re)
+      }
+      _ => article_branches,
+    };
+    citations.push(StatuteCitation {
+      law,
+      article,
+      article_branches,
+      paragraph: caps
+        .name("paragraph")
+        .and_then(|m| parse_number(m.as_str())),
+      item: caps.name("item").and_then(|m| parse_number(m.as_str())),
+      text: m.as_str().to_string(),
+      law_id: None,
+      url: None,
+    });
+    last_end = m.end();
+  }
+  citations
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn citation(
+    law: &str,
+    article: u32,
+    paragraph: Option<u32>,
+    item: Option<u32>,
+  ) -> (String, u32, Option<u32>, Option<u32>) {
+    (law.to_string(), article, paragraph, item)
+  }
+
+  fn simplify(citations: &[StatuteCitation]) -> Vec<(String, u32, Option<u32>, Option<u32>)> {
+    citations
+      .iter()
+      .map(|c| (c.law.clone(), c.article, c.paragraph, c.item))
+      .collect()
+  }
+
+  #[test]
+  fn extract_kanji_article() {
+    let citations = extract("被告の行為は民法七〇九条の不法行為に当たる。");
+    assert_eq!(
+      simplify(&citations),
+      vec![citation("民法", 709, None, None)]
+    );
+    assert_eq!(citations[0].text, "民法七〇九条");
+  }
+
+  #[test]
+  fn extract_article_paragraph_item() {
+    let citations = extract("会社法第423条第1項に基づき、破産法第１６２条第１項第２号により");
+    assert_eq!(
+      simplify(&citations),
+      vec![
+        citation("会社法", 423, Some(1), None),
+        citation("破産法", 162, Some(1), Some(2)),
+      ]
+    );
+  }
+
+  #[test]
+  fn extract_article_branches() {
+    let citations = extract("独占禁止法第２７条の２の３");
+    assert_eq!(citations.len(), 1);
+    assert_eq!(citations[0].article, 27);
+    assert_eq!(citations[0].article_branches, vec![2, 3]);
+  }
+
+  #[test]
+  fn extract_law_name_with_katakana_and_suffixes() {
+    let citations =
+      extract("民事訴訟法施行規則第5条、地方公務員法施行令3条、東京都迷惑防止条例第２条");
+    assert_eq!(
+      simplify(&citations),
+      vec![
+        citation("民事訴訟法施行規則", 5, None, None),
+        citation("地方公務員法施行令", 3, None, None),
+        citation("東京都迷惑防止条例", 2, None, None),
+      ]
+    );
+  }
+
+  #[test]
+  fn resolve_same_law_and_article() {
+    let citations = extract("刑法２３５条及び同法２３６条１項、同条２項");
+    assert_eq!(
+      simplify(&citations),
+      vec![
+        citation("刑法", 235, None, None),
+        citation("刑法", 236, Some(1), None),
+        citation("刑法", 236, Some(2), None),
+      ]
+    );
+  }
+
+  #[test]
+  fn resolve_connected_articles() {
+    let citations = extract("民法709条、710条及び第715条第1項");
+    assert_eq!(
+      simplify(&citations),
+      vec![
+        citation("民法", 709, None, None),
+        citation("民法", 710, None, None),
+        citation("民法", 715, Some(1), None),
+      ]
+    );
+  }
+
+  #[test]
+  fn ignore_unconnected_bare_articles() {
+    let citations = extract("民法709条に基づく請求について、第5条の規定");
+    assert_eq!(
+      simplify(&citations),
+      vec![citation("民法", 709, None, None)]
+    );
+  }
+
+  #[test]
+  fn ignore_same_law_without_previous_citation() {
+    assert!(extract("同法第5条により").is_empty());
+    assert!(extract("同条第2項により").is_empty());
+  }
+
+  #[test]
+  fn ignore_text_without_citations() {
+    assert!(extract("原告の請求を棄却する。訴訟費用は原告の負担とする。").is_empty());
+  }
+}
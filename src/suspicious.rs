@@ -0,0 +1,114 @@
+//! OCRの結果が崩れているとみられるページを見つける
+//!
+//! ページのテキストを文字の種類（漢字・ひらがな・カタカナ・英字・数字・記号）が続く範囲ごとの語に分け、
+//! 日本語の文書に現れにくい語の割合を求める。
+//! 辞書を使う形態素解析（lindera・vibrato）は依存するクレートに加えられないため行わず、
+//! OCRの誤りでよく現れる次の語を、未知の語として数える
+//!
+//! - 日本語の文書で使われない文字を含む語
+//! - 前後を漢字に挟まれた1文字のカタカナ（「口」を「ロ」と誤るなど）
+//! - 前後を日本語の文字に挟まれた2文字以下の英字（「l」「Il」など）
+//! - 句読点・括弧以外の記号が2文字以上続く語
+//!
+//! 辞書を引かないため、別の漢字に誤った語（「原告」を「原吉」とするなど）は未知の語として数えられない
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+  Kanji,
+  Hiragana,
+  Katakana,
+  Latin,
+  Digit,
+  Punctuation,
+  Symbol,
+  Unexpected,
+}
+
+fn script(c: char) -> Script {
+  match c {
+    '、' | '。' | '，' | '．' | '・' | '「' | '」' | '『' | '』' | '（' | '）' | '(' | ')'
+    | '【' | '】' | '〔' | '〕' | '：' | '；' | ',' | '.' | ':' | ';' => Script::Punctuation,
+    '々' | '〆' | '\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}' => Script::Kanji,
+    '\u{3041}'..='\u{309F}' => Script::Hiragana,
+    'ー' | '\u{30A0}'..='\u{30FF}' | '\u{FF66}'..='\u{FF9F}' => Script::Katakana,
+    'a'..='z' | 'A'..='Z' | 'ａ'..='ｚ' | 'Ａ'..='Ｚ' => Script::Latin,
+    '0'..='9' | '０'..='９' => Script::Digit,
+    c if c.is_ascii_graphic() => Script::Symbol,
+    '\u{3000}'..='\u{303F}'
+    | '\u{FF01}'..='\u{FF65}'
+    | '\u{2010}'..='\u{2312}'
+    | '\u{2460}'..='\u{24FF}'
+    | '\u{25A0}'..='\u{25FF}' => Script::Symbol,
+    _ => Script::Unexpected,
+  }
+}
+
+fn is_japanese(script: Script) -> bool {
+  matches!(script, Script::Kanji | Script::Hiragana | Script::Katakana)
+}
+
+/// 崩れているとみられるページ
+#[derive(Debug, Clone, Serialize)]
+pub struct SuspiciousPage {
+  /// 1から始まるページ番号
+  pub page: usize,
+  /// 未知の語の割合
+  pub unknown_ratio: f64,
+}
+
+/// ページのテキストの語の数と、そのうち未知の語の数
+fn count_tokens(text: &str) -> (usize, usize) {
+  let mut tokens = Vec::<(Script, usize)>::new();
+  for c in text.chars().filter(|c| !c.is_whitespace()) {
+    let script = script(c);
+    match tokens.last_mut() {
+      Some((last, len)) if *last == script => *len += 1,
+      _ => tokens.push((script, 1)),
+    }
+  }
+  let unknown = (0..tokens.len())
+    .filter(|&i| {
+      let (script, len) = tokens[i];
+      let before = i.checked_sub(1).map(|i| tokens[i].0);
+      let after = tokens.get(i + 1).map(|token| token.0);
+      match script {
+        Script::Unexpected => true,
+        Script::Katakana => {
+          len == 1 && before == Some(Script::Kanji) && after == Some(Script::Kanji)
+        }
+        Script::Latin => {
+          len <= 2 && before.is_some_and(is_japanese) && after.is_some_and(is_japanese)
+        }
+        Script::Symbol => len >= 2,
+        _ => false,
+      }
+    })
+    .count();
+  (tokens.len(), unknown)
+}
+
+/// 未知の語の割合が`threshold`より大きいページを返す `pages`は1ページ目からのテキスト
+///
+/// 語が少ないページは割合がぶれやすいため調べない
+pub fn find<'a>(pages: impl Iterator<Item = &'a str>, threshold: f64) -> Vec<SuspiciousPage> {
+  const MIN_TOKENS: usize = 20;
+  pages
+    .enumerate()
+    .filter_map(|(i, text)| {
+      let (tokens, unknown) = count_tokens(text);
+      let unknown_ratio = unknown as f64 / tokens as f64;
+      (tokens >= MIN_TOKENS && unknown_ratio > threshold).then_some(SuspiciousPage {
+        page: i + 1,
+        unknown_ratio,
+      })
+    })
+    .collect()
+}
+
+/// テキスト全体の語のうち未知の語ではないものの割合（0〜1） 語が無い場合は`None`を返す
+pub fn quality(text: &str) -> Option<f64> {
+  let (tokens, unknown) = count_tokens(text);
+  (tokens > 0).then(|| 1.0 - unknown as f64 / tokens as f64)
+}
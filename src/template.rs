@@ -0,0 +1,75 @@
+//! `--header-template`・`--footer-template`で指定したファイルの内容を、出力ファイルの最初と最後に加える
+//!
+//! 公開するデータセットに利用条件や出典の表示を自動的に付けられるようにする。
+//! テンプレートの`{name}`は事件の名前、`{version}`はこのソフトウェアのバージョン、それ以外の`{項目名}`は入力のJSONファイルにある事件の情報に置き換える。
+//! `{{`と`}}`はそのまま`{`と`}`とする
+
+use anyhow::{anyhow, Result};
+use regex::{Captures, Regex};
+use serde_json::{Map, Value};
+use tokio::fs;
+
+pub struct Templates {
+  header: Option<String>,
+  footer: Option<String>,
+  placeholder: Regex,
+}
+
+async fn read(path: Option<&str>) -> Result<Option<String>> {
+  match path {
+    Some(path) => {
+      Ok(Some(fs::read_to_string(path).await.map_err(|e| {
+        anyhow!("テンプレートを読み込めない: {path}: {e}")
+      })?))
+    }
+    None => Ok(None),
+  }
+}
+
+impl Templates {
+  pub async fn load(header: Option<&str>, footer: Option<&str>) -> Result<Self> {
+    Ok(Templates {
+      header: read(header).await?,
+      footer: read(footer).await?,
+      placeholder: Regex::new(r"\{\{|\}\}|\{([^{}]+)\}")?,
+    })
+  }
+
+  /// 入力のJSONファイルに無い項目は空文字列にする
+  fn render(&self, template: &str, name: &str, metadata: &Map<String, Value>) -> String {
+    self
+      .placeholder
+      .replace_all(template, |caps: &Captures| match caps.get(1) {
+        None => caps[0][..1].to_string(),
+        Some(field) => match field.as_str() {
+          "name" => name.to_string(),
+          "version" => env!("CARGO_PKG_VERSION").to_string(),
+          field => match metadata.get(field) {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Null) | None => String::new(),
+            Some(v) => v.to_string(),
+          },
+        },
+      })
+      .into_owned()
+  }
+
+  /// `text`の最初と最後にテンプレートを加える
+  pub fn apply(&self, text: String, name: &str, metadata: &Map<String, Value>) -> String {
+    if self.header.is_none() && self.footer.is_none() {
+      return text;
+    }
+    let mut s = String::new();
+    if let Some(header) = &self.header {
+      s.push_str(&self.render(header, name, metadata));
+    }
+    s.push_str(&text);
+    if let Some(footer) = &self.footer {
+      if !s.is_empty() && !s.ends_with('\n') {
+        s.push('\n');
+      }
+      s.push_str(&self.render(footer, name, metadata));
+    }
+    s
+  }
+}
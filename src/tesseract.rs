@@ -0,0 +1,15 @@
+//! tesseractを呼び出すコマンドを作る
+
+use tokio::process::Command;
+
+/// `threads`が指定されている場合は、`OMP_THREAD_LIMIT`でtesseractが内部で使うスレッドの数を制限する
+///
+/// 複数のページや事件を並行して処理する場合に、tesseractがそれぞれCPUの数だけスレッドを使うと取り合いになるため
+pub fn command(threads: Option<usize>) -> Command {
+  let mut command = Command::new("tesseract");
+  command.kill_on_drop(true);
+  if let Some(threads) = threads {
+    command.env("OMP_THREAD_LIMIT", threads.to_string());
+  }
+  command
+}
@@ -0,0 +1,65 @@
+//! 全ての事件のダウンロードを合わせた速さを`--max-bandwidth`以下に抑える
+
+use serde::Serialize;
+use std::{str::FromStr, time::Duration};
+use tokio::{
+  sync::Mutex,
+  time::{sleep_until, Instant},
+};
+
+/// `--max-bandwidth`で指定する1秒あたりのバイト数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Bandwidth(pub u64);
+
+impl FromStr for Bandwidth {
+  type Err = String;
+  /// `2MB/s`・`500KB/s`・`1000000`のように指定する。`K`・`M`・`G`は1024倍ずつとする
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let err = || format!("`2MB/s`のように1秒あたりの大きさを指定してください: {s}");
+    let value = s.trim().trim_end_matches("/s");
+    let value = value.strip_suffix(['B', 'b']).unwrap_or(value);
+    let (number, unit) = match value.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+      Some((i, _)) => value.split_at(i),
+      None => (value, ""),
+    };
+    let multiplier = match unit.to_ascii_uppercase().as_str() {
+      "" => 1,
+      "K" => 1024,
+      "M" => 1024 * 1024,
+      "G" => 1024 * 1024 * 1024,
+      _ => return Err(err()),
+    };
+    match number.parse::<u64>() {
+      Ok(number) if number > 0 => Ok(Bandwidth(number * multiplier)),
+      _ => Err(err()),
+    }
+  }
+}
+
+/// 並行して行うダウンロードで共有する帯域の制限
+pub struct Throttle {
+  bandwidth: Bandwidth,
+  /// これまでに受け取った分を制限の速さで受け取り終える時刻
+  next: Mutex<Instant>,
+}
+
+impl Throttle {
+  pub fn new(bandwidth: Bandwidth) -> Self {
+    Throttle {
+      bandwidth,
+      next: Mutex::new(Instant::now()),
+    }
+  }
+
+  /// `bytes`バイトを受け取った後に呼び、制限の速さを超えないように待つ
+  pub async fn consume(&self, bytes: usize) {
+    let duration = Duration::from_secs_f64(bytes as f64 / self.bandwidth.0 as f64);
+    let until = {
+      let mut next = self.next.lock().await;
+      // しばらくダウンロードしていなかった間の分を後でまとめて使えないようにする
+      *next = (*next).max(Instant::now()) + duration;
+      *next
+    };
+    sleep_until(until).await;
+  }
+}
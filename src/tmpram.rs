@@ -0,0 +1,175 @@
+//! `--tmp-in-ram`の際に、ページの画像やテキストなどの途中のファイルをメモリ上のファイルシステム（tmpfs）に置く
+//!
+//! ネットワーク越しのファイルシステムでは画像の書き込みと削除に時間がかかるため、事件ごとの途中のファイルを
+//! tmpfsのフォルダで作り、事件を処理し終えたらフォルダごと削除する。キャッシュしたPDFファイルは`--tmp`に置いたまま
+//! シンボリックリンクで参照する。使う容量の見積もりを足すと上限を超える事件は、これまでどおり`--tmp`で処理する
+
+use serde::Serialize;
+use std::{
+  str::FromStr,
+  sync::Mutex,
+  time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::fs;
+
+/// tmpfsがマウントされていることの多いフォルダ
+const CANDIDATE_DIRS: [&str; 2] = ["/dev/shm", "/run/shm"];
+
+/// 1ページあたりのOCRの結果のテキスト・hOCR・記録のファイルの大きさの見積もり
+const PAGE_FILES_BYTES: u64 = 64 * 1024;
+
+/// `--tmp-in-ram`で指定するtmpfsに置く途中のファイルの合計の大きさの上限
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Size(pub u64);
+
+impl FromStr for Size {
+  type Err = String;
+  /// `1G`・`512MB`・`1000000`のように指定する。`K`・`M`・`G`は1024倍ずつとする
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let err = || format!("`512M`のように大きさを指定してください: {s}");
+    let value = s.trim();
+    let value = value.strip_suffix(['B', 'b']).unwrap_or(value);
+    let (number, unit) = match value.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+      Some((i, _)) => value.split_at(i),
+      None => (value, ""),
+    };
+    let multiplier = match unit.to_ascii_uppercase().as_str() {
+      "" => 1,
+      "K" => 1024,
+      "M" => 1024 * 1024,
+      "G" => 1024 * 1024 * 1024,
+      _ => return Err(err()),
+    };
+    match number.parse::<u64>() {
+      Ok(number) if number > 0 => Ok(Size(number * multiplier)),
+      _ => Err(err()),
+    }
+  }
+}
+
+/// `/proc/mounts`の内容から、`dir`がtmpfsかramfsのマウントポイントかを調べる
+fn is_memory_backed(mounts: &str, dir: &str) -> bool {
+  mounts.lines().any(|line| {
+    let mut fields = line.split_whitespace().skip(1);
+    fields.next() == Some(dir) && matches!(fields.next(), Some("tmpfs" | "ramfs"))
+  })
+}
+
+/// `--dpi`の解像度で変換したA4のページを展開した画像1枚の大きさと、ページごとのファイルの大きさから、
+/// 事件の途中のファイルの合計の大きさを見積もる
+///
+/// 画像は1ページずつ変換してOCRの後に削除するため、同時に置くのは1枚のみとする
+fn estimate(page_size: usize, dpi: u32) -> u64 {
+  let inches = 8.27 * 11.69;
+  let image_bytes = (inches * f64::from(dpi) * f64::from(dpi) * 3.0) as u64;
+  image_bytes + page_size as u64 * PAGE_FILES_BYTES
+}
+
+pub struct TmpRam {
+  /// 事件ごとのフォルダを作るフォルダ
+  root: String,
+  cap: u64,
+  /// 処理中の事件の見積もりの合計
+  used: Mutex<u64>,
+}
+
+impl TmpRam {
+  /// tmpfsのフォルダが見つからない場合は`None`を返す
+  pub async fn open(cap: Size) -> Option<Self> {
+    let mounts = fs::read_to_string("/proc/mounts").await.ok()?;
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR").ok();
+    let dir = CANDIDATE_DIRS
+      .iter()
+      .copied()
+      .chain(runtime_dir.as_deref())
+      .find(|dir| is_memory_backed(&mounts, dir))?;
+    // 設定ファイルを読み込み直した場合なども重ならないように、作った時刻も名前に含める
+    let nanos = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|d| d.as_nanos())
+      .unwrap_or_default();
+    let root = format!("{dir}/pdf2txt_precedent-{}-{nanos}", std::process::id());
+    fs::create_dir_all(&root).await.ok()?;
+    Some(TmpRam {
+      root,
+      cap: cap.0,
+      used: Mutex::new(0),
+    })
+  }
+
+  pub fn root(&self) -> &str {
+    &self.root
+  }
+
+  /// 事件`name`の途中のファイルを置くフォルダを作り、`file_path_pdf`へのシンボリックリンクを置く
+  ///
+  /// 見積もりを足すと上限を超える場合は`None`を返す
+  pub async fn enter(
+    &self,
+    name: &str,
+    file_path_pdf: &str,
+    page_size: usize,
+    dpi: u32,
+  ) -> std::io::Result<Option<Workspace<'_>>> {
+    let bytes = estimate(page_size, dpi);
+    {
+      let mut used = self.used.lock().unwrap();
+      if *used + bytes > self.cap {
+        return Ok(None);
+      }
+      *used += bytes;
+    }
+    // 先に作ることで、以降に失敗した場合もDropで見積もりを戻してフォルダを削除する
+    let workspace = Workspace {
+      dir: format!("{}/{name}", self.root),
+      bytes,
+      tmp_ram: self,
+    };
+    fs::create_dir_all(&workspace.dir).await?;
+    let target = fs::canonicalize(file_path_pdf).await?;
+    fs::symlink(target, format!("{}/{name}.pdf", workspace.dir)).await?;
+    Ok(Some(workspace))
+  }
+}
+
+impl Drop for TmpRam {
+  fn drop(&mut self) {
+    std::fs::remove_dir_all(&self.root).ok();
+  }
+}
+
+/// tmpfsに作った事件ごとのフォルダ 中断された場合も削除するため、Dropで同期的に削除する
+pub struct Workspace<'a> {
+  pub dir: String,
+  bytes: u64,
+  tmp_ram: &'a TmpRam,
+}
+
+impl Drop for Workspace<'_> {
+  fn drop(&mut self) {
+    std::fs::remove_dir_all(&self.dir).ok();
+    *self.tmp_ram.used.lock().unwrap() -= self.bytes;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_size() {
+    assert_eq!("1G".parse::<Size>(), Ok(Size(1024 * 1024 * 1024)));
+    assert_eq!("512MB".parse::<Size>(), Ok(Size(512 * 1024 * 1024)));
+    assert_eq!("1000".parse::<Size>(), Ok(Size(1000)));
+    assert!("0".parse::<Size>().is_err());
+    assert!("1T".parse::<Size>().is_err());
+  }
+
+  #[test]
+  fn memory_backed_mounts() {
+    let mounts = "tmpfs /dev/shm tmpfs rw,nosuid,nodev 0 0\n/dev/sda1 /run/shm ext4 rw 0 0\n";
+    assert!(is_memory_backed(mounts, "/dev/shm"));
+    assert!(!is_memory_backed(mounts, "/run/shm"));
+    assert!(!is_memory_backed(mounts, "/tmp"));
+  }
+}
@@ -0,0 +1,100 @@
+//! 出力するテキストの行を指定した幅で折り返す
+
+use serde::Serialize;
+use std::str::FromStr;
+
+/// 行頭に来てはいけない句読点や閉じ括弧
+const NO_LINE_START: &str =
+  "、。，．,.)）」』】〕〉》]］}｝・：；:;!！?？ー々ぁぃぅぇぉっゃゅょァィゥェォッャュョ";
+
+/// `--wrap`で指定する折り返しの幅
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Wrap {
+  /// 折り返さない
+  None,
+  /// 半角文字を1、全角文字を2と数えた幅で折り返す
+  Width(usize),
+}
+
+impl FromStr for Wrap {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    if s == "none" {
+      return Ok(Wrap::None);
+    }
+    match s.parse::<usize>() {
+      Ok(width) if width > 0 => Ok(Wrap::Width(width)),
+      _ => Err(format!("1以上の数かnoneを指定してください: {s}")),
+    }
+  }
+}
+
+/// 半角文字を1、それ以外を2とした表示幅
+fn char_width(c: char) -> usize {
+  if c.is_ascii() || ('\u{FF61}'..='\u{FF9F}').contains(&c) {
+    1
+  } else {
+    2
+  }
+}
+
+/// 続けて書かれた英数字は一つのまとまりとして扱い、途中で折り返さない
+fn tokens(line: &str) -> Vec<&str> {
+  let mut tokens = Vec::new();
+  let mut start = None;
+  for (i, c) in line.char_indices() {
+    let is_word_char = c.is_ascii() && !c.is_ascii_whitespace();
+    match (start, is_word_char) {
+      (None, true) => start = Some(i),
+      (Some(s), false) => {
+        tokens.push(&line[s..i]);
+        start = None;
+      }
+      _ => (),
+    }
+    if !is_word_char {
+      tokens.push(&line[i..i + c.len_utf8()]);
+    }
+  }
+  if let Some(s) = start {
+    tokens.push(&line[s..]);
+  }
+  tokens
+}
+
+fn wrap_line(line: &str, width: usize, s: &mut String) {
+  let mut current = String::new();
+  let mut current_width = 0;
+  for token in tokens(line) {
+    let token_width = token.chars().map(char_width).sum::<usize>();
+    let is_no_line_start = token.chars().all(|c| NO_LINE_START.contains(c));
+    if current_width + token_width > width && !current.is_empty() && !is_no_line_start {
+      s.push_str(current.trim_end());
+      s.push('\n');
+      current.clear();
+      current_width = 0;
+      if token.trim().is_empty() {
+        continue;
+      }
+    }
+    current.push_str(token);
+    current_width += token_width;
+  }
+  s.push_str(&current);
+  s.push('\n');
+}
+
+/// 各行を`wrap`の幅で折り返す
+///
+/// 句読点や閉じ括弧は行頭に置かず、英数字の語は途中で分けない
+pub fn wrap(text: &str, wrap: Wrap) -> String {
+  let Wrap::Width(width) = wrap else {
+    return text.to_string();
+  };
+  let mut s = String::new();
+  for line in text.lines() {
+    wrap_line(line, width, &mut s);
+  }
+  s
+}
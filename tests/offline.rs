@@ -0,0 +1,103 @@
+//! courts.go.jpに接続せずに、ダウンロードからテキストの出力までを通して動かす
+//!
+//! `tests/fixtures`のPDFファイルを`file://`のURLと、テストの中で立ち上げるHTTPサーバから配信する
+
+use std::{
+  fs,
+  io::{BufRead, BufReader, Write},
+  net::TcpListener,
+  path::{Path, PathBuf},
+  process::Command,
+  thread,
+};
+
+const FIXTURE_PDF: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sample.pdf");
+
+const CASE_NAME: &str = "sample_2023_1_1_判決";
+
+/// テストごとに空の作業フォルダを作る
+fn work_dir(test_name: &str) -> PathBuf {
+  let dir = std::env::temp_dir().join(format!(
+    "pdf2txt_precedent_{test_name}_{}",
+    std::process::id()
+  ));
+  let _ = fs::remove_dir_all(&dir);
+  fs::create_dir_all(&dir).unwrap();
+  dir
+}
+
+/// 一件分の一覧のJSONファイルを書き出す
+fn write_input(dir: &Path, url: &str) -> PathBuf {
+  let path = dir.join("input.json");
+  let json =
+    format!(r#"{{"{CASE_NAME}": {{"case_number": "令和5(ワ)1", "full_pdf_link": "{url}"}}}}"#);
+  fs::write(&path, json).unwrap();
+  path
+}
+
+/// 1回だけリクエストを受け付けて`body`を返すHTTPサーバを立ち上げ、そのURLを返す
+fn serve_once(body: Vec<u8>) -> String {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let addr = listener.local_addr().unwrap();
+  thread::spawn(move || {
+    let (mut stream, _) = listener.accept().unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut line = String::new();
+    while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+      line.clear();
+    }
+    let header = format!(
+      "HTTP/1.1 200 OK\r\nContent-Type: application/pdf\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+      body.len()
+    );
+    stream.write_all(header.as_bytes()).unwrap();
+    stream.write_all(&body).unwrap();
+  });
+  format!("http://{addr}/sample.pdf")
+}
+
+fn run(dir: &Path, input: &Path) {
+  let status = Command::new(env!("CARGO_BIN_EXE_pdf2txt_precedent"))
+    .arg("--input")
+    .arg(input)
+    .arg("--tmp")
+    .arg(dir.join("tmp"))
+    .arg("--output")
+    .arg(dir.join("output"))
+    .status()
+    .unwrap();
+  assert!(status.success());
+}
+
+/// PDFファイルがキャッシュされ、マニフェストに記録され、テキストファイルが出力されていることを確かめる
+///
+/// `pdftotext`がある環境ではテキストの内容も確かめる
+fn assert_outputs(dir: &Path) {
+  let cached = fs::read(dir.join(format!("tmp/{CASE_NAME}.pdf"))).unwrap();
+  assert_eq!(cached, fs::read(FIXTURE_PDF).unwrap());
+  let manifest = fs::read_to_string(dir.join("tmp/manifest.json")).unwrap();
+  assert!(manifest.contains(CASE_NAME));
+  let txt = fs::read_to_string(dir.join(format!("output/{CASE_NAME}.txt"))).unwrap();
+  if Command::new("pdftotext").arg("-v").output().is_ok() {
+    assert!(txt.contains("Sample judgment text"));
+  }
+}
+
+#[test]
+fn download_from_file_url() {
+  let dir = work_dir("file_url");
+  let input = write_input(&dir, &format!("file://{FIXTURE_PDF}"));
+  run(&dir, &input);
+  assert_outputs(&dir);
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn download_from_local_http_stub() {
+  let dir = work_dir("http_stub");
+  let url = serve_once(fs::read(FIXTURE_PDF).unwrap());
+  let input = write_input(&dir, &url);
+  run(&dir, &input);
+  assert_outputs(&dir);
+  fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,48 @@
+//! `tests/fixtures`を見本として`regress`サブコマンドを動かす
+
+use std::process::Command;
+
+const CORPUS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+#[test]
+fn fixtures_match_golden_text() {
+  if Command::new("pdftotext").arg("-v").output().is_err() {
+    eprintln!("pdftotextが無いため省略");
+    return;
+  }
+  let tmp = std::env::temp_dir().join(format!("pdf2txt_precedent_regress_{}", std::process::id()));
+  let status = Command::new(env!("CARGO_BIN_EXE_pdf2txt_precedent"))
+    .arg("--tmp")
+    .arg(&tmp)
+    .arg("regress")
+    .arg("--corpus")
+    .arg(CORPUS)
+    .status()
+    .unwrap();
+  let _ = std::fs::remove_dir_all(&tmp);
+  assert!(status.success());
+}
+
+#[test]
+fn mismatch_fails_with_diff() {
+  let corpus = std::env::temp_dir().join(format!(
+    "pdf2txt_precedent_regress_mismatch_{}",
+    std::process::id()
+  ));
+  std::fs::create_dir_all(&corpus).unwrap();
+  std::fs::copy(format!("{CORPUS}/sample.pdf"), corpus.join("sample.pdf")).unwrap();
+  std::fs::write(corpus.join("sample.txt"), "別の内容\n").unwrap();
+  let output = Command::new(env!("CARGO_BIN_EXE_pdf2txt_precedent"))
+    .arg("--tmp")
+    .arg(corpus.join("tmp"))
+    .arg("regress")
+    .arg("--corpus")
+    .arg(&corpus)
+    .output()
+    .unwrap();
+  let _ = std::fs::remove_dir_all(&corpus);
+  assert!(!output.status.success());
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert!(stdout.contains("[NG] regress: sample"));
+  assert!(stdout.contains("-    1: 別の内容"));
+}